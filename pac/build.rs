@@ -0,0 +1,34 @@
+#![allow(non_camel_case_types)]
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+
+// assembles the rom patches of src/*.asm into tables of (address, bytes),
+// included by the board core, which has no assembler (nor allocator)
+
+use std::env;
+use std::fs;
+use std::path::Path;
+
+const patches: [&str; 1] = ["invincibility"];
+
+fn main() {
+    let out_dir = env::var("OUT_DIR").unwrap();
+    for name in patches {
+        let path = format!("src/{}.asm", name);
+        println!("cargo:rerun-if-changed={}", path);
+
+        let source = fs::read_to_string(&path).unwrap();
+        let blocks = match z80::z80_asm(&source) {
+            Ok(blocks) => blocks,
+            Err(e) => panic!("{}: {}", path, e),
+        };
+
+        let mut table = String::from("&[\n");
+        for block in blocks {
+            let bytes: Vec<String> = block.bytes.iter().map(|b| format!("0x{:02x}", b)).collect();
+            table += &format!("    (0x{:04x}, &[{}]),\n", block.org, bytes.join(", "));
+        }
+        table += "]\n";
+        fs::write(Path::new(&out_dir).join(format!("{}.rs", name)), table).unwrap();
+    }
+}
@@ -0,0 +1,64 @@
+#![allow(non_camel_case_types)]
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+
+use crate::*;
+
+// Crush Roller, on a plain Pac-Man board (based on MAME's init_maketrax).
+// The roms are not encrypted, but the game checks a protection device: it
+// drives bits 6 and 7 of the dip switch port and the whole of the second
+// one, with values depending on the address read and, at a few places, on
+// the instruction reading it.
+
+// the dip switch port, with the protection in bits 6 and 7
+fn protection_dsw(p: &pac::pac, offset: u16) -> u8 {
+    let dsw = p.dsw1;
+    if p.fetch_pc == 0x1973 || p.fetch_pc == 0x2389 {
+        return dsw | 0x40;
+    }
+    return match offset {
+        0x01 | 0x04 => dsw | 0x40,
+        0x05 => dsw | 0xc0,
+        _ => dsw & 0x3f,
+    };
+}
+
+// the second dip switch port, all of it protection
+fn protection_port(p: &pac::pac, offset: u16) -> u8 {
+    if p.fetch_pc == 0x040e {
+        return 0x20;
+    } else if p.fetch_pc == 0x115e || p.fetch_pc == 0x3ae2 {
+        return 0x00;
+    }
+    return match offset {
+        0x00 => 0x1f,
+        0x09 => 0x30,
+        0x0c => 0x00,
+        _ => 0x20,
+    };
+}
+
+pub fn crush_rb(userdata: &mut userdata, addr: u16) -> u8 {
+    //println!("crush_rb");
+
+    let a = addr & 0x7fff;
+    unsafe {
+        let p = &(*userdata.pac_ptr);
+        if a >= 0x5080 && a <= 0x50bf {
+            return protection_dsw(p, a - 0x5080);
+        } else if a >= 0x50c0 && a <= 0x50ff {
+            return protection_port(p, a - 0x50c0);
+        }
+    }
+    return pac_rb(userdata, addr);
+}
+
+// keeps the address of the instruction, for the protection
+pub fn crush_read_opcode(z: &mut z80::z80, addr: u16) -> u8 {
+    //println!("crush_read_opcode");
+
+    unsafe {
+        (*z.userdata.pac_ptr).fetch_pc = addr;
+    }
+    return (z.read_byte)(&mut z.userdata, addr);
+}
@@ -0,0 +1,264 @@
+#![allow(non_camel_case_types)]
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+
+// The board side of the debugger: breakpoints on pc, watchpoints on memory
+// ranges, stepping. While debugging, the cpu is traced before each
+// instruction, its memory accesses go through pac_debug_rb/pac_debug_wb and
+// its opcode fetches through pac_debug_fetch, which call the driver's. When
+// one of them stops the board, pac_update returns at once until the frontend
+// resumes it. The symbols (names of routines and variables) are loaded by
+// the frontend.
+
+use crate::*;
+
+pub const PAC_MAX_BREAKPOINTS: usize = 16;
+pub const PAC_MAX_WATCHPOINTS: usize = 16;
+
+// memory accesses from "start" to "end" (included) that stop the board
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct pac_watchpoint {
+    pub start: u16,
+    pub end: u16,
+    pub read: bool, // opcode fetches are reads too
+    pub write: bool,
+}
+
+// a name given to an address, or to a range of them (a table, a variable of
+// several bytes)
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct pac_symbol {
+    pub start: u16,
+    pub end: u16, // included
+    pub name: &'static str,
+    pub comment: &'static str,
+}
+
+// why the board stopped
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum pac_stop {
+    Break, // asked by the frontend
+    Step,
+    RunTo(u16),
+    Breakpoint(u16),
+    // after the instruction that made the access
+    Watchpoint { addr: u16, val: u8, write: bool },
+}
+
+pub struct pac_debug {
+    pub enabled: bool,
+    pub breakpoints: [Option<u16>; PAC_MAX_BREAKPOINTS],
+    pub watchpoints: [Option<pac_watchpoint>; PAC_MAX_WATCHPOINTS],
+    pub step: bool,          // stops before the next instruction
+    pub run_to: Option<u16>, // stops before the instruction at this address
+    pub stop: Option<pac_stop>,
+    pub resumed: bool, // the instruction at pc runs, even at a breakpoint
+    // called before each instruction, to print it, None if not tracing
+    pub trace: Option<fn(p: &mut pac::pac)>,
+    pub symbols: &'static [pac_symbol],
+}
+impl pac_debug {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            breakpoints: [None; PAC_MAX_BREAKPOINTS],
+            watchpoints: [None; PAC_MAX_WATCHPOINTS],
+            step: false,
+            run_to: None,
+            stop: None,
+            resumed: false,
+            trace: None,
+            symbols: &[],
+        }
+    }
+}
+
+// installs the debugger hooks on the cpu, or gives it back the driver's
+pub fn pac_debug_enable(p: &mut pac::pac, enabled: bool) {
+    //println!("pac_debug_enable");
+
+    p.debug.enabled = enabled;
+    if enabled {
+        p.cpu.read_byte = pac_debug_rb;
+        p.cpu.write_byte = pac_debug_wb;
+        p.cpu.read_opcode = pac_debug_fetch;
+        p.cpu.trace = Some(pac_debug_trace);
+    } else {
+        p.cpu.read_byte = p.driver.read_byte;
+        p.cpu.write_byte = p.driver.write_byte;
+        p.cpu.read_opcode = p.driver.read_opcode;
+        p.cpu.trace = None;
+        p.debug.stop = None;
+        p.debug.step = false;
+        p.debug.run_to = None;
+    }
+}
+
+// stops the board before the instruction at pc
+pub fn pac_debug_break(p: &mut pac::pac) {
+    //println!("pac_debug_break");
+
+    p.debug.stop = Some(pac_stop::Break);
+}
+
+// lets the board run again, from the instruction at pc
+pub fn pac_debug_resume(p: &mut pac::pac) {
+    //println!("pac_debug_resume");
+
+    p.debug.stop = None;
+    p.debug.resumed = true;
+}
+
+pub fn pac_debug_step(p: &mut pac::pac) {
+    //println!("pac_debug_step");
+
+    p.debug.step = true;
+    pac_debug_resume(p);
+}
+
+pub fn pac_debug_run_to(p: &mut pac::pac, addr: u16) {
+    //println!("pac_debug_run_to");
+
+    p.debug.run_to = Some(addr);
+    pac_debug_resume(p);
+}
+
+// puts "val" in a free slot. Returns false if there is none.
+fn add_slot<T: Copy + PartialEq>(slots: &mut [Option<T>], val: T) -> bool {
+    if slots.contains(&Some(val)) {
+        return true;
+    }
+    if let Some(slot) = slots.iter_mut().find(|slot| slot.is_none()) {
+        *slot = Some(val);
+        return true;
+    }
+    return false;
+}
+
+pub fn pac_add_breakpoint(p: &mut pac::pac, addr: u16) -> bool {
+    return add_slot(&mut p.debug.breakpoints, addr);
+}
+
+pub fn pac_remove_breakpoint(p: &mut pac::pac, addr: u16) -> bool {
+    for slot in p.debug.breakpoints.iter_mut() {
+        if *slot == Some(addr) {
+            *slot = None;
+            return true;
+        }
+    }
+    return false;
+}
+
+pub fn pac_add_watchpoint(p: &mut pac::pac, w: pac_watchpoint) -> bool {
+    return add_slot(&mut p.debug.watchpoints, w);
+}
+
+// reads memory as the cpu would, without stopping at watchpoints nor
+// flipping the latches that some boards switch on reads (ms. pac-man)
+pub fn pac_debug_read(p: &mut pac::pac, addr: u16) -> u8 {
+    let aux_decode = p.aux_decode;
+    let val = (p.driver.read_byte)(&mut p.cpu.userdata, addr);
+    p.aux_decode = aux_decode;
+    return val;
+}
+
+// reads an opcode as the cpu would fetch it (decrypted on some boards)
+pub fn pac_debug_read_opcode(p: &mut pac::pac, addr: u16) -> u8 {
+    let aux_decode = p.aux_decode;
+    let read_byte = p.cpu.read_byte;
+    p.cpu.read_byte = p.driver.read_byte;
+    let val = (p.driver.read_opcode)(&mut p.cpu, addr);
+    p.cpu.read_byte = read_byte;
+    p.aux_decode = aux_decode;
+    return val;
+}
+
+// the symbol "addr" is in, the one starting there first
+pub fn pac_find_symbol(symbols: &'static [pac_symbol], addr: u16) -> Option<&'static pac_symbol> {
+    return symbols
+        .iter()
+        .find(|s| s.start == addr)
+        .or_else(|| symbols.iter().find(|s| addr >= s.start && addr <= s.end));
+}
+
+pub fn pac_symbol_addr(symbols: &[pac_symbol], name: &str) -> Option<u16> {
+    return symbols.iter().find(|s| s.name == name).map(|s| s.start);
+}
+
+pub fn pac_debug_trace(z: &mut z80::z80) {
+    //println!("pac_debug_trace");
+
+    let d = unsafe { &mut (*z.userdata.pac_ptr).debug };
+    let pc = z.pc;
+    let stop = if d.resumed {
+        None
+    } else if d.step {
+        Some(pac_stop::Step)
+    } else if d.run_to == Some(pc) {
+        Some(pac_stop::RunTo(pc))
+    } else if d.breakpoints.contains(&Some(pc)) {
+        Some(pac_stop::Breakpoint(pc))
+    } else {
+        None
+    };
+    d.resumed = false;
+
+    if stop.is_some() {
+        d.step = false;
+        d.run_to = None;
+        d.stop = stop;
+        z80_stop_run(z);
+        return;
+    }
+
+    if let Some(trace) = d.trace {
+        trace(unsafe { &mut (*z.userdata.pac_ptr) });
+    }
+}
+
+fn check_watchpoints(p: &mut pac::pac, addr: u16, val: u8, write: bool) {
+    if p.debug.stop.is_some() {
+        return;
+    }
+    let hit = p.debug.watchpoints.iter().flatten().any(|w| {
+        addr >= w.start && addr <= w.end && (if write { w.write } else { w.read })
+    });
+    if hit {
+        p.debug.stop = Some(pac_stop::Watchpoint { addr, val, write });
+        z80_stop_run(&mut p.cpu);
+    }
+}
+
+pub fn pac_debug_rb(userdata: &mut userdata, addr: u16) -> u8 {
+    //println!("pac_debug_rb");
+
+    unsafe {
+        let p = &mut (*userdata.pac_ptr);
+        let val = (p.driver.read_byte)(userdata, addr);
+        check_watchpoints(p, addr, val, false);
+        return val;
+    }
+}
+
+pub fn pac_debug_wb(userdata: &mut userdata, addr: u16, val: u8) {
+    //println!("pac_debug_wb");
+
+    unsafe {
+        let p = &mut (*userdata.pac_ptr);
+        (p.driver.write_byte)(userdata, addr, val);
+        check_watchpoints(p, addr, val, true);
+    }
+}
+
+// opcode fetches are reads too, even when the driver doesn't fetch them
+// through read_byte (pengo's decrypted opcodes)
+pub fn pac_debug_fetch(z: &mut z80::z80, addr: u16) -> u8 {
+    //println!("pac_debug_fetch");
+
+    unsafe {
+        let p = &mut (*z.userdata.pac_ptr);
+        let val = (p.driver.read_opcode)(z, addr);
+        check_watchpoints(p, addr, val, false);
+        return val;
+    }
+}
@@ -0,0 +1,1015 @@
+#![allow(non_camel_case_types)]
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+
+use crate::*;
+
+// everything that differs between the games running on the Pac-Man board:
+// rom files, memory map, inputs and dip switches. Games on other boards
+// (Space Invaders) also bring their own timings.
+
+// the inputs a game can read, as wired to the bits of its input ports
+#[derive(Copy, Clone, PartialEq)]
+pub enum input {
+    Unused, // always reads as 1 (inputs are active low)
+    Low,    // always reads as 0
+    P1Up,
+    P1Left,
+    P1Right,
+    P1Down,
+    P1Button,
+    P2Up,
+    P2Left,
+    P2Right,
+    P2Down,
+    P2Button,
+    P1Start,
+    P2Start,
+    Coin1,
+    Coin2,
+    Service,
+    Test,
+    RackAdvance,
+    Upright, // cabinet switch: 1 = upright, 0 = cocktail
+}
+
+pub const NB_INPUTS: usize = 20;
+
+// a group of bits of a dip switch bank, and the values it can take
+#[derive(Copy, Clone)]
+pub struct dip_switch {
+    pub name: &'static str,
+    pub mask: u8,
+    pub default: u8,
+    pub settings: &'static [(&'static str, u8)],
+}
+
+// memory areas of the board that rom files are loaded into
+#[derive(Copy, Clone, PartialEq)]
+pub enum rom_region {
+    Cpu,
+    Color,
+    Palette,
+    Tile,
+    Sprite,
+    // a file holding a bank of tiles, then a bank of sprites, of half its
+    // size each (Pengo): the halves go at "offset" in tile_rom and sprite_rom
+    Gfx,
+    Sound1,
+    Sound2,
+}
+
+// a file of a rom set and where it is loaded, as listed in MAME's rom
+// definitions. "sha256" is empty when only the crc32 of the file is known.
+pub struct rom_info {
+    pub name: &'static str,
+    pub region: rom_region,
+    pub offset: usize,
+    pub size: usize,
+    pub crc32: u32,
+    pub sha256: &'static str,
+}
+
+// position (x, y) in the image of each 8*4px strip of a tile or sprite, in
+// the order the strips are stored in rom
+pub struct gfx_layout {
+    pub tile_strips: [(i32, i32); 2],
+    pub sprite_strips: [(i32, i32); 8],
+}
+
+pub struct driver {
+    pub name: &'static str, // short name, as used by MAME for its zip archive
+    pub description: &'static str,
+    pub parent: Option<&'static str>, // set holding the files shared by clones
+    pub roms: &'static [rom_info],
+    // sound samples, by file name without ".wav", in the order the board
+    // plays them. Loaded by the frontend in pac.samples.
+    pub samples: &'static [&'static str],
+
+    // memory map
+    pub read_byte: fn(userdata: &mut userdata, addr: u16) -> u8,
+    pub write_byte: fn(userdata: &mut userdata, addr: u16, val: u8),
+    pub port_in: fn(z: &mut z80::z80, port: u8) -> u8,
+    pub port_out: fn(z: &mut z80::z80, port: u8, val: u8),
+    pub int_ack: fn(z: &mut z80::z80) -> u8,
+    pub read_opcode: fn(z: &mut z80::z80, addr: u16) -> u8,
+    pub draw: fn(p: &mut pac::pac, screen: &mut [u8]),
+    pub gfx: &'static gfx_layout,
+
+    // inputs, from bit 0 to bit 7 of each port
+    pub in0: [input; 8],
+    pub in1: [input; 8],
+    pub dsw1: &'static [dip_switch],
+    pub dsw2: &'static [dip_switch],
+
+    // called once the rom files are loaded
+    pub init: Option<fn(p: &mut pac::pac)>,
+    // runs the board for "ms" milliseconds, if not timed as the Pac-Man board
+    pub update: Option<fn(p: &mut pac::pac, ms: u32, out: &mut pac_output)>,
+    pub invincibility: Option<fn(p: &mut pac::pac)>,
+}
+
+// value read from a dip switch bank with every switch set to its default
+pub fn dip_default(dips: &[dip_switch]) -> u8 {
+    //println!("dip_default");
+
+    let mut val: u8 = 0xff;
+    for dip in dips {
+        val = (val & !dip.mask) | (dip.default & dip.mask);
+    }
+    return val;
+}
+
+// bits of a port that are read from a dip switch bank
+pub fn dip_mask(dips: &[dip_switch]) -> u8 {
+    //println!("dip_mask");
+
+    let mut mask: u8 = 0;
+    for dip in dips {
+        mask |= dip.mask;
+    }
+    return mask;
+}
+
+// returns "val" with the bits reordered: bit i of the result is bit
+// bits[n - 1 - i] of "val", as MAME's bitswap. Used to decrypt roms whose
+// address or data lines are swapped.
+pub fn bitswap(val: u16, bits: &[u8]) -> u16 {
+    let mut res: u16 = 0;
+    for (i, &bit) in bits.iter().rev().enumerate() {
+        res |= ((val >> bit) & 1) << i;
+    }
+    return res;
+}
+
+pub fn find_driver(name: &str) -> Option<&'static driver> {
+    //println!("find_driver");
+
+    return DRIVERS.iter().copied().find(|d| d.name == name);
+}
+
+pub static DRIVERS: [&driver; 12] = [
+    &PUCKMAN, &PUCKMANB, &PACMAN, &PACMANF, &MSPACMAN, &JRPACMAN, &PENGO, &EYES, &MRTNT, &CRUSH,
+    &PONPOKO, &INVADERS,
+];
+
+// MARK: pac-man
+
+const pacman_gfx: gfx_layout = gfx_layout {
+    tile_strips: [(0, 4), (0, 0)],
+    sprite_strips: [
+        (8, 12),
+        (8, 0),
+        (8, 4),
+        (8, 8),
+        (0, 12),
+        (0, 0),
+        (0, 4),
+        (0, 8),
+    ],
+};
+
+const pacman_in0: [input; 8] = [
+    input::P1Up,
+    input::P1Left,
+    input::P1Right,
+    input::P1Down,
+    input::RackAdvance,
+    input::Coin1,
+    input::Coin2,
+    input::Service,
+];
+
+// the cocktail (player 2) controls are wired to the player 1 joystick
+const pacman_in1: [input; 8] = [
+    input::P1Up,
+    input::P1Left,
+    input::P1Right,
+    input::P1Down,
+    input::Test,
+    input::P1Start,
+    input::P2Start,
+    input::Upright,
+];
+
+const pacman_dsw1: [dip_switch; 5] = [
+    dip_switch {
+        name: "Coinage",
+        mask: 0x03,
+        default: 0x01,
+        settings: &[
+            ("Free Play", 0x00),
+            ("1 Coin/1 Credit", 0x01),
+            ("1 Coin/2 Credits", 0x02),
+            ("2 Coins/1 Credit", 0x03),
+        ],
+    },
+    dip_switch {
+        name: "Lives",
+        mask: 0x0c,
+        default: 0x08,
+        settings: &[("1", 0x00), ("2", 0x04), ("3", 0x08), ("5", 0x0c)],
+    },
+    dip_switch {
+        name: "Bonus Life",
+        mask: 0x30,
+        default: 0x00,
+        settings: &[
+            ("10000", 0x00),
+            ("15000", 0x10),
+            ("20000", 0x20),
+            ("None", 0x30),
+        ],
+    },
+    dip_switch {
+        name: "Difficulty",
+        mask: 0x40,
+        default: 0x40,
+        settings: &[("Hard", 0x00), ("Normal", 0x40)],
+    },
+    dip_switch {
+        name: "Ghost Names",
+        mask: 0x80,
+        default: 0x80,
+        settings: &[("Alternate", 0x00), ("Normal", 0x80)],
+    },
+];
+
+const fn rom(
+    name: &'static str,
+    region: rom_region,
+    offset: usize,
+    size: usize,
+    crc32: u32,
+    sha256: &'static str,
+) -> rom_info {
+    return rom_info {
+        name,
+        region,
+        offset,
+        size,
+        crc32,
+        sha256,
+    };
+}
+
+const SHA256_PACMAN_7F: &str = "48fe0b01d68e3d702019ca715f7266c8e3261c769509b281720f53ca0a1cc8fb";
+const SHA256_PACMAN_4A: &str = "ef8f7a3b0c10f787d9cc1cbc5cc266fcc1afadb24c3b4d610fe252b9c3df1d76";
+const SHA256_PACMAN_1M: &str = "8e723ad91e46ef1a186b2ed3c99a8bf1c571786bc7ceae2b367cbfc80857a394";
+const SHA256_PACMAN_3M: &str = "8c34002652e587aa19a77bff9040d870af18b4b2fe5c5f0ed962899386e0e751";
+const SHA256_PACMAN_5E: &str = "8d9a86c97fe94b1fd010b139672c330e3b257ba59b0d8df7a821592e30a77b4b";
+const SHA256_PACMAN_5F: &str = "49c8f656cb8ea1ae02fb64a2c09df98e7f06a034b43c6c8240032df417c6d36f";
+const SHA256_PACMAN_6E: &str = "fe1c3234df345855d30728637f361f79472cabfe2a892a7567c63eaf31a4217b";
+const SHA256_PACMAN_6F: &str = "09a723c9f84790e9019633e37761cfa4e9d7ab6db14f6fdb12738f51fec11065";
+const SHA256_PACMAN_6H: &str = "69347409739b64ed9d9b19713de0bc66627bd137687de649796b9d2ef88ed8e6";
+const SHA256_PACMAN_6J: &str = "03ee523c210e87fb8dd1d925b092ad269fdd753b5b7a20b3757b0ceee5f18679";
+
+const puckman_roms: [rom_info; 16] = [
+    rom("pm1_prg1.6e", rom_region::Cpu, 0x0000, 0x0800, 0xf36e88ab, ""),
+    rom("pm1_prg2.6k", rom_region::Cpu, 0x0800, 0x0800, 0x618bd9b3, ""),
+    rom("pm1_prg3.6f", rom_region::Cpu, 0x1000, 0x0800, 0x7d177853, ""),
+    rom("pm1_prg4.6m", rom_region::Cpu, 0x1800, 0x0800, 0xd3e8914c, ""),
+    rom("pm1_prg5.6h", rom_region::Cpu, 0x2000, 0x0800, 0x6bf4f625, ""),
+    rom("pm1_prg6.6n", rom_region::Cpu, 0x2800, 0x0800, 0xa948ce83, ""),
+    rom("pm1_prg7.6j", rom_region::Cpu, 0x3000, 0x0800, 0xb6289b26, ""),
+    rom("pm1_prg8.6p", rom_region::Cpu, 0x3800, 0x0800, 0x17a88c13, ""),
+    rom("pm1_chg1.5e", rom_region::Tile, 0x0000, 0x0800, 0x2066a0b7, ""),
+    rom("pm1_chg2.5h", rom_region::Tile, 0x0800, 0x0800, 0x3591b89d, ""),
+    rom("pm1_chg3.5f", rom_region::Sprite, 0x0000, 0x0800, 0x9e39323a, ""),
+    rom("pm1_chg4.5j", rom_region::Sprite, 0x0800, 0x0800, 0x1b1d9096, ""),
+    rom("pm1-1.7f", rom_region::Color, 0x0000, 0x0020, 0x2fc650bd, SHA256_PACMAN_7F),
+    rom("pm1-4.4a", rom_region::Palette, 0x0000, 0x0100, 0x3eb3a8e4, SHA256_PACMAN_4A),
+    rom("pm1-3.1m", rom_region::Sound1, 0x0000, 0x0100, 0xa9cc86bf, SHA256_PACMAN_1M),
+    rom("pm1-2.3m", rom_region::Sound2, 0x0000, 0x0100, 0x77245b66, SHA256_PACMAN_3M),
+];
+
+const puckmanb_roms: [rom_info; 10] = [
+    rom("namcopac.6e", rom_region::Cpu, 0x0000, 0x1000, 0xfee263b3, ""),
+    rom("namcopac.6f", rom_region::Cpu, 0x1000, 0x1000, 0x39d1fc83, ""),
+    rom("namcopac.6h", rom_region::Cpu, 0x2000, 0x1000, 0x02083b03, ""),
+    rom("namcopac.6j", rom_region::Cpu, 0x3000, 0x1000, 0x7a36fe55, ""),
+    rom("pacman.5e", rom_region::Tile, 0x0000, 0x1000, 0x0c944964, SHA256_PACMAN_5E),
+    rom("pacman.5f", rom_region::Sprite, 0x0000, 0x1000, 0x958fedf9, SHA256_PACMAN_5F),
+    rom("82s123.7f", rom_region::Color, 0x0000, 0x0020, 0x2fc650bd, SHA256_PACMAN_7F),
+    rom("82s126.4a", rom_region::Palette, 0x0000, 0x0100, 0x3eb3a8e4, SHA256_PACMAN_4A),
+    rom("82s126.1m", rom_region::Sound1, 0x0000, 0x0100, 0xa9cc86bf, SHA256_PACMAN_1M),
+    rom("82s126.3m", rom_region::Sound2, 0x0000, 0x0100, 0x77245b66, SHA256_PACMAN_3M),
+];
+
+const pacman_roms: [rom_info; 10] = [
+    rom("pacman.6e", rom_region::Cpu, 0x0000, 0x1000, 0xc1e6ab10, SHA256_PACMAN_6E),
+    rom("pacman.6f", rom_region::Cpu, 0x1000, 0x1000, 0x1a6fb2d4, SHA256_PACMAN_6F),
+    rom("pacman.6h", rom_region::Cpu, 0x2000, 0x1000, 0xbcdd1beb, SHA256_PACMAN_6H),
+    rom("pacman.6j", rom_region::Cpu, 0x3000, 0x1000, 0x817d94e3, SHA256_PACMAN_6J),
+    rom("pacman.5e", rom_region::Tile, 0x0000, 0x1000, 0x0c944964, SHA256_PACMAN_5E),
+    rom("pacman.5f", rom_region::Sprite, 0x0000, 0x1000, 0x958fedf9, SHA256_PACMAN_5F),
+    rom("82s123.7f", rom_region::Color, 0x0000, 0x0020, 0x2fc650bd, SHA256_PACMAN_7F),
+    rom("82s126.4a", rom_region::Palette, 0x0000, 0x0100, 0x3eb3a8e4, SHA256_PACMAN_4A),
+    rom("82s126.1m", rom_region::Sound1, 0x0000, 0x0100, 0xa9cc86bf, SHA256_PACMAN_1M),
+    rom("82s126.3m", rom_region::Sound2, 0x0000, 0x0100, 0x77245b66, SHA256_PACMAN_3M),
+];
+
+// the "fast" hack only patches the second program rom
+const pacmanf_roms: [rom_info; 10] = [
+    rom("pacman.6e", rom_region::Cpu, 0x0000, 0x1000, 0xc1e6ab10, SHA256_PACMAN_6E),
+    rom("pacfast.6f", rom_region::Cpu, 0x1000, 0x1000, 0x720dc3ee, ""),
+    rom("pacman.6h", rom_region::Cpu, 0x2000, 0x1000, 0xbcdd1beb, SHA256_PACMAN_6H),
+    rom("pacman.6j", rom_region::Cpu, 0x3000, 0x1000, 0x817d94e3, SHA256_PACMAN_6J),
+    rom("pacman.5e", rom_region::Tile, 0x0000, 0x1000, 0x0c944964, SHA256_PACMAN_5E),
+    rom("pacman.5f", rom_region::Sprite, 0x0000, 0x1000, 0x958fedf9, SHA256_PACMAN_5F),
+    rom("82s123.7f", rom_region::Color, 0x0000, 0x0020, 0x2fc650bd, SHA256_PACMAN_7F),
+    rom("82s126.4a", rom_region::Palette, 0x0000, 0x0100, 0x3eb3a8e4, SHA256_PACMAN_4A),
+    rom("82s126.1m", rom_region::Sound1, 0x0000, 0x0100, 0xa9cc86bf, SHA256_PACMAN_1M),
+    rom("82s126.3m", rom_region::Sound2, 0x0000, 0x0100, 0x77245b66, SHA256_PACMAN_3M),
+];
+
+// memory map and inputs shared by pac-man and its clones
+const pacman_base: driver = driver {
+    name: "",
+    description: "",
+    parent: None,
+    roms: &[],
+    samples: &[],
+    read_byte: pac_rb,
+    write_byte: pac_wb,
+    port_in: pac::port_in,
+    port_out: pac::port_out,
+    int_ack: pac::int_ack,
+    read_opcode: z80::read_opcode_default,
+    draw: pac_draw,
+    gfx: &pacman_gfx,
+    in0: pacman_in0,
+    in1: pacman_in1,
+    dsw1: &pacman_dsw1,
+    dsw2: &[],
+    init: None,
+    update: None,
+    invincibility: None,
+};
+
+pub static PUCKMAN: driver = driver {
+    name: "puckman",
+    description: "Puck Man (Japan set 1)",
+    roms: &puckman_roms,
+    ..pacman_base
+};
+
+pub static PUCKMANB: driver = driver {
+    name: "puckmanb",
+    description: "Puck Man (bootleg set 1)",
+    parent: Some("puckman"),
+    roms: &puckmanb_roms,
+    ..pacman_base
+};
+
+pub static PACMAN: driver = driver {
+    name: "pacman",
+    description: "Pac-Man (Midway)",
+    parent: Some("puckman"),
+    roms: &pacman_roms,
+    invincibility: Some(pac_cheat_invincibility),
+    ..pacman_base
+};
+
+pub static PACMANF: driver = driver {
+    name: "pacmanf",
+    description: "Pac-Man (Midway, with speedup hack)",
+    parent: Some("puckman"),
+    roms: &pacmanf_roms,
+    ..pacman_base
+};
+
+// MARK: ms. pac-man
+
+// the Pac-Man program roms, plus the encrypted aux board roms (u5, u6, u7)
+const mspacman_roms: [rom_info; 13] = [
+    rom("pacman.6e", rom_region::Cpu, 0x0000, 0x1000, 0xc1e6ab10, SHA256_PACMAN_6E),
+    rom("pacman.6f", rom_region::Cpu, 0x1000, 0x1000, 0x1a6fb2d4, SHA256_PACMAN_6F),
+    rom("pacman.6h", rom_region::Cpu, 0x2000, 0x1000, 0xbcdd1beb, SHA256_PACMAN_6H),
+    rom("pacman.6j", rom_region::Cpu, 0x3000, 0x1000, 0x817d94e3, SHA256_PACMAN_6J),
+    rom("u5", rom_region::Cpu, 0x8000, 0x0800, 0xf45fbbcd, ""),
+    rom("u6", rom_region::Cpu, 0x9000, 0x1000, 0xa90e7000, ""),
+    rom("u7", rom_region::Cpu, 0xb000, 0x1000, 0xc82cd714, ""),
+    rom("5e", rom_region::Tile, 0x0000, 0x1000, 0x5c281d01, ""),
+    rom("5f", rom_region::Sprite, 0x0000, 0x1000, 0x615af909, ""),
+    rom("82s123.7f", rom_region::Color, 0x0000, 0x0020, 0x2fc650bd, SHA256_PACMAN_7F),
+    rom("82s126.4a", rom_region::Palette, 0x0000, 0x0100, 0x3eb3a8e4, SHA256_PACMAN_4A),
+    rom("82s126.1m", rom_region::Sound1, 0x0000, 0x0100, 0xa9cc86bf, SHA256_PACMAN_1M),
+    rom("82s126.3m", rom_region::Sound2, 0x0000, 0x0100, 0x77245b66, SHA256_PACMAN_3M),
+];
+
+pub static MSPACMAN: driver = driver {
+    name: "mspacman",
+    description: "Ms. Pac-Man",
+    roms: &mspacman_roms,
+    read_byte: mspacman_rb,
+    write_byte: mspacman_wb,
+    init: Some(mspacman_init),
+    ..pacman_base
+};
+
+// MARK: jr. pac-man
+
+const jrpacman_in1: [input; 8] = [
+    input::P2Up,
+    input::P2Left,
+    input::P2Right,
+    input::P2Down,
+    input::Test,
+    input::P1Start,
+    input::P2Start,
+    input::Upright,
+];
+
+const jrpacman_dsw1: [dip_switch; 4] = [
+    pacman_dsw1[0],
+    pacman_dsw1[1],
+    dip_switch {
+        name: "Bonus Life",
+        mask: 0x30,
+        default: 0x00,
+        settings: &[
+            ("10000", 0x00),
+            ("15000", 0x10),
+            ("20000", 0x20),
+            ("30000", 0x30),
+        ],
+    },
+    pacman_dsw1[3],
+];
+
+// the two color proms are loaded one after the other, and merged on init
+const jrpacman_roms: [rom_info; 12] = [
+    rom("jrp8d.8d", rom_region::Cpu, 0x0000, 0x2000, 0xe3fa972e, ""),
+    rom("jrp8e.8e", rom_region::Cpu, 0x2000, 0x2000, 0xec889e94, ""),
+    rom("jrp8h.8h", rom_region::Cpu, 0x8000, 0x2000, 0x35f1fc6e, ""),
+    rom("jrp8j.8j", rom_region::Cpu, 0xa000, 0x2000, 0x9737099e, ""),
+    rom("jrp8k.8k", rom_region::Cpu, 0xc000, 0x2000, 0x5252dd97, ""),
+    rom("jrp2c.2c", rom_region::Tile, 0x0000, 0x2000, 0x0527ff9b, ""),
+    rom("jrp2e.2e", rom_region::Sprite, 0x0000, 0x2000, 0x73477193, ""),
+    rom("a290-27axv-bxhd.9e", rom_region::Color, 0x0000, 0x0100, 0x029d35c4, ""),
+    rom("a290-27axv-cxhd.9f", rom_region::Color, 0x0100, 0x0100, 0xeee34a79, ""),
+    rom("a290-27axv-axhd.9p", rom_region::Palette, 0x0000, 0x0100, 0x9f6ea9d8, ""),
+    rom("a290-27axv-dxhd.7p", rom_region::Sound1, 0x0000, 0x0100, 0xa9cc86bf, SHA256_PACMAN_1M),
+    rom("a290-27axv-exhd.5s", rom_region::Sound2, 0x0000, 0x0100, 0x77245b66, SHA256_PACMAN_3M),
+];
+
+pub static JRPACMAN: driver = driver {
+    name: "jrpacman",
+    description: "Jr. Pac-Man (11/9/83)",
+    roms: &jrpacman_roms,
+    read_byte: jrpacman_rb,
+    write_byte: jrpacman_wb,
+    draw: jrpacman_draw,
+    in1: jrpacman_in1,
+    dsw1: &jrpacman_dsw1,
+    init: Some(jrpacman_init),
+    ..pacman_base
+};
+
+// MARK: pengo
+
+const pengo_in0: [input; 8] = [
+    input::P1Up,
+    input::P1Down,
+    input::P1Left,
+    input::P1Right,
+    input::Coin1,
+    input::Coin2,
+    input::Service,
+    input::P1Button,
+];
+
+const pengo_in1: [input; 8] = [
+    input::P2Up,
+    input::P2Down,
+    input::P2Left,
+    input::P2Right,
+    input::Test,
+    input::P1Start,
+    input::P2Start,
+    input::P2Button,
+];
+
+// read at 0x9040
+const pengo_dsw1: [dip_switch; 6] = [
+    dip_switch {
+        name: "Bonus Life",
+        mask: 0x01,
+        default: 0x00,
+        settings: &[("30000", 0x00), ("50000", 0x01)],
+    },
+    dip_switch {
+        name: "Demo Sounds",
+        mask: 0x02,
+        default: 0x00,
+        settings: &[("On", 0x00), ("Off", 0x02)],
+    },
+    dip_switch {
+        name: "Cabinet",
+        mask: 0x04,
+        default: 0x00,
+        settings: &[("Upright", 0x00), ("Cocktail", 0x04)],
+    },
+    dip_switch {
+        name: "Lives",
+        mask: 0x18,
+        default: 0x10,
+        settings: &[("2", 0x18), ("3", 0x10), ("4", 0x08), ("5", 0x00)],
+    },
+    dip_switch {
+        name: "Rack Test",
+        mask: 0x20,
+        default: 0x20,
+        settings: &[("On", 0x00), ("Off", 0x20)],
+    },
+    dip_switch {
+        name: "Difficulty",
+        mask: 0xc0,
+        default: 0x80,
+        settings: &[
+            ("Easy", 0xc0),
+            ("Medium", 0x80),
+            ("Hard", 0x40),
+            ("Hardest", 0x00),
+        ],
+    },
+];
+
+// read at 0x9000
+const pengo_dsw2: [dip_switch; 2] = [
+    dip_switch {
+        name: "Coin A",
+        mask: 0x0f,
+        default: 0x0c,
+        settings: &[
+            ("2 Coins/1 Credit", 0x0e),
+            ("1 Coin/1 Credit", 0x0c),
+            ("1 Coin/2 Credits", 0x0d),
+            ("1 Coin/3 Credits", 0x03),
+        ],
+    },
+    dip_switch {
+        name: "Coin B",
+        mask: 0xf0,
+        default: 0xc0,
+        settings: &[
+            ("2 Coins/1 Credit", 0xe0),
+            ("1 Coin/1 Credit", 0xc0),
+            ("1 Coin/2 Credits", 0xd0),
+            ("1 Coin/3 Credits", 0x30),
+        ],
+    },
+];
+
+const pengo_roms: [rom_info; 14] = [
+    rom("epr-1689c.ic8", rom_region::Cpu, 0x0000, 0x1000, 0xf37066a8, ""),
+    rom("epr-1690b.ic7", rom_region::Cpu, 0x1000, 0x1000, 0xbaf48143, ""),
+    rom("epr-1691b.ic15", rom_region::Cpu, 0x2000, 0x1000, 0xadf0eba0, ""),
+    rom("epr-1692b.ic14", rom_region::Cpu, 0x3000, 0x1000, 0xa086d60f, ""),
+    rom("epr-1693b.ic21", rom_region::Cpu, 0x4000, 0x1000, 0xb72084ec, ""),
+    rom("epr-1694b.ic20", rom_region::Cpu, 0x5000, 0x1000, 0x94194a89, ""),
+    rom("epr-5118b.ic32", rom_region::Cpu, 0x6000, 0x1000, 0xaf7b12c4, ""),
+    rom("epr-5119c.ic31", rom_region::Cpu, 0x7000, 0x1000, 0x933950fe, ""),
+    rom("epr-1640.ic92", rom_region::Gfx, 0x0000, 0x2000, 0xd7eec6cd, ""),
+    rom("epr-1695.ic105", rom_region::Gfx, 0x1000, 0x2000, 0x5bfd26e9, ""),
+    rom("pr1633.ic78", rom_region::Color, 0x0000, 0x0020, 0x3a5844ec, ""),
+    rom("pr1634.ic88", rom_region::Palette, 0x0000, 0x0400, 0x766b139b, ""),
+    rom("pr1635.ic51", rom_region::Sound1, 0x0000, 0x0100, 0xc29dea27, ""),
+    rom("pr1636.ic70", rom_region::Sound2, 0x0000, 0x0100, 0x77245b66, SHA256_PACMAN_3M),
+];
+
+pub static PENGO: driver = driver {
+    name: "pengo",
+    description: "Pengo (set 1 rev c)",
+    roms: &pengo_roms,
+    read_byte: pengo_rb,
+    write_byte: pengo_wb,
+    read_opcode: pengo_read_opcode,
+    in0: pengo_in0,
+    in1: pengo_in1,
+    dsw1: &pengo_dsw1,
+    dsw2: &pengo_dsw2,
+    init: Some(pengo_init),
+    ..pacman_base
+};
+
+// MARK: eyes
+
+const eyes_in0: [input; 8] = [
+    input::P1Up,
+    input::P1Left,
+    input::P1Right,
+    input::P1Down,
+    input::P1Button,
+    input::Coin1,
+    input::Coin2,
+    input::Service,
+];
+
+const eyes_in1: [input; 8] = [
+    input::P2Up,
+    input::P2Left,
+    input::P2Right,
+    input::P2Down,
+    input::P2Button,
+    input::P1Start,
+    input::P2Start,
+    input::Unused,
+];
+
+const eyes_dsw1: [dip_switch; 4] = [
+    dip_switch {
+        name: "Coinage",
+        mask: 0x03,
+        default: 0x03,
+        settings: &[
+            ("Free Play", 0x00),
+            ("2 Coins/1 Credit", 0x01),
+            ("1 Coin/2 Credits", 0x02),
+            ("1 Coin/1 Credit", 0x03),
+        ],
+    },
+    dip_switch {
+        name: "Lives",
+        mask: 0x0c,
+        default: 0x08,
+        settings: &[("2", 0x0c), ("3", 0x08), ("4", 0x04), ("5", 0x00)],
+    },
+    dip_switch {
+        name: "Bonus Life",
+        mask: 0x30,
+        default: 0x30,
+        settings: &[
+            ("50000", 0x30),
+            ("75000", 0x20),
+            ("100000", 0x10),
+            ("125000", 0x00),
+        ],
+    },
+    dip_switch {
+        name: "Cabinet",
+        mask: 0x40,
+        default: 0x40,
+        settings: &[("Upright", 0x40), ("Cocktail", 0x00)],
+    },
+];
+
+const eyes_roms: [rom_info; 10] = [
+    rom("d7", rom_region::Cpu, 0x0000, 0x1000, 0x3b09ac89, ""),
+    rom("e7", rom_region::Cpu, 0x1000, 0x1000, 0x97096855, ""),
+    rom("f7", rom_region::Cpu, 0x2000, 0x1000, 0x731e294e, ""),
+    rom("h7", rom_region::Cpu, 0x3000, 0x1000, 0x22f7a719, ""),
+    rom("d5", rom_region::Tile, 0x0000, 0x1000, 0xd6af0030, ""),
+    rom("e5", rom_region::Sprite, 0x0000, 0x1000, 0xa42b5201, ""),
+    rom("82s123.7f", rom_region::Color, 0x0000, 0x0020, 0x2fc650bd, SHA256_PACMAN_7F),
+    rom("82s129.4a", rom_region::Palette, 0x0000, 0x0100, 0xd8d78829, ""),
+    rom("82s126.1m", rom_region::Sound1, 0x0000, 0x0100, 0xa9cc86bf, SHA256_PACMAN_1M),
+    rom("82s126.3m", rom_region::Sound2, 0x0000, 0x0100, 0x77245b66, SHA256_PACMAN_3M),
+];
+
+pub static EYES: driver = driver {
+    name: "eyes",
+    description: "Eyes (US set 1)",
+    roms: &eyes_roms,
+    in0: eyes_in0,
+    in1: eyes_in1,
+    dsw1: &eyes_dsw1,
+    init: Some(eyes_init),
+    ..pacman_base
+};
+
+// MARK: mr. tnt
+
+// the roms are wired as on Eyes
+const mrtnt_in0: [input; 8] = [
+    input::P1Up,
+    input::P1Left,
+    input::P1Right,
+    input::P1Down,
+    input::Test,
+    input::Coin1,
+    input::Coin2,
+    input::Service,
+];
+
+const mrtnt_in1: [input; 8] = [
+    input::P2Up,
+    input::P2Left,
+    input::P2Right,
+    input::P2Down,
+    input::Unused,
+    input::P1Start,
+    input::P2Start,
+    input::Unused,
+];
+
+const mrtnt_dsw1: [dip_switch; 4] = [
+    eyes_dsw1[0],
+    eyes_dsw1[1],
+    dip_switch {
+        name: "Bonus Life",
+        mask: 0x30,
+        default: 0x30,
+        settings: &[
+            ("75000", 0x30),
+            ("100000", 0x20),
+            ("125000", 0x10),
+            ("150000", 0x00),
+        ],
+    },
+    eyes_dsw1[3],
+];
+
+const mrtnt_roms: [rom_info; 10] = [
+    rom("tnt.1", rom_region::Cpu, 0x0000, 0x1000, 0x0e836586, ""),
+    rom("tnt.2", rom_region::Cpu, 0x1000, 0x1000, 0x779c4c5b, ""),
+    rom("tnt.3", rom_region::Cpu, 0x2000, 0x1000, 0xad6fc688, ""),
+    rom("tnt.4", rom_region::Cpu, 0x3000, 0x1000, 0xd77557b3, ""),
+    rom("tnt.5", rom_region::Tile, 0x0000, 0x1000, 0x3038cc0e, ""),
+    rom("tnt.6", rom_region::Sprite, 0x0000, 0x1000, 0x97634d8b, ""),
+    rom("82s123.7f", rom_region::Color, 0x0000, 0x0020, 0x2fc650bd, SHA256_PACMAN_7F),
+    rom("82s126.4a", rom_region::Palette, 0x0000, 0x0100, 0x3eb3a8e4, SHA256_PACMAN_4A),
+    rom("82s126.1m", rom_region::Sound1, 0x0000, 0x0100, 0xa9cc86bf, SHA256_PACMAN_1M),
+    rom("82s126.3m", rom_region::Sound2, 0x0000, 0x0100, 0x77245b66, SHA256_PACMAN_3M),
+];
+
+pub static MRTNT: driver = driver {
+    name: "mrtnt",
+    description: "Mr. TNT",
+    roms: &mrtnt_roms,
+    in0: mrtnt_in0,
+    in1: mrtnt_in1,
+    dsw1: &mrtnt_dsw1,
+    init: Some(eyes_init),
+    ..pacman_base
+};
+
+// MARK: crush roller
+
+// the cabinet switch reads 0 on an upright, and the bits of the protection
+// (see crush.rs) read 0
+const crush_in0: [input; 8] = [
+    input::P1Up,
+    input::P1Left,
+    input::P1Right,
+    input::P1Down,
+    input::Low,
+    input::Coin1,
+    input::Coin2,
+    input::Service,
+];
+
+const crush_in1: [input; 8] = [
+    input::P2Up,
+    input::P2Left,
+    input::P2Right,
+    input::P2Down,
+    input::Low,
+    input::P1Start,
+    input::P2Start,
+    input::Low,
+];
+
+const crush_dsw1: [dip_switch; 4] = [
+    dip_switch {
+        name: "Coinage",
+        mask: 0x03,
+        default: 0x01,
+        settings: &[
+            ("Free Play", 0x00),
+            ("1 Coin/1 Credit", 0x01),
+            ("1 Coin/2 Credits", 0x02),
+            ("2 Coins/1 Credit", 0x03),
+        ],
+    },
+    dip_switch {
+        name: "Lives",
+        mask: 0x0c,
+        default: 0x00,
+        settings: &[("3", 0x00), ("4", 0x04), ("5", 0x08), ("6", 0x0c)],
+    },
+    dip_switch {
+        name: "First Pattern",
+        mask: 0x10,
+        default: 0x10,
+        settings: &[("Easy", 0x10), ("Hard", 0x00)],
+    },
+    dip_switch {
+        name: "Teleport Holes",
+        mask: 0x20,
+        default: 0x20,
+        settings: &[("Off", 0x20), ("On", 0x00)],
+    },
+];
+
+const crush_roms: [rom_info; 10] = [
+    rom("crushkrl.6e", rom_region::Cpu, 0x0000, 0x1000, 0xa8dd8f54, ""),
+    rom("crushkrl.6f", rom_region::Cpu, 0x1000, 0x1000, 0x91387299, ""),
+    rom("crushkrl.6h", rom_region::Cpu, 0x2000, 0x1000, 0xd4455f27, ""),
+    rom("crushkrl.6j", rom_region::Cpu, 0x3000, 0x1000, 0xd59fc251, ""),
+    rom("maketrax.5e", rom_region::Tile, 0x0000, 0x1000, 0x91bad2da, ""),
+    rom("maketrax.5f", rom_region::Sprite, 0x0000, 0x1000, 0xaea79f55, ""),
+    rom("82s123.7f", rom_region::Color, 0x0000, 0x0020, 0x2fc650bd, SHA256_PACMAN_7F),
+    rom("2s140.4a", rom_region::Palette, 0x0000, 0x0100, 0x63efb927, ""),
+    rom("82s126.1m", rom_region::Sound1, 0x0000, 0x0100, 0xa9cc86bf, SHA256_PACMAN_1M),
+    rom("82s126.3m", rom_region::Sound2, 0x0000, 0x0100, 0x77245b66, SHA256_PACMAN_3M),
+];
+
+pub static CRUSH: driver = driver {
+    name: "crush",
+    description: "Crush Roller (set 1)",
+    roms: &crush_roms,
+    read_byte: crush_rb,
+    read_opcode: crush_read_opcode,
+    in0: crush_in0,
+    in1: crush_in1,
+    dsw1: &crush_dsw1,
+    ..pacman_base
+};
+
+// MARK: ponpoko
+
+// the strips are stored in a different order than on Pac-Man
+const ponpoko_gfx: gfx_layout = gfx_layout {
+    tile_strips: [(0, 0), (0, 4)],
+    sprite_strips: [
+        (8, 0),
+        (8, 4),
+        (8, 8),
+        (8, 12),
+        (0, 0),
+        (0, 4),
+        (0, 8),
+        (0, 12),
+    ],
+};
+
+const ponpoko_in0: [input; 8] = [
+    input::P1Up,
+    input::P1Left,
+    input::P1Right,
+    input::P1Down,
+    input::P1Button,
+    input::Coin1,
+    input::Unused,
+    input::Coin2,
+];
+
+// the player 2 controls are used even on an upright cabinet
+const ponpoko_in1: [input; 8] = [
+    input::P2Up,
+    input::P2Left,
+    input::P2Right,
+    input::P2Down,
+    input::P2Button,
+    input::P1Start,
+    input::P2Start,
+    input::Unused,
+];
+
+const ponpoko_dsw1: [dip_switch; 3] = [
+    dip_switch {
+        name: "Bonus Life",
+        mask: 0x03,
+        default: 0x01,
+        settings: &[
+            ("10000", 0x01),
+            ("30000", 0x02),
+            ("50000", 0x03),
+            ("None", 0x00),
+        ],
+    },
+    dip_switch {
+        name: "Lives",
+        mask: 0x30,
+        default: 0x20,
+        settings: &[("2", 0x00), ("3", 0x10), ("4", 0x20), ("5", 0x30)],
+    },
+    dip_switch {
+        name: "Cabinet",
+        mask: 0x40,
+        default: 0x40,
+        settings: &[("Upright", 0x40), ("Cocktail", 0x00)],
+    },
+];
+
+// read at 0x50c0
+const ponpoko_dsw2: [dip_switch; 1] = [dip_switch {
+    name: "Coinage",
+    mask: 0x0f,
+    default: 0x01,
+    settings: &[
+        ("Free Play", 0x00),
+        ("1 Coin/1 Credit", 0x01),
+        ("1 Coin/2 Credits", 0x04),
+        ("2 Coins/1 Credit", 0x02),
+    ],
+}];
+
+const ponpoko_roms: [rom_info; 14] = [
+    rom("ppokoj1.bin", rom_region::Cpu, 0x0000, 0x1000, 0xffa3c004, ""),
+    rom("ppokoj2.bin", rom_region::Cpu, 0x1000, 0x1000, 0x4a496866, ""),
+    rom("ppokoj3.bin", rom_region::Cpu, 0x2000, 0x1000, 0x17da6ca3, ""),
+    rom("ppokoj4.bin", rom_region::Cpu, 0x3000, 0x1000, 0x9d39a565, ""),
+    rom("ppoko5.bin", rom_region::Cpu, 0x8000, 0x1000, 0x54ca3d7d, ""),
+    rom("ppoko6.bin", rom_region::Cpu, 0x9000, 0x1000, 0x3055c7e0, ""),
+    rom("ppoko7.bin", rom_region::Cpu, 0xa000, 0x1000, 0x3cbe47ca, ""),
+    rom("ppokoj8.bin", rom_region::Cpu, 0xb000, 0x1000, 0x04b63fc6, ""),
+    rom("ppoko9.bin", rom_region::Tile, 0x0000, 0x1000, 0xb73e1a06, ""),
+    rom("ppoko10.bin", rom_region::Sprite, 0x0000, 0x1000, 0x62069b5d, ""),
+    rom("82s123.7f", rom_region::Color, 0x0000, 0x0020, 0x2fc650bd, SHA256_PACMAN_7F),
+    rom("82s126.4a", rom_region::Palette, 0x0000, 0x0100, 0x3eb3a8e4, SHA256_PACMAN_4A),
+    rom("82s126.1m", rom_region::Sound1, 0x0000, 0x0100, 0xa9cc86bf, SHA256_PACMAN_1M),
+    rom("82s126.3m", rom_region::Sound2, 0x0000, 0x0100, 0x77245b66, SHA256_PACMAN_3M),
+];
+
+pub static PONPOKO: driver = driver {
+    name: "ponpoko",
+    description: "Ponpoko",
+    roms: &ponpoko_roms,
+    read_byte: woodpek_rb,
+    gfx: &ponpoko_gfx,
+    in0: ponpoko_in0,
+    in1: ponpoko_in1,
+    dsw1: &ponpoko_dsw1,
+    dsw2: &ponpoko_dsw2,
+    ..pacman_base
+};
+
+// MARK: space invaders
+
+// port 1. The coin and bit 3 are active low, the other bits active high.
+const invaders_in0: [input; 8] = [
+    input::Coin1,
+    input::P2Start,
+    input::P1Start,
+    input::Unused,
+    input::P1Button,
+    input::P1Left,
+    input::P1Right,
+    input::Unused,
+];
+
+// port 2: the player 2 controls are only read on a cocktail cabinet. Bits 0,
+// 1, 3 and 7 are dip switches, bit 2 is the tilt switch.
+const invaders_in1: [input; 8] = [
+    input::Low,
+    input::Low,
+    input::Low,
+    input::Low,
+    input::P2Button,
+    input::P2Left,
+    input::P2Right,
+    input::Low,
+];
+
+const invaders_dsw1: [dip_switch; 3] = [
+    dip_switch {
+        name: "Lives",
+        mask: 0x03,
+        default: 0x00,
+        settings: &[("3", 0x00), ("4", 0x01), ("5", 0x02), ("6", 0x03)],
+    },
+    dip_switch {
+        name: "Extra Base At",
+        mask: 0x08,
+        default: 0x00,
+        settings: &[("1000", 0x08), ("1500", 0x00)],
+    },
+    dip_switch {
+        name: "Display Coinage",
+        mask: 0x80,
+        default: 0x00,
+        settings: &[("Off", 0x80), ("On", 0x00)],
+    },
+];
+
+const invaders_roms: [rom_info; 4] = [
+    rom("invaders.h", rom_region::Cpu, 0x0000, 0x0800, 0x734f5ad8, ""),
+    rom("invaders.g", rom_region::Cpu, 0x0800, 0x0800, 0x6bfaca4a, ""),
+    rom("invaders.f", rom_region::Cpu, 0x1000, 0x0800, 0x0ccead96, ""),
+    rom("invaders.e", rom_region::Cpu, 0x1800, 0x0800, 0x14e538b0, ""),
+];
+
+// MAME's invaders samples set, in the order of invaders.rs' SAMPLE_ numbers
+const invaders_samples: [&str; 10] = ["1", "2", "3", "4", "5", "6", "7", "8", "9", "0"];
+
+pub static INVADERS: driver = driver {
+    name: "invaders",
+    description: "Space Invaders / Space Invaders M",
+    roms: &invaders_roms,
+    samples: &invaders_samples,
+    read_byte: invaders_rb,
+    write_byte: invaders_wb,
+    port_in: invaders_port_in,
+    port_out: invaders_port_out,
+    int_ack: invaders_int_ack,
+    draw: invaders_draw,
+    in0: invaders_in0,
+    in1: invaders_in1,
+    dsw1: &invaders_dsw1,
+    init: Some(invaders_init),
+    update: Some(invaders_update),
+    ..pacman_base
+};
@@ -0,0 +1,38 @@
+#![allow(non_camel_case_types)]
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+
+use crate::*;
+
+// Eyes and the other Pac-Man board games whose roms have some address and
+// data lines swapped (based on MAME's init_eyes). The game code is otherwise
+// unmodified, so decrypting the roms on load is enough to run them. Eyes and
+// Mr. TNT have a driver; Lizard Wizard has none yet.
+
+// program roms: data lines D3 and D5 are swapped
+fn decrypt_program(rom: &mut [u8]) {
+    for byte in rom.iter_mut() {
+        *byte = bitswap(*byte as u16, &[7, 6, 3, 4, 5, 2, 1, 0]) as u8;
+    }
+}
+
+// graphics roms: data lines D4 and D6, and address lines A0 and A2, are
+// swapped
+fn decrypt_gfx(rom: &mut [u8]) {
+    for chunk in rom.chunks_exact_mut(8) {
+        let mut swapped: [u8; 8] = [0; 8];
+        for j in 0..8 {
+            let byte = chunk[bitswap(j as u16, &[0, 1, 2]) as usize];
+            swapped[j] = bitswap(byte as u16, &[7, 4, 5, 6, 3, 2, 1, 0]) as u8;
+        }
+        chunk.copy_from_slice(&swapped);
+    }
+}
+
+pub fn eyes_init(p: &mut pac::pac) {
+    //println!("eyes_init");
+
+    decrypt_program(&mut p.rom[0..0x4000]);
+    decrypt_gfx(&mut p.tile_rom[0..0x1000]);
+    decrypt_gfx(&mut p.sprite_rom[0..0x1000]);
+}
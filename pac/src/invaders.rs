@@ -0,0 +1,304 @@
+#![allow(non_camel_case_types)]
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+
+use crate::*;
+
+// Space Invaders, based on MAME's mw8080bw driver.
+// Midway's 8080 board has nothing in common with the Pac-Man board but the
+// screen orientation: an intel 8080 (run by the z80 core in 8080 mode), 8kB of
+// rom, 8kB of ram whose upper 7kB are a 1 bit per pixel bitmap, a hardware
+// shift register on the io ports, and discrete sound circuits, replaced here
+// by MAME's samples.
+
+pub const INVADERS_CLOCK_SPEED: u32 = 1996800; // 19.968 MHz / 10
+pub const INVADERS_CYCLES_PER_FRAME: u32 = INVADERS_CLOCK_SPEED / PAC_FPS;
+
+// the 256*224 bitmap is shown rotated, centered on the 224*288 screen
+const INVADERS_SCREEN_TOP: usize = 16;
+
+// samples, as listed by the driver (MAME's invaders samples set)
+const SAMPLE_SHOT: usize = 0;
+const SAMPLE_BASE_HIT: usize = 1;
+const SAMPLE_INVADER_HIT: usize = 2;
+const SAMPLE_FLEET: usize = 3; // 4 notes
+const SAMPLE_UFO_HIT: usize = 7;
+const SAMPLE_BONUS_BASE: usize = 8;
+const SAMPLE_UFO: usize = 9;
+
+// voices: one per group of sounds that can't play together
+const VOICE_SHOT: usize = 0;
+const VOICE_BASE_HIT: usize = 1;
+const VOICE_INVADER_HIT: usize = 2;
+const VOICE_UFO_HIT: usize = 3;
+const VOICE_FLEET: usize = 4;
+const VOICE_BONUS_BASE: usize = 5;
+const VOICE_UFO: usize = 6;
+const NB_VOICES: usize = 7;
+
+// bits of the input ports 1 and 2 that are active high. read_port reads every
+// input as active low.
+const PORT1_ACTIVE_HIGH: u8 = 0xf6;
+const PORT2_ACTIVE_HIGH: u8 = 0x70;
+
+pub struct invaders {
+    pub ram: [u8; 0x2000], // 0x2000-0x3fff, bitmap from 0x2400
+    pub shift_data: u16,
+    pub shift_amount: u8,
+    pub sound_latches: [u8; 2], // ports 3 and 5
+    pub int_vector: u8,         // rst put on the bus by the next interrupt
+}
+
+impl invaders {
+    pub fn new() -> Self {
+        Self {
+            ram: [0; 0x2000],
+            shift_data: 0,
+            shift_amount: 0,
+            sound_latches: [0; 2],
+            int_vector: 0,
+        }
+    }
+}
+
+pub fn invaders_init(p: &mut pac::pac) {
+    //println!("invaders_init");
+
+    p.cpu.i8080 = true;
+    samples_init(&mut p.samples, NB_VOICES);
+}
+
+pub fn invaders_rb(userdata: &mut userdata, addr: u16) -> u8 {
+    //println!("invaders_rb");
+
+    // A15 is not decoded, and the ram is mirrored at 0x6000
+    let addr = addr & 0x7fff;
+    unsafe {
+        let p = &(*userdata.pac_ptr);
+        if addr & 0x2000 != 0 {
+            return p.invaders.ram[(addr & 0x1fff) as usize];
+        }
+        return p.rom[addr as usize];
+    }
+}
+
+pub fn invaders_wb(userdata: &mut userdata, addr: u16, val: u8) {
+    //println!("invaders_wb");
+
+    let addr = addr & 0x7fff;
+    unsafe {
+        let p = &mut (*userdata.pac_ptr);
+        if addr & 0x2000 != 0 {
+            p.invaders.ram[(addr & 0x1fff) as usize] = val;
+        }
+        // cannot write to rom
+    }
+}
+
+pub fn invaders_port_in(z: &mut z80::z80, port: u8) -> u8 {
+    //println!("invaders_port_in");
+
+    unsafe {
+        let p = &(*z.userdata.pac_ptr);
+        match port & 3 {
+            0 => {
+                // only read by the self test
+                return 0x0e;
+            }
+            1 => {
+                return read_port(p, &p.driver.in0) ^ PORT1_ACTIVE_HIGH;
+            }
+            2 => {
+                // the dip switches share the port with the player 2 controls
+                let mask = dip_mask(p.driver.dsw1);
+                let inputs = read_port(p, &p.driver.in1) ^ PORT2_ACTIVE_HIGH;
+                return (inputs & !mask) | (p.dsw1 & mask);
+            }
+            _ => {
+                // shift register: 8 bits of the 16 bits written, "shift_amount"
+                // bits from the top
+                let b = &p.invaders;
+                return (b.shift_data >> (8 - b.shift_amount)) as u8;
+            }
+        }
+    }
+}
+
+pub fn invaders_port_out(z: &mut z80::z80, port: u8, val: u8) {
+    //println!("invaders_port_out");
+
+    unsafe {
+        let p = &mut (*z.userdata.pac_ptr);
+        match port & 7 {
+            2 => p.invaders.shift_amount = val & 7,
+            3 => sound_latch1_w(p, val),
+            4 => p.invaders.shift_data = (val as u16) << 8 | p.invaders.shift_data >> 8,
+            5 => sound_latch2_w(p, val),
+            6 => {} // watchdog
+            _ => {}
+        }
+    }
+}
+
+// the interrupt is released once acknowledged, with the rst instruction
+// selected by the video timing on the data bus
+pub fn invaders_int_ack(z: &mut z80::z80) -> u8 {
+    //println!("invaders_int_ack");
+
+    z80_clear_irq(z);
+    unsafe {
+        return (*z.userdata.pac_ptr).invaders.int_vector;
+    }
+}
+
+// port 3: ufo, shot, base hit, invader hit, bonus base, amplifier
+fn sound_latch1_w(p: &mut pac::pac, val: u8) {
+    let rising = val & !p.invaders.sound_latches[0];
+    let falling = !val & p.invaders.sound_latches[0];
+    p.invaders.sound_latches[0] = val;
+    let s = &mut p.samples;
+
+    if rising & 0x01 != 0 {
+        samples_start(s, VOICE_UFO, SAMPLE_UFO, true);
+    }
+    if falling & 0x01 != 0 {
+        samples_stop(s, VOICE_UFO);
+    }
+    if rising & 0x02 != 0 {
+        samples_start(s, VOICE_SHOT, SAMPLE_SHOT, false);
+    }
+    if rising & 0x04 != 0 {
+        samples_start(s, VOICE_BASE_HIT, SAMPLE_BASE_HIT, false);
+    }
+    if falling & 0x04 != 0 {
+        samples_stop(s, VOICE_BASE_HIT);
+    }
+    if rising & 0x08 != 0 {
+        samples_start(s, VOICE_INVADER_HIT, SAMPLE_INVADER_HIT, false);
+    }
+    if rising & 0x10 != 0 {
+        samples_start(s, VOICE_BONUS_BASE, SAMPLE_BONUS_BASE, false);
+    }
+    p.sound_enabled = (val >> 5) & 1;
+}
+
+// port 5: the 4 notes of the fleet, ufo hit, cocktail flip
+fn sound_latch2_w(p: &mut pac::pac, val: u8) {
+    let rising = val & !p.invaders.sound_latches[1];
+    p.invaders.sound_latches[1] = val;
+    let s = &mut p.samples;
+
+    for note in 0..4 {
+        if rising & (1 << note) != 0 {
+            samples_start(s, VOICE_FLEET, SAMPLE_FLEET + note, false);
+        }
+    }
+    if rising & 0x10 != 0 {
+        samples_start(s, VOICE_UFO_HIT, SAMPLE_UFO_HIT, false);
+    }
+    p.flip_screen = (val >> 5) & 1;
+}
+
+// color of the gel overlay of the upright cabinet at (x, y) of the bitmap
+fn overlay_color(x: usize, y: usize) -> (u8, u8, u8) {
+    let red = (0xff, 0x20, 0x20);
+    let green = (0x20, 0xff, 0x20);
+    let white = (0xff, 0xff, 0xff);
+
+    if y >= 32 && y < 64 {
+        return red; // ufo
+    } else if y >= 184 && y < 240 {
+        return green; // shields and base
+    } else if y >= 240 && x >= 16 && x < 134 {
+        return green; // remaining bases
+    }
+    return white;
+}
+
+pub fn invaders_draw(p: &mut pac::pac, screen: &mut [u8]) {
+    //println!("invaders_draw");
+
+    // each line of the bitmap is 32 bytes, lsb first. The monitor is rotated:
+    // lines are drawn from left to right, their pixels from bottom to top.
+    for offs in 0..0x1c00 {
+        let byte: u8 = p.invaders.ram[0x400 + offs];
+        let x: usize = offs / 32;
+        for b in 0..8 {
+            let y: usize = 255 - ((offs % 32) * 8 + b);
+            let (r, gr, bl) = if (byte >> b) & 1 != 0 { overlay_color(x, y) } else { (0, 0, 0) };
+
+            // the cocktail cabinet flips the screen for player 2
+            let (sx, sy) = if p.flip_screen != 0 { (223 - x, 255 - y) } else { (x, y) };
+            let screenbuf_pos: usize = (sy + INVADERS_SCREEN_TOP) * PAC_SCREEN_WIDTH + sx;
+            screen[screenbuf_pos * 3 + 0] = r;
+            screen[screenbuf_pos * 3 + 1] = gr;
+            screen[screenbuf_pos * 3 + 2] = bl;
+        }
+    }
+}
+
+// generates audio for one frame
+fn invaders_sound_update(p: &mut pac::pac, out: &mut pac_output) {
+    //println!("invaders_sound_update");
+
+    if p.sound_enabled == 0 || p.mute_audio {
+        return;
+    }
+
+    let len = (p.sample_rate / PAC_FPS as i32) as usize;
+    let buffer = &mut p.audio_buffer[0..len];
+    buffer.fill(0);
+    samples_play(&mut p.samples, buffer, p.sample_rate as u32);
+    for i in 0..len {
+        pac_push_sample(out, p.audio_buffer[i]);
+    }
+}
+
+// updates emulation for "ms" milliseconds.
+pub fn invaders_update(p: &mut pac::pac, ms: u32, out: &mut pac_output) {
+    //println!("invaders_update");
+
+    let mid_frame: u32 = INVADERS_CYCLES_PER_FRAME / 2;
+    let mut count: u32 = ms * INVADERS_CLOCK_SPEED / 1000;
+    while count > 0 {
+        // up to the next interrupt at most
+        let next: u32 = if p.frame_cyc < mid_frame {
+            mid_frame
+        } else {
+            INVADERS_CYCLES_PER_FRAME
+        };
+        let mut slice: u32 = count.min(next - p.frame_cyc);
+        let extra: u32 = p.cpu.run_extra as u32;
+        let executed = z80_run(&mut p.cpu, slice as u64) as u32;
+        // stopped in the debugger: the rest of the time is not run. The
+        // cycles the cpu ran past the previous slice count as well, the
+        // ones past this slice stay in run_extra.
+        let stopped = p.debug.stop.is_some();
+        if stopped {
+            slice = slice.min(extra + executed);
+        }
+        count -= slice;
+        p.frame_cyc += slice;
+
+        // rst 1 when the beam reaches the middle of the screen...
+        if p.frame_cyc == mid_frame {
+            p.invaders.int_vector = 0xcf;
+            z80_assert_irq(&mut p.cpu);
+        }
+
+        // ...and rst 2 at vblank
+        if p.frame_cyc == INVADERS_CYCLES_PER_FRAME {
+            p.frame_cyc = 0;
+            p.invaders.int_vector = 0xd7;
+            z80_assert_irq(&mut p.cpu);
+
+            (p.driver.draw)(p, out.screen);
+            out.frames += 1;
+            invaders_sound_update(p, out);
+        }
+
+        if stopped {
+            return;
+        }
+    }
+}
@@ -0,0 +1,16 @@
+; Pac-Man invincibility patch (from http://cheat.retrogames.com), assembled
+; by build.rs. The collision with a ghost jumps to a check in the unused end
+; of the program rom, that skips it.
+
+        org 0x1774
+        jp check
+        db 0x32                 ; unchanged
+
+        org 0x3cdf
+        nop
+check:  and a
+        jr nz,skip
+        xor a
+        jp 0x1764
+skip:   xor a
+        jp 0x1777
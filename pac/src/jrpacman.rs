@@ -0,0 +1,211 @@
+#![allow(non_camel_case_types)]
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+
+use crate::*;
+
+// Jr. Pac-Man, based on MAME's jrpacman driver.
+// It runs on a Pac-Man board with more program rom (0x8000-0xdfff), twice as
+// many tiles and sprites selected by bank registers, a palette bank, and a
+// playfield of 54 columns that scrolls horizontally under the score lines.
+
+// width of the playfield, in tiles
+const JR_PLAYFIELD_TILES: i32 = 54;
+
+// The program roms are encrypted by PALs that garble bits 0, 2 and 7. The
+// encryption is a state machine, so rather than emulating it this table (from
+// David Caldwell, via MAME) gives the value each byte is xored with: "count"
+// bytes in a row are xored with "value".
+const decrypt_table: [(u16, u8); 80] = [
+    (0x00c1, 0x00), (0x0002, 0x80), (0x0004, 0x00), (0x0006, 0x80),
+    (0x0003, 0x00), (0x0002, 0x80), (0x0009, 0x00), (0x0004, 0x80),
+    (0x9968, 0x00), (0x0001, 0x80), (0x0002, 0x00), (0x0001, 0x80),
+    (0x0009, 0x00), (0x0002, 0x80), (0x0009, 0x00), (0x0001, 0x80),
+    (0x00af, 0x00), (0x000e, 0x04), (0x0002, 0x00), (0x0004, 0x04),
+    (0x001e, 0x00), (0x0001, 0x80), (0x0002, 0x00), (0x0001, 0x80),
+    (0x0002, 0x00), (0x0002, 0x80), (0x0009, 0x00), (0x0002, 0x80),
+    (0x0009, 0x00), (0x0002, 0x80), (0x0083, 0x00), (0x0001, 0x04),
+    (0x0001, 0x01), (0x0001, 0x00), (0x0002, 0x05), (0x0001, 0x00),
+    (0x0003, 0x04), (0x0003, 0x01), (0x0002, 0x00), (0x0001, 0x04),
+    (0x0003, 0x01), (0x0003, 0x00), (0x0003, 0x04), (0x0001, 0x01),
+    (0x002e, 0x00), (0x0078, 0x01), (0x0001, 0x04), (0x0001, 0x05),
+    (0x0001, 0x00), (0x0001, 0x01), (0x0001, 0x04), (0x0002, 0x00),
+    (0x0001, 0x01), (0x0001, 0x04), (0x0002, 0x00), (0x0001, 0x01),
+    (0x0001, 0x04), (0x0002, 0x00), (0x0001, 0x01), (0x0001, 0x04),
+    (0x0001, 0x05), (0x0001, 0x00), (0x0001, 0x01), (0x0001, 0x04),
+    (0x0002, 0x00), (0x0001, 0x01), (0x0001, 0x04), (0x0002, 0x00),
+    (0x0001, 0x01), (0x0001, 0x04), (0x0001, 0x05), (0x0001, 0x00),
+    (0x01b0, 0x01), (0x0001, 0x00), (0x0002, 0x01), (0x00ad, 0x00),
+    (0x0031, 0x01), (0x005c, 0x00), (0x0005, 0x01), (0x604e, 0x00),
+];
+
+// decrypts the program and builds the color proms
+pub fn jrpacman_init(p: &mut pac::pac) {
+    //println!("jrpacman_init");
+
+    let mut addr: usize = 0;
+    for (count, value) in decrypt_table {
+        for _ in 0..count {
+            p.rom[addr] ^= value;
+            addr += 1;
+        }
+    }
+
+    // the colors are split in two proms of 4 bits (low and high nibbles)
+    for i in 0..0x100 {
+        p.color_rom[i] = (p.color_rom[i] & 0x0f) | (p.color_rom[0x100 + i] << 4);
+    }
+    for i in 0..0x100 {
+        p.palette_rom[i] &= 0x0f;
+    }
+}
+
+pub fn jrpacman_rb(userdata: &mut userdata, addr: u16) -> u8 {
+    //println!("jrpacman_rb");
+
+    if addr < 0x4000 || (addr >= 0x8000 && addr < 0xe000) {
+        unsafe {
+            return (*userdata.pac_ptr).rom[addr as usize];
+        }
+    } else if addr >= 0x8000 {
+        return 0xff;
+    }
+    return pac_rb(userdata, addr);
+}
+
+pub fn jrpacman_wb(userdata: &mut userdata, addr: u16, val: u8) {
+    //println!("jrpacman_wb");
+
+    unsafe {
+        let p = &mut (*userdata.pac_ptr);
+        if addr >= 0x8000 {
+            // cannot write to rom
+        } else if addr == 0x5070 {
+            p.palette_bank = val & 1;
+        } else if addr == 0x5071 {
+            p.colortable_bank = val & 1;
+        } else if addr == 0x5073 {
+            p.bg_priority = val & 1;
+        } else if addr == 0x5074 {
+            p.char_bank = val & 1;
+        } else if addr == 0x5075 {
+            p.sprite_bank = val & 1;
+        } else if addr == 0x5080 {
+            p.scroll = val;
+        } else {
+            pac_wb(userdata, addr, val);
+        }
+    }
+}
+
+// video ram offset of the tile at screen row "row" (0-35) and playfield
+// column "col" (0-53, from the right). The two top and bottom rows hold the
+// scores and are only 30 columns wide.
+fn tile_offset(row: i32, col: i32) -> usize {
+    let r = row - 2;
+    let c = col + 2;
+    if r & 0x20 != 0 && c & 0x20 != 0 {
+        return 0;
+    } else if r & 0x20 != 0 {
+        return (c + (((r & 3) | 0x38) << 5)) as usize;
+    }
+    return (r + (c << 5)) as usize;
+}
+
+// draws a tile that may be partly off screen. Color 0 is skipped if
+// "transparent" is set.
+fn draw_tile_clipped(
+    screen: &mut [u8],
+    p: &mut pac::pac,
+    tile_no: u16,
+    pal: &mut [u8; 4],
+    x: i32,
+    y: i32,
+    transparent: bool,
+) {
+    for i in 0..8 * 8 {
+        let px: i32 = x + i % 8;
+        let py: i32 = y + i / 8;
+        if px < 0 || px >= PAC_SCREEN_WIDTH as i32 {
+            continue;
+        }
+
+        let color: u8 = p.tiles[tile_no as usize * 64 + i as usize];
+        if transparent && color == 0 {
+            continue;
+        }
+        let screenbuf_pos: usize = py as usize * PAC_SCREEN_WIDTH + px as usize;
+
+        let mut r = 0;
+        let mut g = 0;
+        let mut b = 0;
+        get_color(p, pal[color as usize], &mut r, &mut g, &mut b);
+        screen[screenbuf_pos * 3 + 0] = r;
+        screen[screenbuf_pos * 3 + 1] = g;
+        screen[screenbuf_pos * 3 + 2] = b;
+    }
+}
+
+fn draw_playfield(p: &mut pac::pac, screen: &mut [u8], transparent: bool) {
+    let mut palette: [u8; 4] = [0; 4];
+    let width = JR_PLAYFIELD_TILES * 8;
+
+    for row in 0..36 {
+        // the score lines don't scroll
+        let scroll: i32 = if row >= 2 && row < 34 { p.scroll as i32 } else { 0 };
+
+        for col in 0..JR_PLAYFIELD_TILES {
+            let offs = tile_offset(row, col);
+            // the color of a tile is shared by its whole row, except on the
+            // score lines
+            let color_offs = if offs < 0x700 { offs & 0x1f } else { offs + 0x80 };
+
+            let tile_no: u16 = p.ram[offs] as u16 | (p.char_bank as u16) << 8;
+            let palette_no: u8 = (p.ram[color_offs] & 0x1f) | p.colortable_bank << 5;
+            get_palette(p, palette_no, &mut palette);
+
+            // the playfield wraps around
+            let pos = (col * 8 - scroll).rem_euclid(width);
+            let x = PAC_SCREEN_WIDTH as i32 - 8 - pos;
+            if x > -8 {
+                draw_tile_clipped(screen, p, tile_no, &mut palette, x, row * 8, transparent);
+            }
+            if x + width < PAC_SCREEN_WIDTH as i32 {
+                draw_tile_clipped(screen, p, tile_no, &mut palette, x + width, row * 8, transparent);
+            }
+        }
+    }
+}
+
+pub fn jrpacman_draw(p: &mut pac::pac, screen: &mut [u8]) {
+    //println!("jrpacman_draw");
+
+    // 1. playfield, unless it goes over the sprites
+    if p.bg_priority != 0 {
+        screen.fill(0);
+    } else {
+        draw_playfield(p, screen, false);
+    }
+
+    // 2. drawing the 8 sprites (in reverse order)
+    let mut palette: [u8; 4] = [0; 4];
+    for s in (0..=7).rev() {
+        let x: i16 = (PAC_SCREEN_WIDTH as i16) - (p.sprite_pos[s * 2] as i16) + 15;
+        let y: i16 = (PAC_SCREEN_HEIGHT as i16) - (p.sprite_pos[s * 2 + 1] as i16) - 16;
+
+        let sprite_info: u8 = p.ram[0xff0 + s * 2];
+        let palette_no: u8 = (p.ram[0xff0 + s * 2 + 1] & 0x1f) | p.colortable_bank << 5;
+
+        let flip_x: u8 = (sprite_info >> 1) & 1;
+        let flip_y: u8 = (sprite_info >> 0) & 1;
+        let sprite_no: u8 = (sprite_info >> 2) | p.sprite_bank << 6;
+
+        get_palette(p, palette_no, &mut palette);
+        draw_sprite(screen, p, sprite_no, &mut palette, x, y, flip_x, flip_y);
+    }
+
+    // 3. playfield over the sprites
+    if p.bg_priority != 0 {
+        draw_playfield(p, screen, true);
+    }
+}
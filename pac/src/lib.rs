@@ -0,0 +1,48 @@
+#![allow(non_camel_case_types)]
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+#![cfg_attr(not(feature = "std"), no_std)]
+
+// The Pac-Man board and the games running on it (see driver.rs), built
+// without std nor allocator: the caller supplies the rom files, loaded in
+// the board's memory, and the buffers each frame is output to (pac_output).
+// Without the "std" feature, the diagnostics are not printed.
+
+use core::ptr;
+
+pub mod crush;
+pub mod debug;
+pub mod driver;
+pub mod eyes;
+pub mod invaders;
+pub mod jrpacman;
+pub mod mspacman;
+pub mod pac;
+pub mod pengo;
+pub mod samples;
+pub mod wsg;
+pub mod z80;
+
+pub use crush::*;
+pub use debug::*;
+pub use driver::*;
+pub use eyes::*;
+pub use invaders::*;
+pub use jrpacman::*;
+pub use mspacman::*;
+pub use pac::*;
+pub use pengo::*;
+pub use samples::*;
+pub use wsg::*;
+pub use z80::*;
+
+pub struct userdata {
+    pub pac_ptr: *mut pac::pac,
+}
+impl userdata {
+    pub fn new() -> Self {
+        Self {
+            pac_ptr: ptr::null_mut(),
+        }
+    }
+}
@@ -0,0 +1,160 @@
+#![allow(non_camel_case_types)]
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+
+use crate::*;
+
+// Ms. Pac-Man auxiliary board, based on MAME's pacman driver.
+// The aux board plugs into the Z80 socket of a Pac-Man board. It holds three
+// extra program roms (u5, u6, u7) whose address and data lines are scrambled,
+// and a latch that switches the cpu between the original Pac-Man roms and a
+// "decoded" bank: the Pac-Man code with forty 8 bytes patches copied over it,
+// plus the Ms. Pac-Man code at 0x3000 and 0x8000-0x97ff.
+// The latch is flipped whenever the cpu accesses one of the trap addresses.
+
+// data lines of the encrypted roms
+fn decrypt_data(e: u8) -> u8 {
+    return bitswap(e as u16, &[0, 4, 5, 7, 6, 3, 2, 1]) as u8;
+}
+
+// address lines of u6 and u7
+fn decrypt_addr12(i: usize) -> usize {
+    return bitswap(i as u16, &[11, 3, 7, 9, 10, 8, 6, 5, 4, 2, 1, 0]) as usize;
+}
+
+// address lines of u5
+fn decrypt_addr11(i: usize) -> usize {
+    return bitswap(i as u16, &[8, 7, 5, 9, 10, 6, 3, 4, 2, 1, 0]) as usize;
+}
+
+// (destination, source) of the patches copied over the Pac-Man code in the
+// decoded bank
+const patches: [(usize, usize); 40] = [
+    (0x0410, 0x8008),
+    (0x08e0, 0x81d8),
+    (0x0a30, 0x8118),
+    (0x0bd0, 0x80d8),
+    (0x0c20, 0x8120),
+    (0x0e58, 0x8168),
+    (0x0ea8, 0x8198),
+    (0x1000, 0x8020),
+    (0x1008, 0x8010),
+    (0x1288, 0x8098),
+    (0x1348, 0x8048),
+    (0x1688, 0x8088),
+    (0x16b0, 0x8188),
+    (0x16d8, 0x80c8),
+    (0x16f8, 0x81c8),
+    (0x19a8, 0x80a8),
+    (0x19b8, 0x81a8),
+    (0x2060, 0x8148),
+    (0x2108, 0x8018),
+    (0x21a0, 0x81a0),
+    (0x2298, 0x80a0),
+    (0x23e0, 0x80e8),
+    (0x2418, 0x8000),
+    (0x2448, 0x8058),
+    (0x2470, 0x8140),
+    (0x2488, 0x8080),
+    (0x24b0, 0x8180),
+    (0x24d8, 0x80c0),
+    (0x24f8, 0x81c0),
+    (0x2748, 0x8050),
+    (0x2780, 0x8090),
+    (0x27b8, 0x8190),
+    (0x2800, 0x8028),
+    (0x2b20, 0x8100),
+    (0x2b30, 0x8110),
+    (0x2bf0, 0x81d0),
+    (0x2cc0, 0x80d0),
+    (0x2cd8, 0x80e0),
+    (0x2cf0, 0x81e0),
+    (0x2d60, 0x8160),
+];
+
+// trap addresses (8 bytes each) that switch back to the Pac-Man roms
+const disable_traps: [u16; 7] = [0x0038, 0x03b0, 0x1600, 0x2120, 0x3ff0, 0x8000, 0x97f0];
+// trap address that switches to the decoded bank
+const enable_trap: u16 = 0x3ff8;
+
+// builds the decoded bank from the roms loaded in "rom": pacman.6e-6j at
+// 0x0000-0x3fff, u5 at 0x8000, u6 at 0x9000 and u7 at 0xb000. The Pac-Man
+// roms are then mirrored over u5-u7, as the normal bank sees them.
+pub fn mspacman_init(p: &mut pac::pac) {
+    //println!("mspacman_init");
+
+    let rom = &p.rom;
+    let drom = &mut p.aux_rom;
+
+    for i in 0..0x1000 {
+        drom[0x0000 + i] = rom[0x0000 + i]; // pacman.6e
+        drom[0x1000 + i] = rom[0x1000 + i]; // pacman.6f
+        drom[0x2000 + i] = rom[0x2000 + i]; // pacman.6h
+        drom[0x3000 + i] = decrypt_data(rom[0xb000 + decrypt_addr12(i)]); // u7
+    }
+    for i in 0..0x800 {
+        drom[0x8000 + i] = decrypt_data(rom[0x8000 + decrypt_addr11(i)]); // u5
+        drom[0x8800 + i] = decrypt_data(rom[0x9800 + decrypt_addr12(i)]); // half of u6
+        drom[0x9000 + i] = decrypt_data(rom[0x9000 + decrypt_addr12(i)]); // half of u6
+        drom[0x9800 + i] = rom[0x1800 + i]; // mirror of pacman.6f high
+    }
+    for i in 0..0x1000 {
+        drom[0xa000 + i] = rom[0x2000 + i]; // mirror of pacman.6h
+        drom[0xb000 + i] = rom[0x3000 + i]; // mirror of pacman.6j
+    }
+
+    for (dst, src) in patches {
+        for i in 0..8 {
+            drom[dst + i] = drom[src + i];
+        }
+    }
+
+    // the normal bank: Pac-Man at 0x8000-0xbfff too
+    for i in 0..0x4000 {
+        p.rom[0x8000 + i] = p.rom[i];
+    }
+
+    p.aux_decode = true;
+}
+
+// flips the decode latch if "addr" is a trap address. Returns the bank the
+// trap itself is read from, if it is one.
+fn check_traps(p: &mut pac::pac, addr: u16) -> Option<bool> {
+    if addr & 0xfff8 == enable_trap {
+        p.aux_decode = true;
+        return Some(true);
+    }
+    if disable_traps.contains(&(addr & 0xfff8)) {
+        p.aux_decode = false;
+        return Some(false);
+    }
+    return None;
+}
+
+pub fn mspacman_rb(userdata: &mut userdata, addr: u16) -> u8 {
+    //println!("mspacman_rb");
+
+    unsafe {
+        let p = &mut (*userdata.pac_ptr);
+        let decode = check_traps(p, addr).unwrap_or(p.aux_decode);
+
+        // roms are at 0x0000-0x3fff and 0x8000-0xbfff, the rest of the memory
+        // map mirrors the Pac-Man board's
+        if addr & 0x4000 == 0 {
+            if decode {
+                return p.aux_rom[addr as usize];
+            }
+            return p.rom[addr as usize];
+        }
+    }
+    return pac_rb(userdata, addr);
+}
+
+pub fn mspacman_wb(userdata: &mut userdata, addr: u16, val: u8) {
+    //println!("mspacman_wb");
+
+    unsafe {
+        check_traps(&mut (*userdata.pac_ptr), addr);
+    }
+    pac_wb(userdata, addr, val);
+}
@@ -0,0 +1,733 @@
+#![allow(non_camel_case_types)]
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+
+use crate::*;
+
+pub const PAC_CLOCK_SPEED: u32 = 3072000; // 3.072 MHz (= number of cycles per second)
+pub const PAC_FPS: u32 = 60;
+pub const PAC_CYCLES_PER_FRAME: u32 = PAC_CLOCK_SPEED / PAC_FPS;
+pub const PAC_SCREEN_WIDTH: usize = 224;
+pub const PAC_SCREEN_HEIGHT: usize = 288;
+pub const PAC_SCREEN_SIZE: usize = PAC_SCREEN_WIDTH * PAC_SCREEN_HEIGHT * 3; // rgb24
+pub const PAC_WSG_FRAME_LEN: usize = (WSG_SAMPLE_RATE / PAC_FPS) as usize;
+
+// the buffers the board outputs to, owned by the frontend
+pub struct pac_output<'b> {
+    pub screen: &'b mut [u8], // PAC_SCREEN_SIZE bytes, redrawn at each frame
+    pub audio: &'b mut [i16], // samples at the board's sample_rate
+    pub audio_len: usize,     // samples written to audio
+    pub frames: u32,          // frames drawn to screen
+}
+impl<'b> pac_output<'b> {
+    pub fn new(screen: &'b mut [u8], audio: &'b mut [i16]) -> Self {
+        Self {
+            screen: screen,
+            audio: audio,
+            audio_len: 0,
+            frames: 0,
+        }
+    }
+}
+
+// appends a sample to the audio buffer, dropped when the buffer is full
+pub fn pac_push_sample(out: &mut pac_output, sample: i16) {
+    if out.audio_len < out.audio.len() {
+        out.audio[out.audio_len] = sample;
+        out.audio_len += 1;
+    }
+}
+
+pub struct pac {
+    pub cpu: z80::z80,
+    pub rom: [u8; 0x10000],     // 0x0000-0x4000
+    pub aux_rom: [u8; 0x10000], // ms. pac-man decoded bank, pengo opcodes
+    pub aux_decode: bool,       // aux board latch: decoded bank selected
+    pub ram: [u8; 0x1000],      // 0x4000-0x5000
+    pub sprite_pos: [u8; 0x10], // 0x5060-0x506f
+
+    pub color_rom: [u8; 0x200], // 0x100-0x1ff: high nibbles of 4 bits proms
+    pub palette_rom: [u8; 0x400],
+    pub tile_rom: [u8; 0x2000],
+    pub sprite_rom: [u8; 0x2000],
+    pub sound_rom1: [u8; 0x100],
+    pub sound_rom2: [u8; 0x100],
+
+    pub tiles: [u8; 512 * 8 * 8],     // to store predecoded tiles
+    pub sprites: [u8; 128 * 16 * 16], // to store predecoded sprites
+
+    pub int_vector: u8,
+    pub vblank_enabled: u8,
+    pub frame_cyc: u32, // cycles run in the current frame
+    pub sound_enabled: u8,
+    pub flip_screen: u8,
+
+    // video banks, on the boards that have them
+    pub palette_bank: u8,
+    pub colortable_bank: u8,
+    pub char_bank: u8,
+    pub sprite_bank: u8,
+    pub bg_priority: u8, // playfield drawn over the sprites
+    pub scroll: u8,
+
+    // game running on the board, and the state of its inputs (1 = pressed)
+    pub driver: &'static driver::driver,
+    pub inputs: [u8; NB_INPUTS],
+    pub dsw1: u8,
+    pub dsw2: u8,
+    pub fetch_pc: u16, // address of the last opcode fetch, for protections
+
+    // state of the other boards
+    pub invaders: invaders::invaders,
+    pub samples: samples::samples, // sound samples, on the boards without a wsg
+
+    // audio
+    pub sound_chip: wsg::wsg,
+    pub audio_buffer: [i16; PAC_WSG_FRAME_LEN], // one frame of the wsg
+    pub sample_rate: i32,
+    pub mute_audio: bool,
+
+    pub debug: debug::pac_debug,
+}
+impl pac {
+    pub fn new() -> Self {
+        Self {
+            cpu: z80::z80::new(userdata::new()),
+            rom: [0; 0x10000],
+            aux_rom: [0; 0x10000],
+            aux_decode: false,
+            ram: [0; 0x1000],
+            sprite_pos: [0; 0x10],
+            color_rom: [0; 0x200],
+            palette_rom: [0; 0x400],
+            tile_rom: [0; 0x2000],
+            sprite_rom: [0; 0x2000],
+            sound_rom1: [0; 0x100],
+            sound_rom2: [0; 0x100],
+            tiles: [0; 512 * 8 * 8],
+            sprites: [0; 128 * 16 * 16],
+            int_vector: 0,
+            vblank_enabled: 0,
+            frame_cyc: 0,
+            sound_enabled: 0,
+            flip_screen: 0,
+            palette_bank: 0,
+            colortable_bank: 0,
+            char_bank: 0,
+            sprite_bank: 0,
+            bg_priority: 0,
+            scroll: 0,
+            driver: &PACMAN,
+            inputs: [0; NB_INPUTS],
+            dsw1: 0xff,
+            dsw2: 0xff,
+            fetch_pc: 0,
+            invaders: invaders::invaders::new(),
+            samples: samples::samples::new(),
+            // audio
+            sound_chip: wsg::wsg::new(),
+            audio_buffer: [0; PAC_WSG_FRAME_LEN],
+            sample_rate: 0,
+            mute_audio: false,
+            debug: debug::pac_debug::new(),
+        }
+    }
+}
+
+// builds the value of an input port from the inputs wired to its bits.
+// Inputs are active low.
+pub fn read_port(p: &pac, bits: &[input; 8]) -> u8 {
+    let mut value: u8 = 0;
+    for (i, bit) in bits.iter().enumerate() {
+        let level = match bit {
+            input::Unused | input::Upright => 1,
+            input::Low => 0,
+            _ => !p.inputs[*bit as usize] & 1,
+        };
+        value |= level << i;
+    }
+    return value;
+}
+
+// sets the state of an input (1 = pressed)
+pub fn pac_set_input(p: &mut pac, inp: input, pressed: bool) {
+    p.inputs[inp as usize] = pressed as u8;
+}
+
+pub fn pac_rb(userdata: &mut userdata, addr: u16) -> u8 {
+    //println!("pac_rb");
+    // according to https://www.csh.rit.edu/~jerry/arcade/pacman/daves/
+    // the highest bit of the address is unused
+    let addr = addr & 0x7fff;
+
+    unsafe {
+        if addr < 0x4000 {
+            return (*userdata.pac_ptr).rom[addr as usize];
+        } else if addr < 0x5000 {
+            return (*userdata.pac_ptr).ram[(addr - 0x4000) as usize];
+        } else if addr <= 0x50ff {
+            // io
+            if addr == 0x5003 {
+                return (*userdata.pac_ptr).flip_screen;
+            } else if addr == 0x5004 || addr == 0x5005 {
+                // lamps, not used in pacman
+                return 0;
+            } else if addr == 0x5006 {
+                // coin lockout, not used in pacman
+                return 0;
+            } else if addr == 0x5007 {
+                // coin counter
+            } else if addr >= 0x5000 && addr <= 0x503f {
+                // in 0
+                let p = &(*userdata.pac_ptr);
+                return read_port(p, &p.driver.in0);
+            } else if addr >= 0x5040 && addr <= 0x507f {
+                // in 1
+                let p = &(*userdata.pac_ptr);
+                return read_port(p, &p.driver.in1);
+            } else if addr >= 0x5080 && addr <= 0x50bf {
+                // dip switch
+                return (*userdata.pac_ptr).dsw1;
+            } else if addr >= 0x50c0 && addr <= 0x50ff {
+                // second dip switch, on the boards that have one
+                return (*userdata.pac_ptr).dsw2;
+            }
+        } else {
+            #[cfg(feature = "std")]
+            println!("ERR: read at {:04x}", addr);
+            return 0;
+        }
+
+        return 0xff;
+    }
+}
+
+// memory map of the boards with more program rom at 0x8000-0xbfff
+pub fn woodpek_rb(userdata: &mut userdata, addr: u16) -> u8 {
+    //println!("woodpek_rb");
+
+    if addr >= 0x8000 && addr < 0xc000 {
+        unsafe {
+            return (*userdata.pac_ptr).rom[addr as usize];
+        }
+    }
+    return pac_rb(userdata, addr);
+}
+
+pub fn pac_wb(userdata: &mut userdata, addr: u16, val: u8) {
+    //println!("pac_wb");
+    // according to https://www.csh.rit.edu/~jerry/arcade/pacman/daves/
+    // the highest bit of the address is unused
+    let addr = addr & 0x7fff;
+
+    unsafe {
+        if addr < 0x4000 {
+            // cannot write to rom
+        } else if addr < 0x5000 {
+            (*userdata.pac_ptr).ram[(addr - 0x4000) as usize] = val;
+        } else if addr <= 0x50ff {
+            // io
+            if addr == 0x5000 {
+                // the mask also releases an interrupt not yet taken
+                (*userdata.pac_ptr).vblank_enabled = val & 1;
+                if val & 1 == 0 {
+                    z80_clear_irq(&mut (*userdata.pac_ptr).cpu);
+                }
+            } else if addr == 0x5001 {
+                (*userdata.pac_ptr).sound_enabled = val & 1;
+            } else if addr == 0x5002 {
+                // not connected: the ms. pac-man aux board is switched by
+                // trap addresses (see mspacman.rs)
+            } else if addr == 0x5003 {
+                (*userdata.pac_ptr).flip_screen = val & 1;
+            } else if addr == 0x5004 || addr == 0x5005 {
+                // lamps, not used in pacman
+            } else if addr == 0x5006 {
+                // coin lockout, not used in pacman
+            } else if addr == 0x5007 {
+                // coin counter
+            } else if addr >= 0x5040 && addr <= 0x505f {
+                // audio
+                wsg_write(
+                    &mut (*userdata.pac_ptr).sound_chip,
+                    (addr - 0x5040) as u8,
+                    val,
+                );
+            } else if addr >= 0x5060 && addr <= 0x506f {
+                (*userdata.pac_ptr).sprite_pos[(addr - 0x5060) as usize] = val;
+            } else if addr >= 0x50c0 && addr <= 0x50ff {
+                // watchdog: no action is needed here, because watchdog is not
+                // implemented on the emu.
+            }
+        } else {
+            #[cfg(feature = "std")]
+            println!("ERR: write {:02x} at {:04x}", val, addr);
+        }
+    }
+}
+
+pub fn port_in(_z: &mut z80::z80, _port: u8) -> u8 {
+    //println!("port_in");
+
+    return 0;
+}
+
+pub fn port_out(z: &mut z80::z80, port: u8, val: u8) {
+    //println!("port_out");
+
+    // setting the interrupt vector
+    if port == 0 {
+        unsafe {
+            (*z.userdata.pac_ptr).int_vector = val;
+        }
+    }
+}
+
+// the interrupt vector latched by port_out is put on the data bus
+pub fn int_ack(z: &mut z80::z80) -> u8 {
+    //println!("int_ack");
+
+    unsafe {
+        return (*z.userdata.pac_ptr).int_vector;
+    }
+}
+
+// MARK: graphics
+
+// the color palette is stored in color_rom (82s123.7f). Each byte corresponds
+// to one color and is composed of three components red, green and blue
+// following that pattern: 0bBBGGGRRR.
+// Each color component corresponds to a color intensity.
+// @TODO: add comment on how to get from color intensity to RGB color.
+// The palette bank (Jr. Pac-Man, Pengo) selects the upper 16 colors.
+pub fn get_color(p: &mut pac, color_no: u8, r: &mut u8, g: &mut u8, b: &mut u8) {
+    //println!("get_color");
+
+    let data: u8 = p.color_rom[color_no as usize + p.palette_bank as usize * 0x10];
+
+    *r = ((data >> 0) & 1) * 0x21 + ((data >> 1) & 1) * 0x47 + ((data >> 2) & 1) * 0x97;
+    *g = ((data >> 3) & 1) * 0x21 + ((data >> 4) & 1) * 0x47 + ((data >> 5) & 1) * 0x97;
+    *b = ((data >> 6) & 1) * 0x51 + ((data >> 7) & 1) * 0xae;
+}
+
+// Color palettes are defined in palette_rom (82s126.4a): each palette contains
+// four colors (one byte for each color). The number of the palette wraps
+// within the palette rom of the board (0x100 bytes, 0x400 on Pengo).
+pub fn get_palette(p: &mut pac, pal_no: u8, pal: &mut [u8; 4]) {
+    //println!("get_palette");
+
+    let pal_no = pal_no as usize % (palette_rom_len(p.driver) / 4);
+
+    pal[0] = p.palette_rom[pal_no * 4 + 0];
+    pal[1] = p.palette_rom[pal_no * 4 + 1];
+    pal[2] = p.palette_rom[pal_no * 4 + 2];
+    pal[3] = p.palette_rom[pal_no * 4 + 3];
+}
+
+// size of the palette rom loaded by the driver
+fn palette_rom_len(d: &driver::driver) -> usize {
+    let mut len = 0;
+    for info in d.roms.iter().filter(|info| info.region == rom_region::Palette) {
+        len = len.max(info.offset + info.size);
+    }
+    if len == 0 {
+        return 0x100;
+    }
+    return len;
+}
+
+// decodes a strip from pacman tile/sprite roms to a bitmap output where each
+// byte represents one pixel.
+pub fn decode_strip(
+    _p: &mut pac,
+    input: *mut u8,
+    output: *mut u8,
+    bx: i32,
+    by: i32,
+    img_width: i32,
+) {
+    //println!("decode_strip");
+
+    let base_i: i32 = by * img_width + bx;
+    unsafe {
+        for x in 0..8 {
+            let strip: u8 = *(input.add(x));
+
+            for y in 0..4 {
+                // bitmaps are stored mirrored in memory, so we need to read it
+                // starting from the bottom right:
+                let i = (3 - y) * img_width + 7 - x as i32;
+                *output.add((base_i + i) as usize) = (strip >> (y % 4)) & 1;
+                *output.add((base_i + i) as usize) |= ((strip >> (y % 4 + 4)) & 1) << 1;
+            }
+        }
+    }
+}
+
+// preloads sprites and tiles
+pub fn preload_images(p: &mut pac) {
+    //println!("preload_images");
+
+    // sprites and tiles are images that are stored in sprite/tile rom.
+    // in memory, those images are represented using vertical "strips"
+    // of 8*4px, each strip being 8 bytes long (each pixel is stored on two
+    // bits)
+    let LEN_STRIP_BYTES: i32 = 8;
+    // where each strip goes in the image depends on the board
+    let layout = p.driver.gfx;
+
+    // tiles are 8*8px images. in memory, they are composed of two strips.
+    let NB_PIXELS_PER_TILE: i32 = 8 * 8;
+    let TILE_WIDTH: i32 = 8;
+    let NB_TILES: i32 = 512;
+
+    //memset(p->tiles, 0, NB_TILES * NB_PIXELS_PER_TILE);
+    p.tiles = [0; 512 * 8 * 8];
+    unsafe {
+        for i in 0..NB_TILES {
+            let tile: *mut u8 = &mut p.tiles[(i * NB_PIXELS_PER_TILE) as usize];
+            let rom: *mut u8 = &mut p.tile_rom[(i * (LEN_STRIP_BYTES * 2)) as usize];
+
+            for (strip, (bx, by)) in layout.tile_strips.iter().enumerate() {
+                decode_strip(p, rom.add(strip * LEN_STRIP_BYTES as usize), tile, *bx, *by, TILE_WIDTH);
+            }
+        }
+    }
+
+    // sprites are 16*16px images. in memory, they are composed of 8 strips.
+    let NB_PIXELS_PER_SPRITE: i32 = 16 * 16;
+    let SPRITE_WIDTH: i32 = 16;
+    let NB_SPRITES: i32 = 128;
+
+    //memset(p->sprites, 0, NB_SPRITES * NB_PIXELS_PER_SPRITE);
+    p.sprites = [0; 128 * 16 * 16];
+    unsafe {
+        for i in 0..NB_SPRITES {
+            let sprite: *mut u8 = &mut p.sprites[(i * NB_PIXELS_PER_SPRITE) as usize];
+            let rom: *mut u8 = &mut p.sprite_rom[(i * (LEN_STRIP_BYTES * 8)) as usize];
+
+            for (strip, (bx, by)) in layout.sprite_strips.iter().enumerate() {
+                decode_strip(p, rom.add(strip * LEN_STRIP_BYTES as usize), sprite, *bx, *by, SPRITE_WIDTH);
+            }
+        }
+    }
+}
+
+pub fn draw_tile(screen: &mut [u8], p: &mut pac, tile_no: u16, pal: &mut [u8; 4], x: u16, y: u16) {
+    //println!("draw_tile");
+
+    if x < 0 || x >= PAC_SCREEN_WIDTH as u16 {
+        return;
+    }
+
+    for i in 0..8 * 8 {
+        let px: i32 = i % 8;
+        let py: i32 = i / 8;
+
+        let color: u8 = p.tiles[tile_no as usize * 64 + i as usize];
+        let screenbuf_pos: i32 = (y as i32 + py) * PAC_SCREEN_WIDTH as i32 + (x as i32 + px);
+
+        let mut r = 0;
+        let mut g = 0;
+        let mut b = 0;
+        get_color(p, pal[color as usize], &mut r, &mut g, &mut b);
+        screen[screenbuf_pos as usize * 3 + 0] = r;
+        screen[screenbuf_pos as usize * 3 + 1] = g;
+        screen[screenbuf_pos as usize * 3 + 2] = b;
+    }
+}
+
+pub fn draw_sprite(
+    screen: &mut [u8],
+    p: &mut pac,
+    sprite_no: u8,
+    pal: &mut [u8; 4],
+    x: i16,
+    y: i16,
+    flip_x: u8,
+    flip_y: u8,
+) {
+    //println!("draw_sprite");
+
+    if x <= -16 || x > PAC_SCREEN_WIDTH as i16 {
+        return;
+    }
+
+    let base_i: i32 = y as i32 * PAC_SCREEN_WIDTH as i32 + x as i32;
+
+    for i in 0..16 * 16 {
+        let px: i32 = i % 16;
+        let py: i32 = i / 16;
+
+        let color: u8 = p.sprites[sprite_no as usize * 256 + i as usize];
+
+        // color 0 is transparent
+        if pal[color as usize] == 0 {
+            continue;
+        }
+
+        let x_pos: i32;
+        if flip_x != 0 {
+            x_pos = 15 - px;
+        } else {
+            x_pos = px;
+        }
+
+        let y_pos: i32;
+        if flip_y != 0 {
+            y_pos = 15 - py;
+        } else {
+            y_pos = py;
+        }
+
+        let screenbuf_pos: i32 = base_i + y_pos * PAC_SCREEN_WIDTH as i32 + x_pos;
+
+        if x as i32 + x_pos < 0 || x as i32 + x_pos >= PAC_SCREEN_WIDTH as i32 {
+            continue;
+        }
+
+        let mut r = 0;
+        let mut g = 0;
+        let mut b = 0;
+
+        get_color(p, pal[color as usize], &mut r, &mut g, &mut b);
+
+        screen[screenbuf_pos as usize * 3 + 0] = r;
+        screen[screenbuf_pos as usize * 3 + 1] = g;
+        screen[screenbuf_pos as usize * 3 + 2] = b;
+    }
+}
+
+// tile number in the current char bank
+fn tile_code(p: &pac, tile_no: u8) -> u16 {
+    return tile_no as u16 | (p.char_bank as u16) << 8;
+}
+
+// palette of a tile or sprite in the current color table and palette banks
+fn tile_palette(p: &pac, palette_no: u8) -> u8 {
+    return (palette_no & 0x1f) | p.colortable_bank << 5 | p.palette_bank << 6;
+}
+
+pub fn pac_draw(p: &mut pac, screen: &mut [u8]) {
+    //println!("pac_draw");
+
+    // 1. writing tiles according to VRAM
+
+    let VRAM_SCREEN_BOT: u16 = 0x4000;
+    let VRAM_SCREEN_MID: u16 = 0x4000 + 64;
+    let VRAM_SCREEN_TOP: u16 = 0x4000 + 64 + 0x380;
+
+    let mut x: i32;
+    let mut y: i32;
+    let mut i: i32;
+    let mut palette: [u8; 4] = [0; 4];
+
+    // bottom of screen:
+    x = 31;
+    y = 34;
+    i = VRAM_SCREEN_BOT as i32;
+    while x != 31 || y != 36 {
+        let tile_no: u8 = pac_rb(&mut p.cpu.userdata, i as u16);
+        let palette_no: u8 = pac_rb(&mut p.cpu.userdata, i as u16 + 0x400);
+
+        get_palette(p, tile_palette(p, palette_no), &mut palette);
+        draw_tile(screen, p, tile_code(p, tile_no), &mut palette, (x as u16 - 2) * 8, y as u16 * 8);
+
+        i += 1;
+        if x == 0 {
+            x = 31;
+            y += 1;
+        } else {
+            x -= 1;
+        }
+    }
+
+    // middle of the screen:
+    x = 29;
+    y = 2;
+    i = VRAM_SCREEN_MID as i32;
+    while x != 1 || y != 2 {
+        let tile_no: u8 = pac_rb(&mut p.cpu.userdata, i as u16);
+        let palette_no: u8 = pac_rb(&mut p.cpu.userdata, i as u16 + 0x400);
+
+        get_palette(p, tile_palette(p, palette_no), &mut palette);
+        draw_tile(screen, p, tile_code(p, tile_no), &mut palette, (x as u16 - 2) * 8, y as u16 * 8);
+
+        i += 1;
+        if y == 33 {
+            y = 2;
+            x -= 1;
+        } else {
+            y += 1;
+        }
+    }
+
+    // top of the screen:
+    x = 31;
+    y = 0;
+    i = VRAM_SCREEN_TOP as i32;
+    while x != 31 || y != 2 {
+        let tile_no: u8 = pac_rb(&mut p.cpu.userdata, i as u16);
+        let palette_no: u8 = pac_rb(&mut p.cpu.userdata, i as u16 + 0x400);
+
+        get_palette(p, tile_palette(p, palette_no), &mut palette);
+        draw_tile(screen, p, tile_code(p, tile_no), &mut palette, (x as u16 - 2) * 8, y as u16 * 8);
+
+        i += 1;
+        if x == 0 {
+            x = 31;
+            y += 1;
+        } else {
+            x -= 1;
+        }
+    }
+
+    // 2. drawing the 8 sprites (in reverse order)
+    let VRAM_SPRITES_INFO: u16 = 0x4FF0;
+    for s in (0..=7).rev() {
+        // the screen coordinates of a sprite start on the lower right corner
+        // of the main screen:
+        let x: i16 = (PAC_SCREEN_WIDTH as i16) - (p.sprite_pos[s * 2] as i16) + 15;
+        let y: i16 = (PAC_SCREEN_HEIGHT as i16) - (p.sprite_pos[s * 2 + 1] as i16) - 16;
+
+        let sprite_info: u8 = pac_rb(&mut p.cpu.userdata, VRAM_SPRITES_INFO + (s as u16 * 2));
+        let palette_no: u8 = pac_rb(
+            &mut p.cpu.userdata,
+            VRAM_SPRITES_INFO + (s as u16 * 2) + 1,
+        );
+
+        let flip_x: u8 = (sprite_info >> 1) & 1;
+        let flip_y: u8 = (sprite_info >> 0) & 1;
+        let sprite_no: u8 = (sprite_info >> 2) | p.sprite_bank << 6;
+
+        get_palette(p, tile_palette(p, palette_no), &mut palette);
+        draw_sprite(screen, p, sprite_no, &mut palette, x, y, flip_x, flip_y);
+    }
+}
+
+// generates audio for one frame
+pub fn sound_update(p: &mut pac, out: &mut pac_output) {
+    //println!("sound_update");
+
+    if p.sound_enabled == 0 || p.mute_audio {
+        return;
+    }
+
+    // update the WSG (filling the audio buffer)
+    wsg_play(&mut p.sound_chip, &mut p.audio_buffer);
+
+    // resampling the 96kHz audio stream from the WSG into a 44.1kHz one
+    let d: f32 = WSG_SAMPLE_RATE as f32 / p.sample_rate as f32;
+    for i in 0..p.sample_rate / PAC_FPS as i32 {
+        let pos: i32 = (d * i as f32) as i32;
+        pac_push_sample(out, p.audio_buffer[pos as usize]);
+    }
+}
+
+// the rom regions must be loaded before: the driver decrypts them here
+pub fn pac_init(p: &mut pac) {
+    //println!("pac_init");
+
+    z80_init(&mut p.cpu);
+    //pac-->z80-->userdata-->pac_ptr
+    p.cpu.userdata.pac_ptr = p;
+    let d = p.driver;
+    p.cpu.read_byte = d.read_byte;
+    p.cpu.write_byte = d.write_byte;
+    p.cpu.port_in = d.port_in;
+    p.cpu.port_out = d.port_out;
+    p.cpu.int_ack = d.int_ack;
+    p.cpu.read_opcode = d.read_opcode;
+    p.dsw1 = dip_default(d.dsw1);
+    p.dsw2 = dip_default(d.dsw2);
+
+    // rom decryption, banking...
+    if let Some(init) = d.init {
+        init(p);
+    }
+
+    preload_images(p);
+
+    // audio
+    wsg_init(&mut p.sound_chip, p.sound_rom1);
+    p.sample_rate = 44100;
+    p.mute_audio = false;
+}
+
+pub fn pac_quit(_p: &mut pac) {
+    //println!("pac_quit");
+}
+
+// updates emulation for "ms" milliseconds. The frames and the audio are
+// output to "out".
+pub fn pac_update(p: &mut pac, ms: u32, out: &mut pac_output) {
+    //println!("pac_update");
+
+    // stopped in the debugger
+    if p.debug.stop.is_some() {
+        return;
+    }
+
+    // boards with their own timings
+    if let Some(update) = p.driver.update {
+        return update(p, ms, out);
+    }
+
+    // machine executes exactly PAC_CLOCK_SPEED cycles every second,
+    // so we need to execute "ms * PAC_CLOCK_SPEED / 1000"
+    let mut count: u32 = ms * PAC_CLOCK_SPEED / 1000;
+    while count > 0 {
+        // up to vblank at most
+        let mut slice: u32 = count.min(PAC_CYCLES_PER_FRAME - p.frame_cyc);
+        let extra: u32 = p.cpu.run_extra as u32;
+        let executed = z80_run(&mut p.cpu, slice as u64) as u32;
+        // stopped in the debugger: the rest of the time is not run. The
+        // cycles the cpu ran past the previous slice count as well, the
+        // ones past this slice stay in run_extra.
+        let stopped = p.debug.stop.is_some();
+        if stopped {
+            slice = slice.min(extra + executed);
+        }
+        count -= slice;
+        p.frame_cyc += slice;
+
+        if p.frame_cyc == PAC_CYCLES_PER_FRAME {
+            p.frame_cyc = 0;
+
+            // trigger vblank if enabled. The line stays asserted until the
+            // game clears vblank_enabled.
+            if p.vblank_enabled != 0 {
+                z80_assert_irq(&mut p.cpu);
+
+                (p.driver.draw)(p, out.screen);
+                out.frames += 1;
+                sound_update(p, out);
+            }
+        }
+
+        if stopped {
+            return;
+        }
+    }
+}
+
+// a rom patch: bytes to write from each address on
+pub type pac_patch = &'static [(u16, &'static [u8])];
+
+// src/invincibility.asm, assembled by build.rs
+pub const pac_invincibility_patch: pac_patch = include!(concat!(env!("OUT_DIR"), "/invincibility.rs"));
+
+pub fn pac_apply_patch(p: &mut pac, patch: pac_patch) {
+    //println!("pac_apply_patch");
+
+    for (org, bytes) in patch {
+        let org = *org as usize;
+        p.rom[org..org + bytes.len()].copy_from_slice(bytes);
+    }
+}
+
+pub fn pac_cheat_invincibility(p: &mut pac) {
+    //println!("pac_cheat_invincibility");
+
+    pac_apply_patch(p, pac_invincibility_patch);
+}
@@ -0,0 +1,135 @@
+#![allow(non_camel_case_types)]
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+
+use crate::*;
+
+// Pengo, based on MAME's pengo driver.
+// Sega's board has the same video (tiles, sprites, color proms) and WSG
+// sound as Pac-Man, so pac_draw and the wsg are reused as is. It differs by
+// its memory map (32kB of rom, ram and io moved to 0x8000), a latch for its
+// video banks, and its encrypted cpu, a Sega 315-5010.
+
+// The 315-5010 encrypts bits 3, 5 and 7 of each byte, differently for opcodes
+// and data. The row of the table is given by bits 0, 4, 8 and 12 of the
+// address, the column by bits 3 and 5 of the byte; bytes with bit 7 set use
+// the mirror column, xored with 0xa8.
+const convtable: [[u8; 4]; 32] = [
+    // opcode                   data                     address
+    [0xa0, 0x88, 0x88, 0xa0], [0x28, 0xa0, 0x28, 0xa0], // ...0...0...0...0
+    [0x28, 0xa0, 0x28, 0xa0], [0xa0, 0x88, 0x88, 0xa0], // ...0...0...0...1
+    [0xa0, 0x88, 0x00, 0x28], [0xa0, 0x88, 0x00, 0x28], // ...0...0...1...0
+    [0x08, 0x20, 0xa8, 0x80], [0xa0, 0x88, 0x88, 0xa0], // ...0...0...1...1
+    [0x08, 0x08, 0xa8, 0xa8], [0x28, 0xa0, 0x28, 0xa0], // ...0...1...0...0
+    [0xa0, 0x88, 0x00, 0x28], [0x08, 0x08, 0xa8, 0xa8], // ...0...1...0...1
+    [0xa0, 0x88, 0x00, 0x28], [0xa0, 0x88, 0x00, 0x28], // ...0...1...1...0
+    [0xa0, 0x88, 0x00, 0x28], [0x00, 0x00, 0xa8, 0xa8], // ...0...1...1...1
+    [0x28, 0xa0, 0x28, 0xa0], [0x08, 0x08, 0xa8, 0xa8], // ...1...0...0...0
+    [0x28, 0xa0, 0x28, 0xa0], [0x28, 0xa0, 0x28, 0xa0], // ...1...0...0...1
+    [0xa0, 0x88, 0x00, 0x28], [0xa0, 0x88, 0x00, 0x28], // ...1...0...1...0
+    [0xa8, 0xa8, 0x08, 0x08], [0x88, 0x80, 0x08, 0x00], // ...1...0...1...1
+    [0x08, 0x20, 0xa8, 0x80], [0x28, 0xa0, 0x28, 0xa0], // ...1...1...0...0
+    [0x08, 0x08, 0xa8, 0xa8], [0x88, 0x80, 0x08, 0x00], // ...1...1...0...1
+    [0xa0, 0x88, 0x00, 0x28], [0xa0, 0x88, 0x00, 0x28], // ...1...1...1...0
+    [0x08, 0x20, 0xa8, 0x80], [0x88, 0x80, 0x08, 0x00], // ...1...1...1...1
+];
+
+// decrypts the program: data stays in "rom", opcodes go to "aux_rom"
+pub fn pengo_init(p: &mut pac::pac) {
+    //println!("pengo_init");
+
+    for addr in 0..0x8000 {
+        let src: u8 = p.rom[addr];
+
+        let row: usize =
+            (addr & 1) | ((addr >> 4) & 1) << 1 | ((addr >> 8) & 1) << 2 | ((addr >> 12) & 1) << 3;
+        let mut col: usize = ((src >> 3) & 1) as usize | (((src >> 5) & 1) << 1) as usize;
+        let mut xor: u8 = 0;
+        if src & 0x80 != 0 {
+            col = 3 - col;
+            xor = 0xa8;
+        }
+
+        p.aux_rom[addr] = (src & !0xa8) | (convtable[2 * row][col] ^ xor);
+        p.rom[addr] = (src & !0xa8) | (convtable[2 * row + 1][col] ^ xor);
+    }
+
+    for i in 0..0x400 {
+        p.palette_rom[i] &= 0x0f;
+    }
+}
+
+pub fn pengo_rb(userdata: &mut userdata, addr: u16) -> u8 {
+    //println!("pengo_rb");
+
+    unsafe {
+        let p = &(*userdata.pac_ptr);
+        if addr < 0x8000 {
+            return p.rom[addr as usize];
+        } else if addr < 0x9000 {
+            return p.ram[(addr - 0x8000) as usize];
+        } else if addr <= 0x903f {
+            return p.dsw2;
+        } else if addr <= 0x907f {
+            return p.dsw1;
+        } else if addr <= 0x90bf {
+            // in 1
+            return read_port(p, &p.driver.in1);
+        } else if addr <= 0x90ff {
+            // in 0
+            return read_port(p, &p.driver.in0);
+        }
+    }
+    return 0xff;
+}
+
+pub fn pengo_read_opcode(z: &mut z80::z80, addr: u16) -> u8 {
+    //println!("pengo_read_opcode");
+
+    if addr < 0x8000 {
+        unsafe {
+            return (*z.userdata.pac_ptr).aux_rom[addr as usize];
+        }
+    }
+    return pengo_rb(&mut z.userdata, addr);
+}
+
+pub fn pengo_wb(userdata: &mut userdata, addr: u16, val: u8) {
+    //println!("pengo_wb");
+
+    unsafe {
+        let p = &mut (*userdata.pac_ptr);
+        if addr < 0x8000 {
+            // cannot write to rom
+        } else if addr < 0x9000 {
+            p.ram[(addr - 0x8000) as usize] = val;
+        } else if addr <= 0x901f {
+            // audio
+            wsg_write(&mut p.sound_chip, (addr - 0x9000) as u8, val);
+        } else if addr >= 0x9020 && addr <= 0x902f {
+            p.sprite_pos[(addr - 0x9020) as usize] = val;
+        } else if addr >= 0x9040 && addr <= 0x9047 {
+            // latch: each address sets one output to bit 0 of the value
+            let bit = val & 1;
+            match addr - 0x9040 {
+                0 => {
+                    p.vblank_enabled = bit;
+                    if bit == 0 {
+                        z80_clear_irq(&mut p.cpu);
+                    }
+                }
+                1 => p.sound_enabled = bit,
+                2 => p.palette_bank = bit,
+                3 => p.flip_screen = bit,
+                6 => p.colortable_bank = bit,
+                7 => {
+                    p.char_bank = bit;
+                    p.sprite_bank = bit;
+                }
+                _ => {} // coin counters
+            }
+        } else if addr == 0x9070 {
+            // watchdog
+        }
+    }
+}
@@ -0,0 +1,109 @@
+#![allow(non_camel_case_types)]
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+
+// Sound samples, for the boards whose sounds come from discrete circuits
+// rather than a sound chip. As MAME does with its samples sets, each effect
+// is a wav file recorded from the real board, started and stopped by the
+// game through its sound latches.
+// The samples are loaded by the caller (from the wav files) and live for
+// the whole run.
+
+// most samples and voices of a board
+pub const MAX_SAMPLES: usize = 16;
+pub const MAX_VOICES: usize = 8;
+
+// a sample, converted to 16 bits mono
+#[derive(Copy, Clone)]
+pub struct sample {
+    pub rate: u32,
+    pub data: &'static [i16],
+}
+
+// a channel, playing one sample at a time
+#[derive(Copy, Clone)]
+pub struct sample_voice {
+    pub sample_no: usize,
+    pub pos: f32, // position in the sample
+    pub playing: bool,
+    pub looping: bool,
+}
+
+impl sample_voice {
+    pub fn new() -> Self {
+        Self {
+            sample_no: 0,
+            pos: 0.0,
+            playing: false,
+            looping: false,
+        }
+    }
+}
+
+pub struct samples {
+    pub samples: [Option<sample>; MAX_SAMPLES], // None if it was not loaded
+    pub voices: [sample_voice; MAX_VOICES],
+    pub nb_voices: usize,
+}
+
+impl samples {
+    pub fn new() -> Self {
+        Self {
+            samples: [None; MAX_SAMPLES],
+            voices: [sample_voice::new(); MAX_VOICES],
+            nb_voices: 0,
+        }
+    }
+}
+
+pub fn samples_init(s: &mut samples, nb_voices: usize) {
+    //println!("samples_init");
+
+    s.voices = [sample_voice::new(); MAX_VOICES];
+    s.nb_voices = nb_voices.min(MAX_VOICES);
+}
+
+// starts playing a sample on a voice, from its beginning
+pub fn samples_start(s: &mut samples, voice_no: usize, sample_no: usize, looping: bool) {
+    let voice = &mut s.voices[voice_no];
+    voice.sample_no = sample_no;
+    voice.pos = 0.0;
+    voice.playing = true;
+    voice.looping = looping;
+}
+
+pub fn samples_stop(s: &mut samples, voice_no: usize) {
+    s.voices[voice_no].playing = false;
+}
+
+// mixes the voices into "buffer", at "rate" samples per second
+pub fn samples_play(s: &mut samples, buffer: &mut [i16], rate: u32) {
+    //println!("samples_play");
+
+    for voice in s.voices[..s.nb_voices].iter_mut() {
+        if !voice.playing {
+            continue;
+        }
+        let smp = match s.samples[voice.sample_no] {
+            Some(smp) if !smp.data.is_empty() => smp,
+            _ => {
+                voice.playing = false;
+                continue;
+            }
+        };
+
+        let step: f32 = smp.rate as f32 / rate as f32;
+        for out in buffer.iter_mut() {
+            if voice.pos as usize >= smp.data.len() {
+                if !voice.looping {
+                    voice.playing = false;
+                    break;
+                }
+                voice.pos -= smp.data.len() as f32;
+            }
+            let mixed = *out as i32 + smp.data[voice.pos as usize] as i32 / 2;
+            *out = mixed.clamp(i16::MIN as i32, i16::MAX as i32) as i16;
+            voice.pos += step;
+        }
+    }
+}
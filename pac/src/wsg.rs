@@ -105,10 +105,11 @@ pub fn wsg_write(w: &mut wsg, address: u8, value: u8) {
     }
 }
 
-pub fn wsg_play(w: &mut wsg, buffer: &mut Vec<i16>, buffer_len: i32) {
+// fills "buffer" with samples at WSG_SAMPLE_RATE
+pub fn wsg_play(w: &mut wsg, buffer: &mut [i16]) {
     //println!("wsg_play");
 
-    for i in 0..buffer_len {
+    for i in 0..buffer.len() {
         let mut sample: i16 = 0;
 
         for voice_no in 0..3 {
@@ -128,6 +129,6 @@ pub fn wsg_play(w: &mut wsg, buffer: &mut Vec<i16>, buffer_len: i32) {
             let voice_sample: i16 = ((w.sound_rom[sample_pos as usize] as i16) - 8) * (v.volume as i16);
             sample += voice_sample;
         }
-        buffer[i as usize] = (sample as i32 * w.gain) as i16;
+        buffer[i] = (sample as i32 * w.gain) as i16;
     }
 }
@@ -0,0 +1,10 @@
+#![allow(non_camel_case_types)]
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+
+// the z80 core (the z80 crate), running with the userdata of the board
+use crate::*;
+
+pub use ::z80::*;
+
+pub type z80 = ::z80::z80<userdata>;
@@ -0,0 +1,50 @@
+#![allow(non_camel_case_types)]
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+
+// Checks the protection of Crush Roller against the answers of MAME's
+// maketrax handlers: by the address read, and by the instruction reading it.
+
+use ::pac::*;
+
+fn crush_machine() -> Box<pac::pac> {
+    let mut p = Box::new(pac::pac::new());
+    p.driver = &CRUSH;
+    pac_init(&mut p);
+    return p;
+}
+
+fn read(p: &mut pac::pac, addr: u16) -> u8 {
+    return (p.cpu.read_byte)(&mut p.cpu.userdata, addr);
+}
+
+#[test]
+fn protection_by_address() {
+    let mut p = crush_machine();
+    p.dsw1 = 0xb1;
+
+    // the dip switches, bits 6 and 7 driven by the protection
+    assert_eq!(read(&mut p, 0x5080), 0x31);
+    assert_eq!(read(&mut p, 0x5081), 0xf1);
+    assert_eq!(read(&mut p, 0x5085), 0xf1);
+    assert_eq!(read(&mut p, 0x50bf), 0x31);
+    // the second port
+    assert_eq!(read(&mut p, 0x50c0), 0x1f);
+    assert_eq!(read(&mut p, 0x50c9), 0x30);
+    assert_eq!(read(&mut p, 0x50cc), 0x00);
+    assert_eq!(read(&mut p, 0x50ff), 0x20);
+}
+
+#[test]
+fn protection_by_instruction() {
+    let mut p = crush_machine();
+    p.dsw1 = 0x31;
+
+    // the instruction is the last opcode fetched
+    (p.cpu.read_opcode)(&mut p.cpu, 0x1973);
+    assert_eq!(read(&mut p, 0x5080), 0x71);
+    (p.cpu.read_opcode)(&mut p.cpu, 0x040e);
+    assert_eq!(read(&mut p, 0x50c0), 0x20);
+    (p.cpu.read_opcode)(&mut p.cpu, 0x3ae2);
+    assert_eq!(read(&mut p, 0x50c0), 0x00);
+}
@@ -0,0 +1,83 @@
+#![allow(non_camel_case_types)]
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+
+// Checks the board side of the debugger: watchpoints on opcode fetches, on
+// a board that decrypts its opcodes (Pengo), and the frame timing when the
+// debugger stops the board in the middle of a slice.
+
+use ::pac::*;
+
+// ld a,(0x8000); jp 0
+const loop_program: [u8; 6] = [0x3a, 0x00, 0x80, 0xc3, 0x00, 0x00];
+
+// a Pengo board running "loop_program", its opcodes and data unencrypted
+fn pengo_machine() -> Box<pac::pac> {
+    let mut p = Box::new(pac::pac::new());
+    p.driver = &PENGO;
+    pac_init(&mut p);
+    p.rom[..loop_program.len()].copy_from_slice(&loop_program);
+    p.aux_rom[..loop_program.len()].copy_from_slice(&loop_program);
+    return p;
+}
+
+fn update(p: &mut pac::pac, ms: u32) {
+    let mut screen: Vec<u8> = vec![0; PAC_SCREEN_SIZE];
+    let mut audio: Vec<i16> = vec![0; PAC_WSG_FRAME_LEN];
+    let mut out = pac_output::new(&mut screen, &mut audio);
+    pac_update(p, ms, &mut out);
+}
+
+#[test]
+fn watchpoint_on_fetch() {
+    let mut p = pengo_machine();
+    pac_debug_enable(&mut p, true);
+    let w = pac_watchpoint {
+        start: 0x0003,
+        end: 0x0003,
+        read: true,
+        write: false,
+    };
+    assert!(pac_add_watchpoint(&mut p, w));
+
+    // "jp 0" is fetched from the decrypted opcodes, never read as data
+    update(&mut p, 10);
+    assert_eq!(
+        p.debug.stop,
+        Some(pac_stop::Watchpoint {
+            addr: 0x0003,
+            val: 0xc3,
+            write: false
+        })
+    );
+
+    // the driver's fetch is given back
+    pac_debug_enable(&mut p, false);
+    assert!(std::ptr::fn_addr_eq(p.cpu.read_opcode, PENGO.read_opcode));
+}
+
+#[test]
+fn stop_keeps_frame_timing() {
+    let mut p = pengo_machine();
+    pac_debug_enable(&mut p, true);
+
+    // the cycles the cpu ran, less the ones past the board's time, are the
+    // time of the board: whole frames, and frame_cyc
+    let in_step = |p: &pac::pac| {
+        let board_cyc = p.cpu.cyc - p.cpu.run_extra;
+        assert_eq!(board_cyc % PAC_CYCLES_PER_FRAME as u64, p.frame_cyc as u64);
+    };
+
+    for _ in 0..50 {
+        // ends past a slice, with cycles in run_extra...
+        update(&mut p, 7);
+        in_step(&p);
+
+        // ...then stops one instruction later
+        pac_debug_step(&mut p);
+        update(&mut p, 7);
+        assert_eq!(p.debug.stop, Some(pac_stop::Step));
+        in_step(&p);
+        pac_debug_resume(&mut p);
+    }
+}
@@ -0,0 +1,249 @@
+#![allow(non_camel_case_types)]
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+
+// Checks the decoding of the boards with scrambled roms against bytes worked
+// out by hand from MAME's tables: a byte is put in the rom where the bit
+// swaps of the board take it from, and found decoded where they put it.
+// - Ms. Pac-Man: the address and data lines of u5, u6 and u7, the patches
+//   copied over the Pac-Man code, and the trap addresses.
+// - Jr. Pac-Man: the runs of David Caldwell's xor table, and the color
+//   proms of 4 bits.
+// - Pengo: the opcode and data tables of the 315-5010, and its palette rom
+//   of 0x400 bytes.
+// - Eyes and Mr. TNT: the data lines swapped in the program roms, the data
+//   and address lines swapped in the graphics roms.
+
+use ::pac::*;
+
+// a board of "d", with the bytes loaded by "load" in its roms
+fn machine(d: &'static driver::driver, load: impl FnOnce(&mut pac::pac)) -> Box<pac::pac> {
+    let mut p = Box::new(pac::pac::new());
+    p.driver = d;
+    load(&mut p);
+    pac_init(&mut p);
+    return p;
+}
+
+fn read(p: &mut pac::pac, addr: u16) -> u8 {
+    return (p.cpu.read_byte)(&mut p.cpu.userdata, addr);
+}
+
+// MARK: ms. pac-man
+
+fn mspacman_machine() -> Box<pac::pac> {
+    return machine(&MSPACMAN, |p| {
+        // pacman.6e-6j
+        p.rom[0x0000] = 0x31;
+        p.rom[0x0410] = 0x11;
+        p.rom[0x0418] = 0x22;
+        p.rom[0x1234] = 0x5a;
+        p.rom[0x1900] = 0x66;
+        p.rom[0x2345] = 0x3c;
+        p.rom[0x3456] = 0xa5;
+        // u5: a10 is a8 of the cpu, d6 goes to d3
+        p.rom[0x8400] = 0x40;
+        // u5: a4 is a3, d0 goes to d7
+        p.rom[0x8010] = 0x01;
+        // u6, high half: a0 is a0, d1 goes to d0
+        p.rom[0x9801] = 0x02;
+        // u6, low half: a9 is a7, d7 goes to d4
+        p.rom[0x9200] = 0x80;
+        // u5 and u6, under the mirror of pacman.6e and 6f
+        p.rom[0x8000] = 0x44;
+        p.rom[0x8410] = 0x77;
+        p.rom[0x9234] = 0x99;
+        // u7: a10 is a3, d0 goes to d7
+        p.rom[0xb400] = 0x01;
+    });
+}
+
+#[test]
+fn mspacman_decode() {
+    let p = mspacman_machine();
+    let drom = &p.aux_rom;
+
+    assert_eq!(drom[0x8100], 0x08); // u5
+    assert_eq!(drom[0x8801], 0x01); // u6
+    assert_eq!(drom[0x9080], 0x10); // u6
+    assert_eq!(drom[0x3008], 0x80); // u7
+
+    // the Pac-Man roms, and their mirrors
+    assert_eq!(drom[0x1234], 0x5a);
+    assert_eq!(drom[0x9900], 0x66);
+    assert_eq!(drom[0xa345], 0x3c);
+    assert_eq!(drom[0xb456], 0xa5);
+}
+
+#[test]
+fn mspacman_patches() {
+    let p = mspacman_machine();
+    let drom = &p.aux_rom;
+
+    // 8 bytes from 0x8008 over 0x0410, from 0x8100 over 0x2b20
+    assert_eq!(drom[0x0410], 0x80);
+    assert_eq!(drom[0x0410..0x0418], drom[0x8008..0x8010]);
+    assert_eq!(drom[0x2b20], 0x08);
+    assert_eq!(drom[0x2b20..0x2b28], drom[0x8100..0x8108]);
+    // and not past them
+    assert_eq!(drom[0x0418], 0x22);
+    assert_eq!(p.rom[0x0410], 0x11);
+}
+
+#[test]
+fn mspacman_traps() {
+    let mut p = mspacman_machine();
+
+    // the game starts in the decoded bank
+    assert_eq!(read(&mut p, 0x0410), 0x80);
+
+    // reading 0x0038-0x003f switches to the Pac-Man roms...
+    read(&mut p, 0x003b);
+    assert_eq!(read(&mut p, 0x0410), 0x11);
+    assert_eq!(read(&mut p, 0x3456), 0xa5);
+    // which are mirrored over u5 and u6
+    assert_eq!(read(&mut p, 0x8410), 0x11);
+    assert_eq!(read(&mut p, 0x9234), 0x5a);
+    // the trap at 0x8000 switches them on from the decoded bank, and is
+    // read from pacman.6e
+    read(&mut p, 0x3ffc);
+    assert_eq!(read(&mut p, 0x8000), 0x31);
+    assert_eq!(read(&mut p, 0x0410), 0x11);
+
+    // ...and 0x3ff8-0x3fff back
+    read(&mut p, 0x3ffc);
+    assert_eq!(read(&mut p, 0x0410), 0x80);
+    assert_eq!(read(&mut p, 0x8100), 0x08);
+}
+
+// MARK: jr. pac-man
+
+#[test]
+fn jrpacman_decrypt() {
+    let p = machine(&JRPACMAN, |p| {
+        p.rom[0x00c2] = 0x12;
+        p.rom[0x9be5] = 0xff;
+    });
+
+    // (address, byte) at the ends of a few runs of the table
+    let runs: [(usize, u8); 14] = [
+        (0x00c0, 0x00),
+        (0x00c1, 0x80),
+        (0x00c3, 0x00),
+        (0x00c7, 0x80),
+        (0x00cc, 0x80),
+        (0x00cd, 0x00),
+        (0x9b0f, 0x04),
+        (0x9b1c, 0x04),
+        (0x9b1d, 0x00),
+        (0x9be3, 0x01),
+        (0x9be6, 0x05),
+        (0x9c29, 0x01),
+        (0x9ca0, 0x01),
+        (0xffff, 0x00),
+    ];
+    for (addr, val) in runs {
+        assert_eq!(p.rom[addr], val, "{:04x}", addr);
+    }
+
+    // the other bits are kept
+    assert_eq!(p.rom[0x00c2], 0x92);
+    assert_eq!(p.rom[0x9be5], 0xfa);
+
+    // 689 bytes are xored in all
+    assert_eq!(p.rom.iter().filter(|&&b| b != 0).count(), 689);
+}
+
+#[test]
+fn jrpacman_colors() {
+    let p = machine(&JRPACMAN, |p| {
+        p.color_rom[0x0005] = 0xf7;
+        p.color_rom[0x0105] = 0xfc;
+        p.palette_rom[0x0009] = 0xf3;
+    });
+    assert_eq!(p.color_rom[0x0005], 0xc7);
+    assert_eq!(p.palette_rom[0x0009], 0x03);
+}
+
+// MARK: pengo
+
+#[test]
+fn pengo_decrypt() {
+    // (address, encrypted byte, opcode, data): the row of the tables is given
+    // by a0, a4, a8 and a12, the column by d3 and d5, mirrored and xored
+    // with 0xa8 when d7 is set
+    let bytes: [(usize, u8, u8, u8); 8] = [
+        (0x0000, 0x00, 0xa0, 0x28),
+        (0x0002, 0x08, 0x88, 0xa0),
+        (0x0004, 0x80, 0x08, 0x08),
+        (0x0006, 0x57, 0xf7, 0x7f),
+        (0x0001, 0x00, 0x28, 0xa0),
+        (0x1000, 0x00, 0x28, 0x08),
+        (0x1111, 0x28, 0x80, 0x00),
+        (0x1113, 0xa0, 0x88, 0x28),
+    ];
+    let p = machine(&PENGO, |p| {
+        for (addr, src, _, _) in bytes {
+            p.rom[addr] = src;
+        }
+    });
+    for (addr, _, opcode, data) in bytes {
+        assert_eq!((p.aux_rom[addr], p.rom[addr]), (opcode, data), "{:04x}", addr);
+    }
+}
+
+#[test]
+fn pengo_palette() {
+    let mut p = machine(&PENGO, |p| {
+        p.palette_rom[0x1fc..0x200].copy_from_slice(&[0xf1, 0x02, 0x03, 0x04]);
+    });
+    let mut pal: [u8; 4] = [0; 4];
+
+    // the last palette of the upper color table and palette banks
+    get_palette(&mut p, 0x7f, &mut pal);
+    assert_eq!(pal, [0x01, 0x02, 0x03, 0x04]);
+
+    // the palette rom of Pac-Man is 0x100 bytes
+    let mut p = machine(&PACMAN, |p| {
+        p.palette_rom[0x0004..0x0008].copy_from_slice(&[0x05, 0x06, 0x07, 0x08]);
+    });
+    get_palette(&mut p, 0x41, &mut pal);
+    assert_eq!(pal, [0x05, 0x06, 0x07, 0x08]);
+}
+
+// MARK: eyes
+
+#[test]
+fn eyes_decrypt() {
+    let p = machine(&EYES, |p| {
+        // d3 and d5 of the program
+        p.rom[0x0000] = 0x08;
+        p.rom[0x3fff] = 0x20;
+        p.rom[0x1234] = 0xd7;
+        // d4 and d6, and a0 and a2, of the graphics
+        p.tile_rom[0x0004] = 0x10;
+        p.tile_rom[0x0ffb] = 0x40;
+        p.sprite_rom[0x0012] = 0x80;
+        p.sprite_rom[0x001c] = 0x11;
+    });
+
+    assert_eq!(p.rom[0x0000], 0x20);
+    assert_eq!(p.rom[0x3fff], 0x08);
+    assert_eq!(p.rom[0x1234], 0xd7);
+
+    assert_eq!((p.tile_rom[0x0001], p.tile_rom[0x0004]), (0x40, 0x00));
+    assert_eq!((p.tile_rom[0x0ffe], p.tile_rom[0x0ffb]), (0x10, 0x00));
+    assert_eq!(p.sprite_rom[0x0012], 0x80);
+    assert_eq!((p.sprite_rom[0x0019], p.sprite_rom[0x001c]), (0x41, 0x00));
+}
+
+#[test]
+fn mrtnt_decrypt() {
+    // the roms of Mr. TNT are wired as on Eyes
+    let p = machine(&MRTNT, |p| {
+        p.rom[0x2000] = 0x28;
+        p.sprite_rom[0x0004] = 0x50;
+    });
+    assert_eq!(p.rom[0x2000], 0x28);
+    assert_eq!((p.sprite_rom[0x0001], p.sprite_rom[0x0004]), (0x50, 0x00));
+}
@@ -0,0 +1,238 @@
+#![allow(non_camel_case_types)]
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+
+// Measures the speed of the emulation, headless, in effective MHz:
+// - the Pac-Man attract loop, on the board of the game (the pacman driver,
+//   video and sound included, the frames and samples being dropped). The
+//   rom set is looked for in the rom search path (PACMAN4RUST_ROMPATH, or
+//   "roms").
+// - the ZEXDOC cpu exerciser, for a budget of cycles. zexdoc.com is looked
+//   for in ZEX_DIR, or z80/tests/zex.
+// - a loop of common instructions (sums, copies, calls), on the z80 core
+//   alone, which needs no file.
+// A benchmark whose files are missing is skipped.
+//
+// usage: bench [pacman_seconds] [zex_cycles] [loop_cycles]
+// run it with "cargo run --release --bin bench".
+
+use pacman4rust::*;
+
+use std::ptr;
+use std::time::Instant;
+
+// the z80 core (the z80 crate), running with the userdata of the test
+// machine
+pub mod z80 {
+    pub use ::z80::*;
+
+    pub type z80 = ::z80::z80<super::userdata>;
+}
+
+use z80::{z80_asm, z80_init, z80_run, z80_stop_run};
+
+// emulated seconds of the attract loop, and cycles of zexdoc and of the
+// loop, by default
+const BENCH_PAC_SECONDS: u32 = 120;
+const BENCH_ZEX_CYCLES: u64 = 2_000_000_000;
+const BENCH_LOOP_CYCLES: u64 = 2_000_000_000;
+
+pub struct userdata {
+    pub machine_ptr: *mut machine,
+}
+impl userdata {
+    pub fn new() -> Self {
+        Self {
+            machine_ptr: ptr::null_mut(),
+        }
+    }
+}
+
+// 64 KB of ram, for the programs run on the core alone
+pub struct machine {
+    pub cpu: z80::z80,
+    pub memory: [u8; 0x10000],
+}
+
+impl machine {
+    pub fn new() -> Box<Self> {
+        let mut m = Box::new(Self {
+            cpu: z80::z80::new(userdata::new()),
+            memory: [0; 0x10000],
+        });
+        z80_init(&mut m.cpu);
+        let m_ptr: *mut machine = &mut *m;
+        m.cpu.userdata.machine_ptr = m_ptr;
+        m.cpu.read_byte = machine_rb;
+        m.cpu.write_byte = machine_wb;
+        return m;
+    }
+}
+
+fn machine_rb(userdata: &mut userdata, addr: u16) -> u8 {
+    unsafe {
+        return (*userdata.machine_ptr).memory[addr as usize];
+    }
+}
+
+fn machine_wb(userdata: &mut userdata, addr: u16, val: u8) {
+    unsafe {
+        (*userdata.machine_ptr).memory[addr as usize] = val;
+    }
+}
+
+// MARK: pac-man
+
+// runs the attract loop for "seconds" of emulated time. Returns the cycles
+// run, None if the rom set is missing.
+fn bench_pacman(seconds: u32) -> Option<u64> {
+    let mut p = Box::new(pac::pac::new());
+    p.driver = &PACMAN;
+    if let Err(e) = load_roms(&mut p, &default_rom_path()) {
+        println!("pacman: skipped, {}", e);
+        return None;
+    }
+    pac_init(&mut p);
+
+    // the audio past the buffer is dropped
+    let mut screen: Vec<u8> = vec![0; PAC_SCREEN_SIZE];
+    let mut audio: Vec<i16> = vec![0; PAC_WSG_FRAME_LEN];
+    for _ in 0..seconds {
+        let mut out = pac_output::new(&mut screen, &mut audio);
+        pac_update(&mut p, 1000, &mut out);
+    }
+    return Some(p.cpu.cyc);
+}
+
+// MARK: zexdoc
+
+fn zex_port_in(_z: &mut z80::z80, _port: u8) -> u8 {
+    return 0xff;
+}
+
+// the output of the exerciser is dropped; the warm boot stops the run
+fn zex_port_out(z: &mut z80::z80, port: u8, _val: u8) {
+    if port != 0 {
+        z80_stop_run(z);
+    }
+}
+
+// runs zexdoc for "cycles" at most. Returns the cycles run, None if the
+// file is missing.
+fn bench_zexdoc(cycles: u64) -> Option<u64> {
+    let dir = std::env::var("ZEX_DIR")
+        .unwrap_or(concat!(env!("CARGO_MANIFEST_DIR"), "/z80/tests/zex").to_string());
+    let program = match std::fs::read(format!("{}/zexdoc.com", dir)) {
+        Ok(data) => data,
+        Err(_) => {
+            println!("zexdoc: skipped, {}/zexdoc.com not found", dir);
+            return None;
+        }
+    };
+
+    let mut m = machine::new();
+    m.cpu.port_in = zex_port_in;
+    m.cpu.port_out = zex_port_out;
+
+    // warm boot at 0, BDOS entry at 5, as in z80/tests/zex.rs
+    let mem = &mut m.memory;
+    mem[0x0000] = 0xd3; // out (1),a
+    mem[0x0001] = 0x01;
+    mem[0x0005] = 0xc3; // jp 0xfe00
+    mem[0x0006] = 0x00;
+    mem[0x0007] = 0xfe;
+    mem[0xfe00] = 0xd3; // out (0),a
+    mem[0xfe01] = 0x00;
+    mem[0xfe02] = 0xc9; // ret
+    mem[0x0100..0x0100 + program.len()].copy_from_slice(&program);
+
+    m.cpu.pc = 0x0100;
+    m.cpu.sp = 0xfdfe;
+    z80_run(&mut m.cpu, cycles);
+    return Some(m.cpu.cyc);
+}
+
+// MARK: loop
+
+// sums 4 KB, copies 1 KB with ldir, and calls a routine swapping the
+// register banks, forever
+const loop_program: &str = "
+        org 0x0100
+        ld sp,0xfe00
+start:  ld hl,0x0000
+        ld bc,0x1000
+        xor a
+sum:    add a,(hl)
+        inc hl
+        ld d,a
+        dec bc
+        ld a,b
+        or c
+        ld a,d
+        jr nz,sum
+        ld (0x8000),a
+        ld hl,0x0000
+        ld de,0x9000
+        ld bc,0x0400
+        ldir
+        ld ix,0x9000
+        ld b,0
+bits:   call swap
+        rlc (ix+0)
+        inc ix
+        djnz bits
+        jp start
+swap:   push af
+        exx
+        ex af,af'
+        add hl,de
+        exx
+        ex af,af'
+        pop af
+        ret
+";
+
+// runs the loop for "cycles". Returns the cycles run.
+fn bench_loop(cycles: u64) -> Option<u64> {
+    let mut m = machine::new();
+    let blocks = z80_asm(loop_program).unwrap();
+    let org = blocks[0].org as usize;
+    m.memory[org..org + blocks[0].bytes.len()].copy_from_slice(&blocks[0].bytes);
+    m.cpu.pc = blocks[0].org;
+    z80_run(&mut m.cpu, cycles);
+    return Some(m.cpu.cyc);
+}
+
+// MARK: main
+
+fn report(name: &str, run: impl FnOnce() -> Option<u64>) {
+    let start = Instant::now();
+    if let Some(cycles) = run() {
+        let secs = start.elapsed().as_secs_f64();
+        println!(
+            "{}: {} cycles in {:.2} s, {:.1} MHz",
+            name,
+            cycles,
+            secs,
+            cycles as f64 / secs / 1e6
+        );
+    }
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let arg = |i: usize, default: u64| match args.get(i) {
+        Some(s) => s.parse::<u64>().unwrap_or_else(|_| {
+            eprintln!("usage: bench [pacman_seconds] [zex_cycles] [loop_cycles]");
+            std::process::exit(1);
+        }),
+        None => default,
+    };
+    let pac_seconds = arg(1, BENCH_PAC_SECONDS as u64) as u32;
+    let zex_cycles = arg(2, BENCH_ZEX_CYCLES);
+    let loop_cycles = arg(3, BENCH_LOOP_CYCLES);
+
+    report("pacman", || bench_pacman(pac_seconds));
+    report("zexdoc", || bench_zexdoc(zex_cycles));
+    report("loop", || bench_loop(loop_cycles));
+}
@@ -0,0 +1,573 @@
+#![allow(non_camel_case_types)]
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+
+// Runs CP/M .COM programs (cpu exercisers, utilities...) on the z80 core.
+// The program is loaded at 0x100 and its BDOS calls are served by the host:
+// console through stdin/stdout, files from the current directory.
+//
+// usage: cpm <program.com> [args...]
+
+use std::collections::HashMap;
+use std::fs;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::ptr;
+
+// the z80 core (the z80 crate), running with the userdata of the cp/m machine
+pub mod z80 {
+    pub use ::z80::*;
+
+    pub type z80 = ::z80::z80<super::userdata>;
+}
+
+pub use z80::*;
+
+// the BDOS entry point jumps here: "out (0),a" hands the call over to the
+// host, then "ret" returns to the program
+const BDOS_ADDR: u16 = 0xfe00;
+const TPA_ADDR: u16 = 0x0100;
+const DEFAULT_DMA: u16 = 0x0080;
+const FCB1_ADDR: u16 = 0x005c;
+const FCB2_ADDR: u16 = 0x006c;
+
+const RECORD_LEN: usize = 128;
+
+// the program runs by slices of this many cycles, until it quits
+const CPM_RUN_CYCLES: u64 = 1_000_000;
+
+pub struct userdata {
+    pub cpm_ptr: *mut cpm,
+}
+impl userdata {
+    pub fn new() -> Self {
+        Self {
+            cpm_ptr: ptr::null_mut(),
+        }
+    }
+}
+
+pub struct cpm {
+    pub cpu: z80::z80,
+    pub memory: [u8; 0x10000],
+    pub dma: u16,
+    pub files: HashMap<u16, File>, // open files, by address of their fcb
+    pub search: Vec<String>,       // files left to return by "search next"
+    pub quit: bool,
+}
+
+impl cpm {
+    pub fn new() -> Self {
+        Self {
+            cpu: z80::z80::new(userdata::new()),
+            memory: [0; 0x10000],
+            dma: DEFAULT_DMA,
+            files: HashMap::new(),
+            search: Vec::new(),
+            quit: false,
+        }
+    }
+}
+
+pub fn cpm_rb(userdata: &mut userdata, addr: u16) -> u8 {
+    unsafe {
+        return (*userdata.cpm_ptr).memory[addr as usize];
+    }
+}
+
+pub fn cpm_wb(userdata: &mut userdata, addr: u16, val: u8) {
+    unsafe {
+        (*userdata.cpm_ptr).memory[addr as usize] = val;
+    }
+}
+
+pub fn cpm_port_in(_z: &mut z80::z80, _port: u8) -> u8 {
+    return 0xff;
+}
+
+pub fn cpm_port_out(z: &mut z80::z80, port: u8, _val: u8) {
+    match port {
+        0 => bdos(z),
+        _ => unsafe {
+            // warm boot: the program is over
+            (*z.userdata.cpm_ptr).quit = true;
+            z80_stop_run(z);
+        },
+    }
+}
+
+// MARK: file control blocks
+
+// name of the file of a fcb, as "NAME.TYP"
+fn fcb_name(z: &mut z80::z80, fcb: u16) -> String {
+    let mut name = String::new();
+    for i in 1..9 {
+        name.push((rb(z, fcb.wrapping_add(i)) & 0x7f) as char);
+    }
+    let mut typ = String::new();
+    for i in 9..12 {
+        typ.push((rb(z, fcb.wrapping_add(i)) & 0x7f) as char);
+    }
+
+    let name = name.trim_end().to_string();
+    let typ = typ.trim_end();
+    if typ.is_empty() {
+        return name;
+    }
+    return format!("{}.{}", name, typ);
+}
+
+// fills the name of a fcb from a file name given on the command line.
+// "*" fills the rest of the name (or type) with "?".
+fn fcb_set_name(memory: &mut [u8], fcb: usize, arg: &str) {
+    let arg = arg.to_ascii_uppercase();
+    let arg = match arg.find(':') {
+        Some(i) => &arg[i + 1..],
+        None => &arg[..],
+    };
+    let (name, typ) = match arg.find('.') {
+        Some(i) => (&arg[..i], &arg[i + 1..]),
+        None => (arg, ""),
+    };
+
+    memory[fcb] = 0;
+    for (part, start, len) in [(name, 1, 8), (typ, 9, 3)] {
+        let mut chars = part.bytes();
+        let mut fill: u8 = b' ';
+        for i in 0..len {
+            let c = if fill == b'?' { None } else { chars.next() };
+            memory[fcb + start + i] = match c {
+                Some(b'*') => {
+                    fill = b'?';
+                    b'?'
+                }
+                Some(c) => c,
+                None => fill,
+            };
+        }
+    }
+    // extent and record counts. The rest of the fcb at 0x6c overlaps the
+    // command tail at 0x80.
+    for i in 12..16 {
+        memory[fcb + i] = 0;
+    }
+}
+
+// name of a host file in the 8.3 form of a fcb, padded with spaces
+fn dir_entry_name(file_name: &str) -> Option<[u8; 11]> {
+    let upper = file_name.to_ascii_uppercase();
+    let (name, typ) = match upper.rfind('.') {
+        Some(i) => (&upper[..i], &upper[i + 1..]),
+        None => (&upper[..], ""),
+    };
+    if name.is_empty() || name.len() > 8 || typ.len() > 3 || name.contains('.') {
+        return None;
+    }
+
+    let mut entry = [b' '; 11];
+    entry[..name.len()].copy_from_slice(name.as_bytes());
+    entry[8..8 + typ.len()].copy_from_slice(typ.as_bytes());
+    return Some(entry);
+}
+
+// host file matching a CP/M file name, whatever its case
+fn host_file(name: &str) -> Option<String> {
+    let entries = fs::read_dir(".").ok()?;
+    for entry in entries.flatten() {
+        let host_name = entry.file_name().to_string_lossy().to_string();
+        if host_name.eq_ignore_ascii_case(name) {
+            return Some(host_name);
+        }
+    }
+    return None;
+}
+
+// record of a fcb for the sequential accesses: extent and current record
+fn seq_record(z: &mut z80::z80, fcb: u16) -> u32 {
+    return (rb(z, fcb.wrapping_add(12)) & 0x1f) as u32 * 128 + rb(z, fcb.wrapping_add(32)) as u32;
+}
+
+fn set_seq_record(z: &mut z80::z80, fcb: u16, record: u32) {
+    wb(z, fcb.wrapping_add(12), (record / 128) as u8 & 0x1f);
+    wb(z, fcb.wrapping_add(32), (record % 128) as u8);
+}
+
+// record of a fcb for the random accesses
+fn random_record(z: &mut z80::z80, fcb: u16) -> u32 {
+    return rb(z, fcb.wrapping_add(33)) as u32 | (rb(z, fcb.wrapping_add(34)) as u32) << 8 | (rb(z, fcb.wrapping_add(35)) as u32) << 16;
+}
+
+fn set_random_record(z: &mut z80::z80, fcb: u16, record: u32) {
+    wb(z, fcb.wrapping_add(33), record as u8);
+    wb(z, fcb.wrapping_add(34), (record >> 8) as u8);
+    wb(z, fcb.wrapping_add(35), (record >> 16) as u8);
+}
+
+// reads a record of the file of a fcb into the dma buffer. Returns 0, or 1
+// past the end of the file.
+fn read_record(z: &mut z80::z80, fcb: u16, record: u32) -> u8 {
+    let mut buffer = [0x1a_u8; RECORD_LEN]; // ^Z pads the last record
+    let len = unsafe {
+        let c = &mut *z.userdata.cpm_ptr;
+        let file = match c.files.get_mut(&fcb) {
+            Some(file) => file,
+            None => return 9, // invalid fcb
+        };
+        if file.seek(SeekFrom::Start(record as u64 * RECORD_LEN as u64)).is_err() {
+            return 1;
+        }
+        let mut len = 0;
+        while len < RECORD_LEN {
+            match file.read(&mut buffer[len..]) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => len += n,
+            }
+        }
+        len
+    };
+    if len == 0 {
+        return 1;
+    }
+
+    let dma = unsafe { (*z.userdata.cpm_ptr).dma };
+    for (i, byte) in buffer.iter().enumerate() {
+        wb(z, dma.wrapping_add(i as u16), *byte);
+    }
+    return 0;
+}
+
+// writes the dma buffer to a record of the file of a fcb
+fn write_record(z: &mut z80::z80, fcb: u16, record: u32) -> u8 {
+    let dma = unsafe { (*z.userdata.cpm_ptr).dma };
+    let mut buffer = [0_u8; RECORD_LEN];
+    for i in 0..RECORD_LEN {
+        buffer[i] = rb(z, dma.wrapping_add(i as u16));
+    }
+
+    unsafe {
+        let c = &mut *z.userdata.cpm_ptr;
+        let file = match c.files.get_mut(&fcb) {
+            Some(file) => file,
+            None => return 9,
+        };
+        if file.seek(SeekFrom::Start(record as u64 * RECORD_LEN as u64)).is_err()
+            || file.write_all(&buffer).is_err()
+        {
+            return 2; // disk full
+        }
+    }
+    return 0;
+}
+
+// writes the next matching file of a search as a directory entry in the dma
+// buffer
+fn search_next(z: &mut z80::z80) -> u8 {
+    let name = unsafe {
+        match (*z.userdata.cpm_ptr).search.pop() {
+            Some(name) => name,
+            None => return 0xff,
+        }
+    };
+    let entry = dir_entry_name(&name).unwrap();
+    let dma = unsafe { (*z.userdata.cpm_ptr).dma };
+    wb(z, dma, 0); // user 0
+    for i in 0..11 {
+        wb(z, dma.wrapping_add(1 + i as u16), entry[i]);
+    }
+    for i in 12..32 {
+        wb(z, dma.wrapping_add(i), 0);
+    }
+    return 0;
+}
+
+// MARK: bdos
+
+// reads a character from the console, 0x1a (^Z) at the end of the input
+fn console_in() -> u8 {
+    let _ = std::io::stdout().flush();
+    let mut byte = [0_u8; 1];
+    return match std::io::stdin().read(&mut byte) {
+        Ok(1) => {
+            if byte[0] == b'\n' {
+                b'\r'
+            } else {
+                byte[0]
+            }
+        }
+        _ => 0x1a,
+    };
+}
+
+fn console_out(c: u8) {
+    let mut out = std::io::stdout();
+    let _ = out.write_all(&[c & 0x7f]);
+}
+
+// serves the BDOS function in C, with its parameter in DE or E. The result
+// is returned in A and L, with B and H as its high byte.
+pub fn bdos(z: &mut z80::z80) {
+    let de = get_de(z);
+    let mut result: u16 = 0;
+
+    match z.c {
+        0 => unsafe {
+            // system reset
+            (*z.userdata.cpm_ptr).quit = true;
+            z80_stop_run(z);
+        },
+        1 => {
+            // console input, echoed
+            let c = console_in();
+            console_out(c);
+            result = c as u16;
+        }
+        2 => console_out(z.e),
+        6 => {
+            // direct console io: no input is ever waiting
+            if z.e < 0xfe {
+                console_out(z.e);
+            }
+        }
+        9 => {
+            // print string, up to '$'
+            let mut addr = de;
+            loop {
+                let c = rb(z, addr);
+                if c == b'$' {
+                    break;
+                }
+                console_out(c);
+                addr = addr.wrapping_add(1);
+            }
+        }
+        10 => {
+            // read console buffer: max length at DE, length read at DE+1
+            let max = rb(z, de) as u16;
+            let mut len: u16 = 0;
+            loop {
+                let c = console_in();
+                if c == b'\r' || c == 0x1a {
+                    break;
+                }
+                if len < max {
+                    wb(z, de.wrapping_add(2 + len), c);
+                    len += 1;
+                }
+            }
+            wb(z, de.wrapping_add(1), len as u8);
+        }
+        11 => {} // console status: no key pressed
+        12 => result = 0x0022, // version: CP/M 2.2
+        13 => unsafe {
+            // reset disk system
+            (*z.userdata.cpm_ptr).dma = DEFAULT_DMA;
+        },
+        14 => {} // select disk: there is only A:
+        15 => {
+            // open file
+            result = 0xff;
+            if let Some(host_name) = host_file(&fcb_name(z, de)) {
+                let file = fs::OpenOptions::new()
+                    .read(true)
+                    .write(true)
+                    .open(&host_name)
+                    .or_else(|_| File::open(&host_name));
+                if let Ok(file) = file {
+                    unsafe {
+                        (*z.userdata.cpm_ptr).files.insert(de, file);
+                    }
+                    wb(z, de.wrapping_add(12), 0);
+                    wb(z, de.wrapping_add(32), 0);
+                    result = 0;
+                }
+            }
+        }
+        16 => unsafe {
+            // close file
+            (*z.userdata.cpm_ptr).files.remove(&de);
+        },
+        17 => {
+            // search for first: lists the matching files
+            let mut pattern = [0_u8; 11];
+            for i in 0..11 {
+                pattern[i] = rb(z, de.wrapping_add(1 + i as u16)) & 0x7f;
+            }
+            let mut found: Vec<String> = Vec::new();
+            if let Ok(entries) = fs::read_dir(".") {
+                for entry in entries.flatten() {
+                    let name = entry.file_name().to_string_lossy().to_string();
+                    if let Some(entry_name) = dir_entry_name(&name) {
+                        let matches = (0..11).all(|i| pattern[i] == b'?' || pattern[i] == entry_name[i]);
+                        if matches && entry.path().is_file() {
+                            found.push(name);
+                        }
+                    }
+                }
+            }
+            found.sort();
+            found.reverse();
+            unsafe {
+                (*z.userdata.cpm_ptr).search = found;
+            }
+            result = search_next(z) as u16;
+        }
+        18 => result = search_next(z) as u16, // search for next
+        19 => {
+            // delete file
+            result = 0xff;
+            if let Some(host_name) = host_file(&fcb_name(z, de)) {
+                if fs::remove_file(host_name).is_ok() {
+                    result = 0;
+                }
+            }
+        }
+        20 => {
+            // read sequential
+            let record = seq_record(z, de);
+            result = read_record(z, de, record) as u16;
+            if result == 0 {
+                set_seq_record(z, de, record + 1);
+            }
+        }
+        21 => {
+            // write sequential
+            let record = seq_record(z, de);
+            result = write_record(z, de, record) as u16;
+            if result == 0 {
+                set_seq_record(z, de, record + 1);
+            }
+        }
+        22 => {
+            // make file
+            result = 0xff;
+            let name = fcb_name(z, de);
+            let host_name = host_file(&name).unwrap_or(name.to_ascii_lowercase());
+            let file = fs::OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(host_name);
+            if let Ok(file) = file {
+                unsafe {
+                    (*z.userdata.cpm_ptr).files.insert(de, file);
+                }
+                wb(z, de.wrapping_add(12), 0);
+                wb(z, de.wrapping_add(32), 0);
+                result = 0;
+            }
+        }
+        23 => {
+            // rename file: new name in the second half of the fcb
+            result = 0xff;
+            let new_name = fcb_name(z, de.wrapping_add(16)).to_ascii_lowercase();
+            if let Some(host_name) = host_file(&fcb_name(z, de)) {
+                if fs::rename(host_name, new_name).is_ok() {
+                    result = 0;
+                }
+            }
+        }
+        25 => {} // current disk: A:
+        26 => unsafe {
+            // set dma address
+            (*z.userdata.cpm_ptr).dma = de;
+        },
+        33 => {
+            // read random
+            let record = random_record(z, de);
+            result = read_record(z, de, record) as u16;
+            set_seq_record(z, de, record);
+        }
+        34 => {
+            // write random
+            let record = random_record(z, de);
+            result = write_record(z, de, record) as u16;
+            set_seq_record(z, de, record);
+        }
+        35 => {
+            // compute file size, in records
+            result = 0xff;
+            if let Some(host_name) = host_file(&fcb_name(z, de)) {
+                if let Ok(meta) = fs::metadata(host_name) {
+                    let records = (meta.len() as usize).div_ceil(RECORD_LEN);
+                    set_random_record(z, de, records as u32);
+                    result = 0;
+                }
+            }
+        }
+        36 => {
+            // set random record
+            let record = seq_record(z, de);
+            set_random_record(z, de, record);
+        }
+        _ => {
+            eprintln!("WARN: unsupported BDOS function {}", z.c);
+            result = 0xff;
+        }
+    }
+
+    z.a = result as u8;
+    z.l = result as u8;
+    z.b = (result >> 8) as u8;
+    z.h = (result >> 8) as u8;
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 2 {
+        eprintln!("usage: cpm <program.com> [args...]");
+        std::process::exit(1);
+    }
+
+    let program = match fs::read(&args[1]) {
+        Ok(data) => data,
+        Err(e) => {
+            eprintln!("ERR: cannot read {}: {}", args[1], e);
+            std::process::exit(1);
+        }
+    };
+    if program.len() > (BDOS_ADDR - TPA_ADDR) as usize {
+        eprintln!("ERR: {} is too large ({} bytes)", args[1], program.len());
+        std::process::exit(1);
+    }
+
+    let mut c = cpm::new();
+    z80_init(&mut c.cpu);
+    c.cpu.userdata.cpm_ptr = &mut c;
+    c.cpu.read_byte = cpm_rb;
+    c.cpu.write_byte = cpm_wb;
+    c.cpu.port_in = cpm_port_in;
+    c.cpu.port_out = cpm_port_out;
+
+    // page zero: warm boot at 0, BDOS entry at 5
+    let m = &mut c.memory;
+    m[0x0000] = 0xd3; // out (1),a
+    m[0x0001] = 0x01;
+    m[0x0005] = 0xc3; // jp BDOS_ADDR
+    m[0x0006] = BDOS_ADDR as u8;
+    m[0x0007] = (BDOS_ADDR >> 8) as u8;
+    m[BDOS_ADDR as usize] = 0xd3; // out (0),a
+    m[BDOS_ADDR as usize + 1] = 0x00;
+    m[BDOS_ADDR as usize + 2] = 0xc9; // ret
+
+    // command line: the tail at 0x80, and the first two arguments parsed
+    // as file names
+    let tail: String = args[2..].iter().map(|arg| format!(" {}", arg.to_ascii_uppercase())).collect();
+    let tail = &tail.as_bytes()[..tail.len().min(127)];
+    m[0x80] = tail.len() as u8;
+    m[0x81..0x81 + tail.len()].copy_from_slice(tail);
+    fcb_set_name(m, FCB1_ADDR as usize, args.get(2).map(|s| s.as_str()).unwrap_or(""));
+    fcb_set_name(m, FCB2_ADDR as usize, args.get(3).map(|s| s.as_str()).unwrap_or(""));
+
+    let tpa = TPA_ADDR as usize;
+    m[tpa..tpa + program.len()].copy_from_slice(&program);
+
+    // returning from the program goes to the warm boot
+    c.cpu.pc = TPA_ADDR;
+    c.cpu.sp = BDOS_ADDR - 2;
+
+    while !c.quit {
+        z80_run(&mut c.cpu, CPM_RUN_CYCLES);
+    }
+    let _ = std::io::stdout().flush();
+}
@@ -0,0 +1,428 @@
+#![allow(non_camel_case_types)]
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+
+// The debugger prompt, on stdin/stdout, shown when the board is stopped
+// (see pac's debug.rs): by --debug at startup, the D key, or a breakpoint
+// or watchpoint. The emulation waits while the prompt is open. Addresses
+// are shown by their symbol, if a symbol file is loaded (see symbols.rs).
+
+use crate::*;
+use std::io::{self, BufRead, Write};
+
+const help: &str = "\
+s, step                        run one instruction
+n, next                        run one instruction, over calls, rst, djnz and block repeats
+c, continue                    run until a breakpoint or a watchpoint
+g, goto <addr>                 run up to addr
+b, break <addr>                set a breakpoint on pc
+bd, delete <addr>              delete a breakpoint
+w, watch <start>[:<end>] [r|w] stop on the reads and/or writes of a memory range (both by default)
+wd, unwatch <n>                delete watchpoint n
+l, list                        list the breakpoints and watchpoints
+r, regs [<reg> <val>]          show the registers, or set one (a, bc, pc, hl', ...)
+m, mem [<addr>] [<len>]        dump memory
+d, dis [<addr>] [<count>]      disassemble, from pc by default
+t, trace                       print each instruction run (on/off)
+x, exit                        leave the debugger, the game runs on
+q, quit                        quit
+an empty line repeats step and next, and carries on mem and dis.
+addresses and values are in hex, registers (pc, hl...) or symbols.";
+
+// the instructions that "next" runs over, to the one following them
+const step_over: [&str; 11] = [
+    "call", "rst", "djnz", "ldir", "lddr", "cpir", "cpdr", "inir", "indr", "otir", "otdr",
+];
+
+pub struct debugger {
+    pub last_command: String,
+    pub mem_next: u16, // where mem and dis carry on
+    pub dis_next: u16,
+}
+impl debugger {
+    pub fn new() -> Self {
+        Self {
+            last_command: String::new(),
+            mem_next: 0,
+            dis_next: 0,
+        }
+    }
+}
+
+// MARK: registers
+
+fn flags_text(f: u8) -> String {
+    let names = ['S', 'Z', 'Y', 'H', 'X', 'P', 'N', 'C'];
+    return (0..8)
+        .map(|i| if f & (0x80 >> i) != 0 { names[i] } else { '-' })
+        .collect();
+}
+
+pub fn print_registers(p: &mut pac::pac) {
+    let z = &mut p.cpu;
+    let f = get_f(z);
+    let (bc, de, hl) = (get_bc(z), get_de(z), get_hl(z));
+    println!(
+        "PC: {:04X}, SP: {:04X}, AF: {:04X}, BC: {:04X}, DE: {:04X}, HL: {:04X}, IX: {:04X}, IY: {:04X}",
+        z.pc,
+        z.sp,
+        (z.a as u16) << 8 | f as u16,
+        bc,
+        de,
+        hl,
+        z.ix,
+        z.iy
+    );
+    println!(
+        "AF': {:04X}, BC': {:04X}, DE': {:04X}, HL': {:04X}, I: {:02X}, R: {:02X}, IM: {}, IFF1: {}, flags: {}, cyc: {}",
+        (z.a_ as u16) << 8 | z.f_ as u16,
+        (z.b_ as u16) << 8 | z.c_ as u16,
+        (z.d_ as u16) << 8 | z.e_ as u16,
+        (z.h_ as u16) << 8 | z.l_ as u16,
+        z.i,
+        z.r,
+        z.interrupt_mode,
+        z.iff1 as u8,
+        flags_text(f),
+        z.cyc
+    );
+}
+
+// a register by its name, 8 or 16 bits
+pub fn get_register(p: &mut pac::pac, name: &str) -> Option<u16> {
+    let z = &mut p.cpu;
+    let pair = |hi: u8, lo: u8| Some((hi as u16) << 8 | lo as u16);
+    return match name {
+        "a" => Some(z.a as u16),
+        "f" => Some(get_f(z) as u16),
+        "b" => Some(z.b as u16),
+        "c" => Some(z.c as u16),
+        "d" => Some(z.d as u16),
+        "e" => Some(z.e as u16),
+        "h" => Some(z.h as u16),
+        "l" => Some(z.l as u16),
+        "i" => Some(z.i as u16),
+        "r" => Some(z.r as u16),
+        "af" => pair(z.a, get_f(z)),
+        "bc" => pair(z.b, z.c),
+        "de" => pair(z.d, z.e),
+        "hl" => pair(z.h, z.l),
+        "af'" => pair(z.a_, z.f_),
+        "bc'" => pair(z.b_, z.c_),
+        "de'" => pair(z.d_, z.e_),
+        "hl'" => pair(z.h_, z.l_),
+        "ix" => Some(z.ix),
+        "iy" => Some(z.iy),
+        "sp" => Some(z.sp),
+        "pc" => Some(z.pc),
+        _ => None,
+    };
+}
+
+// sets a register by its name. Returns false if there is no such register.
+pub fn set_register(p: &mut pac::pac, name: &str, val: u16) -> bool {
+    let z = &mut p.cpu;
+    let (hi, lo) = ((val >> 8) as u8, val as u8);
+    match name {
+        "a" => z.a = lo,
+        "f" => set_f(z, lo),
+        "b" => z.b = lo,
+        "c" => z.c = lo,
+        "d" => z.d = lo,
+        "e" => z.e = lo,
+        "h" => z.h = lo,
+        "l" => z.l = lo,
+        "i" => z.i = lo,
+        "r" => z.r = lo,
+        "af" => {
+            z.a = hi;
+            set_f(z, lo);
+        }
+        "bc" => set_bc(z, val),
+        "de" => set_de(z, val),
+        "hl" => set_hl(z, val),
+        "af'" => (z.a_, z.f_) = (hi, lo),
+        "bc'" => (z.b_, z.c_) = (hi, lo),
+        "de'" => (z.d_, z.e_) = (hi, lo),
+        "hl'" => (z.h_, z.l_) = (hi, lo),
+        "ix" => z.ix = val,
+        "iy" => z.iy = val,
+        "sp" => z.sp = val,
+        "pc" => z.pc = val,
+        _ => return false,
+    }
+    return true;
+}
+
+// a value in hex ("4e00", "0x4e00", "$4e00"), the value of a register, or
+// the address of a symbol
+pub fn parse_value(p: &mut pac::pac, s: &str) -> Option<u16> {
+    if let Some(addr) = pac_symbol_addr(p.debug.symbols, s) {
+        return Some(addr);
+    }
+    let s = s.to_ascii_lowercase();
+    if let Some(val) = get_register(p, &s) {
+        // "a" to "f" are registers before being numbers
+        return Some(val);
+    }
+    let digits = s.trim_start_matches("0x").trim_start_matches('$');
+    return u16::from_str_radix(digits, 16).ok();
+}
+
+// MARK: memory
+
+// the line of a symbol starting at "addr", before its code or data
+pub fn print_label(p: &pac::pac, addr: u16) {
+    for s in p.debug.symbols.iter().filter(|s| s.start == addr) {
+        if s.comment.is_empty() {
+            println!("{}:", s.name);
+        } else {
+            println!("{}:  ; {}", s.name, s.comment);
+        }
+    }
+}
+
+// prints "count" instructions from "addr". Returns the address following.
+pub fn print_instructions(p: &mut pac::pac, addr: u16, count: usize) -> u16 {
+    let mut addr = addr;
+    for _ in 0..count {
+        print_label(p, addr);
+        let (text, len) = disasm_at(p, addr);
+        let bytes = disasm_bytes(p, addr, len);
+        let mark = if addr == p.cpu.pc {
+            '>'
+        } else if p.debug.breakpoints.contains(&Some(addr)) {
+            '*'
+        } else {
+            ' '
+        };
+        println!("{}{:04x}  {:<12} {}", mark, addr, bytes, text);
+        addr = addr.wrapping_add(len);
+    }
+    return addr;
+}
+
+// dumps "len" bytes from "addr", 16 by line. Returns the address following.
+pub fn print_memory(p: &mut pac::pac, addr: u16, len: u32) -> u16 {
+    let mut addr = addr;
+    let mut left = len;
+    while left > 0 {
+        let count = left.min(16) as u16;
+        // the variables starting on the line
+        for s in p.debug.symbols.iter().filter(|s| s.start >= addr && s.start - addr < count) {
+            let range = if s.end > s.start { format!(":{:04x}", s.end) } else { String::new() };
+            println!("; {:04x}{} {}  {}", s.start, range, s.name, s.comment);
+        }
+        let bytes: Vec<u8> = (0..count).map(|i| pac_debug_read(p, addr.wrapping_add(i))).collect();
+        let hex: Vec<String> = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+        let ascii: String = bytes
+            .iter()
+            .map(|b| if b.is_ascii_graphic() || *b == b' ' { *b as char } else { '.' })
+            .collect();
+        println!("{:04x}  {:<48} {}", addr, hex.join(" "), ascii);
+        addr = addr.wrapping_add(count);
+        left -= count as u32;
+    }
+    return addr;
+}
+
+// MARK: prompt
+
+// the trace of an instruction, before it runs: as z80_debug_output, with
+// the symbols
+pub fn print_trace(p: &mut pac::pac) {
+    let z = &mut p.cpu;
+    let f = get_f(z);
+    let (pc, bc, de, hl) = (z.pc, get_bc(z), get_de(z), get_hl(z));
+    let line = format!(
+        "PC: {:04X}, AF: {:04X}, BC: {:04X}, DE: {:04X}, HL: {:04X}, SP: {:04X}, IX: {:04X}, IY: {:04X}, cyc: {}",
+        pc,
+        (z.a as u16) << 8 | f as u16,
+        bc,
+        de,
+        hl,
+        z.sp,
+        z.ix,
+        z.iy,
+        z.cyc
+    );
+    let (text, _) = disasm_at(p, pc);
+    match pac_find_symbol(p.debug.symbols, pc).filter(|s| s.start == pc) {
+        Some(s) => println!("{}\t{}: {}", line, s.name, text),
+        None => println!("{}\t{}", line, text),
+    }
+}
+
+pub fn print_stop(p: &mut pac::pac) {
+    match p.debug.stop {
+        Some(pac_stop::Breakpoint(addr)) => {
+            println!("breakpoint at {}", addr_text(p.debug.symbols, addr))
+        }
+        Some(pac_stop::Watchpoint { addr, val, write }) => {
+            let access = if write { "write" } else { "read" };
+            println!("watchpoint: {} of {:02x} at {}", access, val, addr_text(p.debug.symbols, addr));
+        }
+        _ => {}
+    }
+    print_registers(p);
+    print_instructions(p, p.cpu.pc, 1);
+}
+
+fn print_points(p: &mut pac::pac) {
+    for addr in p.debug.breakpoints.iter().flatten() {
+        println!("breakpoint {}", addr_text(p.debug.symbols, *addr));
+    }
+    for (i, slot) in p.debug.watchpoints.iter().enumerate() {
+        if let Some(w) = slot {
+            let access = match (w.read, w.write) {
+                (true, true) => "rw",
+                (true, false) => "r",
+                _ => "w",
+            };
+            let range = format!("{:04x}:{:04x}", w.start, w.end);
+            match symbol_text(p.debug.symbols, w.start) {
+                Some(name) => println!("watchpoint {}: {} ({}) {}", i, range, name, access),
+                None => println!("watchpoint {}: {} {}", i, range, access),
+            }
+        }
+    }
+}
+
+// runs the commands typed at the prompt, until one of them lets the board
+// run again. Returns false to quit.
+pub fn debugger_prompt(dbg: &mut debugger, p: &mut pac::pac) -> bool {
+    //println!("debugger_prompt");
+
+    print_stop(p);
+    dbg.dis_next = p.cpu.pc;
+
+    let stdin = io::stdin();
+    loop {
+        print!("(debug) ");
+        io::stdout().flush().unwrap();
+
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).unwrap_or(0) == 0 {
+            // no more input: the game runs on without the debugger
+            pac_debug_enable(p, false);
+            return true;
+        }
+        let mut line = line.trim().to_string();
+        if line.is_empty() {
+            line = dbg.last_command.clone();
+        }
+
+        let args: Vec<&str> = line.split_whitespace().collect();
+        let command = args.first().copied().unwrap_or("");
+        let arg = |i: usize| args.get(i).copied();
+        // the command an empty line repeats
+        dbg.last_command = String::new();
+
+        match command {
+            "" => {}
+            "s" | "step" => {
+                dbg.last_command = line.clone();
+                pac_debug_step(p);
+                return true;
+            }
+            "n" | "next" => {
+                dbg.last_command = line.clone();
+                let (text, len) = disasm_at(p, p.cpu.pc);
+                let mnemonic = text.split(' ').next().unwrap_or("");
+                if step_over.contains(&mnemonic) {
+                    pac_debug_run_to(p, p.cpu.pc.wrapping_add(len));
+                } else {
+                    pac_debug_step(p);
+                }
+                return true;
+            }
+            "c" | "continue" => {
+                pac_debug_resume(p);
+                return true;
+            }
+            "g" | "goto" => match arg(1).and_then(|s| parse_value(p, s)) {
+                Some(addr) => {
+                    pac_debug_run_to(p, addr);
+                    return true;
+                }
+                None => println!("usage: goto <addr>"),
+            },
+            "b" | "break" => match arg(1).and_then(|s| parse_value(p, s)) {
+                Some(addr) if pac_add_breakpoint(p, addr) => {
+                    println!("breakpoint {}", addr_text(p.debug.symbols, addr))
+                }
+                Some(_) => println!("no more than {} breakpoints", PAC_MAX_BREAKPOINTS),
+                None => println!("usage: break <addr>"),
+            },
+            "bd" | "delete" => match arg(1).and_then(|s| parse_value(p, s)) {
+                Some(addr) if pac_remove_breakpoint(p, addr) => {}
+                Some(addr) => println!("no breakpoint at {:04x}", addr),
+                None => println!("usage: delete <addr>"),
+            },
+            "w" | "watch" => {
+                let range = arg(1).and_then(|s| match s.split_once(':') {
+                    Some((start, end)) => Some((parse_value(p, start)?, parse_value(p, end)?)),
+                    None => parse_value(p, s).map(|addr| (addr, addr)),
+                });
+                let access = match arg(2) {
+                    None | Some("rw") => Some((true, true)),
+                    Some("r") => Some((true, false)),
+                    Some("w") => Some((false, true)),
+                    _ => None,
+                };
+                match (range, access) {
+                    (Some((start, end)), Some((read, write))) if start <= end => {
+                        let w = pac_watchpoint { start, end, read, write };
+                        if !pac_add_watchpoint(p, w) {
+                            println!("no more than {} watchpoints", PAC_MAX_WATCHPOINTS);
+                        }
+                    }
+                    _ => println!("usage: watch <start>[:<end>] [r|w|rw]"),
+                }
+            }
+            "wd" | "unwatch" => match arg(1).and_then(|s| s.parse::<usize>().ok()) {
+                Some(i) if i < PAC_MAX_WATCHPOINTS && p.debug.watchpoints[i].is_some() => {
+                    p.debug.watchpoints[i] = None;
+                }
+                _ => println!("usage: unwatch <n> (see list)"),
+            },
+            "l" | "list" => print_points(p),
+            "r" | "regs" => match (arg(1), arg(2)) {
+                (None, _) => print_registers(p),
+                (Some(name), Some(val)) => match parse_value(p, val) {
+                    Some(val) if set_register(p, &name.to_ascii_lowercase(), val) => {
+                        print_registers(p)
+                    }
+                    _ => println!("usage: regs <reg> <val>"),
+                },
+                _ => println!("usage: regs [<reg> <val>]"),
+            },
+            "m" | "mem" => {
+                let addr = arg(1).and_then(|s| parse_value(p, s)).unwrap_or(dbg.mem_next);
+                let len = arg(2).and_then(|s| u32::from_str_radix(s, 16).ok()).unwrap_or(0x80);
+                dbg.mem_next = print_memory(p, addr, len);
+                dbg.last_command = String::from("m");
+            }
+            "d" | "dis" => {
+                let addr = arg(1).and_then(|s| parse_value(p, s)).unwrap_or(dbg.dis_next);
+                let count = arg(2).and_then(|s| s.parse::<usize>().ok()).unwrap_or(10);
+                dbg.dis_next = print_instructions(p, addr, count);
+                dbg.last_command = String::from("d");
+            }
+            "t" | "trace" => {
+                p.debug.trace = match p.debug.trace {
+                    Some(_) => None,
+                    None => Some(print_trace),
+                };
+                println!("trace {}", if p.debug.trace.is_some() { "on" } else { "off" });
+            }
+            "x" | "exit" => {
+                pac_debug_enable(p, false);
+                return true;
+            }
+            "q" | "quit" => return false,
+            "h" | "help" | "?" => println!("{}", help),
+            _ => println!("unknown command {} (see help)", command),
+        }
+    }
+}
@@ -0,0 +1,40 @@
+#![allow(non_camel_case_types)]
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+
+// The disassembly shown by the debugger and --disasm: the instructions of
+// the board as its cpu fetches them, through the driver (the opcodes
+// decrypted on Pengo, the bank of Ms. Pac-Man switched in), with the
+// operands named by their symbol (see symbols.rs).
+
+use crate::*;
+
+// disassembles the instruction at "addr", as the cpu fetches it
+pub fn disasm_at(p: &mut pac::pac, addr: u16) -> (String, u16) {
+    let symbols = p.debug.symbols;
+    let d = p.driver;
+    return z80_disasm_symbols(
+        &mut |a| {
+            if a == addr {
+                pac_debug_read_opcode(p, a)
+            } else {
+                pac_debug_read(p, a)
+            }
+        },
+        addr,
+        &|a, kind| operand_text(d, symbols, a, kind),
+    );
+}
+
+// the "len" bytes of the instruction at "addr" in hex, fetched as by
+// disasm_at: the opcode, then the operands as data
+pub fn disasm_bytes(p: &mut pac::pac, addr: u16, len: u16) -> String {
+    let bytes: Vec<String> = (0..len)
+        .map(|i| {
+            let a = addr.wrapping_add(i);
+            let val = if i == 0 { pac_debug_read_opcode(p, a) } else { pac_debug_read(p, a) };
+            format!("{:02x}", val)
+        })
+        .collect();
+    return bytes.join(" ");
+}
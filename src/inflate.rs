@@ -0,0 +1,278 @@
+#![allow(non_camel_case_types)]
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+
+// decoder for the DEFLATE format (RFC 1951), as used by zip archives. It is
+// based on the canonical huffman decoding used by zlib's "puff".
+
+const MAX_BITS: usize = 15;
+
+// base lengths and extra bits for length codes 257..285
+const length_base: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const length_extra: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+
+// base offsets and extra bits for distance codes 0..29
+const dist_base: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const dist_extra: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+// order in which the code length code lengths are stored
+const clen_order: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+struct bit_reader<'a> {
+    input: &'a [u8],
+    pos: usize,
+    bit_buf: u32,
+    bit_cnt: u32,
+}
+
+impl<'a> bit_reader<'a> {
+    fn new(input: &'a [u8]) -> Self {
+        Self {
+            input,
+            pos: 0,
+            bit_buf: 0,
+            bit_cnt: 0,
+        }
+    }
+
+    // reads "n" bits (n <= 16), least significant bit first
+    fn bits(&mut self, n: u32) -> Result<u32, String> {
+        while self.bit_cnt < n {
+            if self.pos >= self.input.len() {
+                return Err("unexpected end of compressed data".to_string());
+            }
+            self.bit_buf |= (self.input[self.pos] as u32) << self.bit_cnt;
+            self.pos += 1;
+            self.bit_cnt += 8;
+        }
+        let val = self.bit_buf & ((1u32 << n) - 1);
+        self.bit_buf >>= n;
+        self.bit_cnt -= n;
+        return Ok(val);
+    }
+
+    // drops the remaining bits of the current byte
+    fn align(&mut self) {
+        self.bit_buf = 0;
+        self.bit_cnt = 0;
+    }
+}
+
+// canonical huffman code: number of codes of each length, and the symbols
+// ordered by code
+struct huffman {
+    count: [u16; MAX_BITS + 1],
+    symbol: Vec<u16>,
+}
+
+impl huffman {
+    fn new(lengths: &[u8]) -> Result<Self, String> {
+        let mut count = [0u16; MAX_BITS + 1];
+        for &len in lengths {
+            count[len as usize] += 1;
+        }
+
+        // an over-subscribed set of lengths is invalid (an incomplete one is
+        // allowed, e.g. a single distance code)
+        let mut left: i32 = 1;
+        for len in 1..=MAX_BITS {
+            left <<= 1;
+            left -= count[len] as i32;
+            if left < 0 {
+                return Err("invalid huffman code lengths".to_string());
+            }
+        }
+
+        let mut offs = [0u16; MAX_BITS + 1];
+        for len in 1..MAX_BITS {
+            offs[len + 1] = offs[len] + count[len];
+        }
+        let mut symbol = vec![0u16; lengths.len()];
+        for (sym, &len) in lengths.iter().enumerate() {
+            if len != 0 {
+                symbol[offs[len as usize] as usize] = sym as u16;
+                offs[len as usize] += 1;
+            }
+        }
+
+        return Ok(Self { count, symbol });
+    }
+
+    fn decode(&self, br: &mut bit_reader) -> Result<u16, String> {
+        let mut code: i32 = 0; // bits being decoded
+        let mut first: i32 = 0; // first code of the current length
+        let mut index: i32 = 0; // index of the first code of that length
+        for len in 1..=MAX_BITS {
+            code |= br.bits(1)? as i32;
+            let count = self.count[len] as i32;
+            if code - count < first {
+                return Ok(self.symbol[(index + (code - first)) as usize]);
+            }
+            index += count;
+            first += count;
+            first <<= 1;
+            code <<= 1;
+        }
+        return Err("invalid huffman code".to_string());
+    }
+}
+
+fn inflate_stored(br: &mut bit_reader, out: &mut Vec<u8>) -> Result<(), String> {
+    br.align();
+    if br.pos + 4 > br.input.len() {
+        return Err("unexpected end of compressed data".to_string());
+    }
+    let len = u16::from_le_bytes([br.input[br.pos], br.input[br.pos + 1]]);
+    let nlen = u16::from_le_bytes([br.input[br.pos + 2], br.input[br.pos + 3]]);
+    if len != !nlen {
+        return Err("invalid stored block length".to_string());
+    }
+    br.pos += 4;
+    let end = br.pos + len as usize;
+    if end > br.input.len() {
+        return Err("unexpected end of compressed data".to_string());
+    }
+    out.extend_from_slice(&br.input[br.pos..end]);
+    br.pos = end;
+    return Ok(());
+}
+
+fn inflate_codes(
+    br: &mut bit_reader,
+    out: &mut Vec<u8>,
+    lencode: &huffman,
+    distcode: &huffman,
+) -> Result<(), String> {
+    loop {
+        let sym = lencode.decode(br)?;
+        if sym < 256 {
+            out.push(sym as u8);
+        } else if sym == 256 {
+            return Ok(());
+        } else {
+            let sym = (sym - 257) as usize;
+            if sym >= 29 {
+                return Err("invalid length code".to_string());
+            }
+            let len = length_base[sym] as usize + br.bits(length_extra[sym] as u32)? as usize;
+
+            let dsym = distcode.decode(br)? as usize;
+            if dsym >= 30 {
+                return Err("invalid distance code".to_string());
+            }
+            let dist = dist_base[dsym] as usize + br.bits(dist_extra[dsym] as u32)? as usize;
+            if dist > out.len() {
+                return Err("distance too far back".to_string());
+            }
+
+            // copied byte by byte, as the source may overlap the output
+            let start = out.len() - dist;
+            for i in 0..len {
+                let byte = out[start + i];
+                out.push(byte);
+            }
+        }
+    }
+}
+
+fn inflate_fixed(br: &mut bit_reader, out: &mut Vec<u8>) -> Result<(), String> {
+    let mut lengths = [0u8; 288];
+    for (sym, len) in lengths.iter_mut().enumerate() {
+        *len = match sym {
+            0..=143 => 8,
+            144..=255 => 9,
+            256..=279 => 7,
+            _ => 8,
+        };
+    }
+    let lencode = huffman::new(&lengths)?;
+    let distcode = huffman::new(&[5u8; 30])?;
+    return inflate_codes(br, out, &lencode, &distcode);
+}
+
+fn inflate_dynamic(br: &mut bit_reader, out: &mut Vec<u8>) -> Result<(), String> {
+    let nlen = br.bits(5)? as usize + 257;
+    let ndist = br.bits(5)? as usize + 1;
+    let ncode = br.bits(4)? as usize + 4;
+    if nlen > 286 || ndist > 30 {
+        return Err("bad dynamic block counts".to_string());
+    }
+
+    let mut lengths = [0u8; 320];
+    for &index in clen_order.iter().take(ncode) {
+        lengths[index] = br.bits(3)? as u8;
+    }
+    let clencode = huffman::new(&lengths[..19])?;
+
+    // literal/length and distance code lengths are stored as one sequence
+    let mut index = 0;
+    while index < nlen + ndist {
+        let sym = clencode.decode(br)?;
+        if sym < 16 {
+            lengths[index] = sym as u8;
+            index += 1;
+            continue;
+        }
+        let (len, repeat) = match sym {
+            16 => {
+                if index == 0 {
+                    return Err("repeat with no previous length".to_string());
+                }
+                (lengths[index - 1], 3 + br.bits(2)? as usize)
+            }
+            17 => (0, 3 + br.bits(3)? as usize),
+            _ => (0, 11 + br.bits(7)? as usize),
+        };
+        if index + repeat > nlen + ndist {
+            return Err("too many code lengths".to_string());
+        }
+        for _ in 0..repeat {
+            lengths[index] = len;
+            index += 1;
+        }
+    }
+    if lengths[256] == 0 {
+        return Err("missing end-of-block code".to_string());
+    }
+
+    let lencode = huffman::new(&lengths[..nlen])?;
+    let distcode = huffman::new(&lengths[nlen..nlen + ndist])?;
+    return inflate_codes(br, out, &lencode, &distcode);
+}
+
+// decompresses a raw deflate stream. "size_hint" is the expected size of the
+// uncompressed data, used to preallocate the output.
+pub fn inflate(input: &[u8], size_hint: usize) -> Result<Vec<u8>, String> {
+    //println!("inflate");
+
+    let mut out: Vec<u8> = Vec::with_capacity(size_hint);
+    let mut br = bit_reader::new(input);
+
+    loop {
+        let last = br.bits(1)?;
+        match br.bits(2)? {
+            0 => inflate_stored(&mut br, &mut out)?,
+            1 => inflate_fixed(&mut br, &mut out)?,
+            2 => inflate_dynamic(&mut br, &mut out)?,
+            _ => return Err("invalid block type".to_string()),
+        }
+        if last == 1 {
+            break;
+        }
+    }
+
+    return Ok(out);
+}
@@ -0,0 +1,26 @@
+#![allow(non_camel_case_types)]
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+
+// The files of the frontend: rom sets (loose files or zip archives, checked
+// against their hashes), sound samples, symbol files and the disassembly.
+// Shared by the game, the bench binary and the tests.
+
+pub mod disasm;
+pub mod hash;
+pub mod inflate;
+pub mod romset;
+pub mod symbols;
+pub mod wav;
+pub mod zip;
+
+// the board (the pac crate), whose rom regions are loaded here
+pub use ::pac::*;
+
+pub use disasm::*;
+pub use hash::*;
+pub use inflate::*;
+pub use romset::*;
+pub use symbols::*;
+pub use wav::*;
+pub use zip::*;
@@ -14,30 +14,15 @@ use sdl2::video::*;
 use sdl2::Sdl;
 use sdl2::TimerSubsystem;
 
-use std::fs::File;
-use std::io::Read;
-use std::ptr;
+pub mod debugger;
 
-pub mod pac;
-pub mod wsg;
-pub mod z80;
+// the board (the pac crate) and its files (src/lib.rs); the frontend loads
+// them and outputs its frames
+pub use pacman4rust::*;
 
-pub use pac::*;
-pub use wsg::*;
-pub use z80::*;
+pub use debugger::*;
 
-pub struct userdata<'a> {
-    pub game_ptr: *mut game<'a>,
-}
-impl<'a> userdata<'a> {
-    pub fn new() -> Self {
-        Self {
-            game_ptr: ptr::null_mut(),
-        }
-    }
-}
-
-pub struct game<'a> {
+pub struct game {
     pub should_quit: bool,
     pub has_focus: bool,
     pub is_paused: bool,
@@ -47,13 +32,16 @@ pub struct game<'a> {
     pub renderer: Canvas<Window>,
     pub audio: AudioSubsystem,
     pub audio_device: AudioQueue<i16>,
-    pub p: pac::pac<'a>,
+    pub p: pac::pac,
+    pub screen_buffer: Vec<u8>,
+    pub audio_buffer: Vec<i16>,
+    pub debugger: debugger::debugger,
     pub current_time: u32,
     pub last_time: u32,
     pub dt: u32,
 }
 
-impl<'a> game<'a> {
+impl game {
     pub fn new() -> Self {
         // SDL init
         let sdl_context = sdl2::init().unwrap();
@@ -103,6 +91,9 @@ impl<'a> game<'a> {
             audio,
             audio_device,
             p: pac::pac::new(),
+            screen_buffer: vec![0; PAC_SCREEN_SIZE],
+            audio_buffer: Vec::new(),
+            debugger: debugger::debugger::new(),
             current_time: 0,
             last_time: 0,
             dt: 0,
@@ -124,7 +115,7 @@ pub fn update_screen(g: &mut game) {
         .map_err(|e| e.to_string())
         .unwrap();
 
-    let pixels = &mut g.p.screen_buffer;
+    let pixels = &mut g.screen_buffer;
     let pitch = 3 * PAC_SCREEN_WIDTH;
 
     texture.update(None, pixels, pitch as usize).unwrap();
@@ -133,12 +124,6 @@ pub fn update_screen(g: &mut game) {
     g.renderer.present();
 }
 
-pub fn push_sample(g: &mut game, sample: i16) {
-    //println!("push_sample");
-
-    g.audio_device.queue_audio(&[sample]).unwrap();
-}
-
 pub fn send_quit_event(g: &mut game) {
     //println!("send_quit_event");
 
@@ -149,6 +134,51 @@ pub fn screenshot(_g: &mut game) {
     //println!("screenshot");
 }
 
+// input of the board a key is bound to
+pub fn key_input(scancode: Scancode) -> Option<input> {
+    return match scancode {
+        Scancode::Return | Scancode::Num1 => Some(input::P1Start), // start (1p)
+        Scancode::Num2 => Some(input::P2Start),                    // start (2p)
+        Scancode::Up => Some(input::P1Up),
+        Scancode::Down => Some(input::P1Down),
+        Scancode::Left => Some(input::P1Left),
+        Scancode::Right => Some(input::P1Right),
+        Scancode::LCtrl | Scancode::Space => Some(input::P1Button),
+        Scancode::C | Scancode::Num5 => Some(input::Coin1), // coin
+        Scancode::V => Some(input::Coin2),                  // coin (slot 2)
+        Scancode::Num9 => Some(input::Service),
+        Scancode::T => Some(input::Test), // board test
+        Scancode::R => Some(input::RackAdvance),
+        _ => None,
+    };
+}
+
+// parses a range of addresses "start:end", in hex
+pub fn parse_range(s: &str) -> Option<(u16, u16)> {
+    let (start, end) = s.split_once(':')?;
+    let hex = |s: &str| u16::from_str_radix(s.trim_start_matches("0x"), 16).ok();
+    let (start, end) = (hex(start)?, hex(end)?);
+    if start > end {
+        return None;
+    }
+    return Some((start, end));
+}
+
+// prints the disassembly of the program rom from "start" to "end", as the
+// cpu fetches it (see disasm.rs)
+pub fn print_disasm(p: &mut pac::pac, start: u16, end: u16) {
+    //println!("print_disasm");
+
+    let mut addr: u32 = start as u32;
+    while addr <= end as u32 {
+        print_label(p, addr as u16);
+        let (text, len) = disasm_at(p, addr as u16);
+        let bytes = disasm_bytes(p, addr as u16, len);
+        println!("{:04x}  {:<12} {}", addr, bytes, text);
+        addr += len as u32;
+    }
+}
+
 pub fn mainloop(g: &mut game) {
     //println!("mainloop");
 
@@ -173,34 +203,10 @@ pub fn mainloop(g: &mut game) {
                 scancode: Some(scancode),
                 ..
             } => {
+                if let Some(inp) = key_input(scancode) {
+                    pac_set_input(&mut g.p, inp, true);
+                }
                 match scancode {
-                    Scancode::Return | Scancode::Num1 => {
-                        g.p.p1_start = 1; // start (1p)
-                    }
-                    Scancode::Num2 => {
-                        g.p.p2_start = 1; // start (2p)
-                    }
-                    Scancode::Up => {
-                        g.p.p1_up = 1; // up
-                    }
-                    Scancode::Down => {
-                        g.p.p1_down = 1; // down
-                    }
-                    Scancode::Left => {
-                        g.p.p1_left = 1; // left
-                    }
-                    Scancode::Right => {
-                        g.p.p1_right = 1; // right
-                    }
-                    Scancode::C | Scancode::Num5 => {
-                        g.p.coin_s1 = 1; // coin
-                    }
-                    Scancode::V => {
-                        g.p.coin_s2 = 1; // coin (slot 2)
-                    }
-                    Scancode::T => {
-                        g.p.board_test = 1; // board test
-                    }
                     Scancode::M => {
                         g.p.mute_audio = !g.p.mute_audio;
                     }
@@ -211,11 +217,18 @@ pub fn mainloop(g: &mut game) {
                         screenshot(g);
                     }
                     Scancode::I => {
-                        pac_cheat_invincibility(g);
+                        if let Some(cheat) = g.p.driver.invincibility {
+                            cheat(&mut g.p);
+                            println!("applied invincibility patch");
+                        }
                     }
                     Scancode::Tab => {
                         g.speed = 5;
                     }
+                    Scancode::D => {
+                        pac_debug_enable(&mut g.p, true);
+                        pac_debug_break(&mut g.p);
+                    }
                     _ => {}
                 }
             }
@@ -223,34 +236,10 @@ pub fn mainloop(g: &mut game) {
                 scancode: Some(scancode),
                 ..
             } => {
+                if let Some(inp) = key_input(scancode) {
+                    pac_set_input(&mut g.p, inp, false);
+                }
                 match scancode {
-                    Scancode::Return | Scancode::Num1 => {
-                        g.p.p1_start = 0; // start (1p)
-                    }
-                    Scancode::Num2 => {
-                        g.p.p2_start = 0; // start (2p)
-                    }
-                    Scancode::Up => {
-                        g.p.p1_up = 0; // up
-                    }
-                    Scancode::Down => {
-                        g.p.p1_down = 0; // down
-                    }
-                    Scancode::Left => {
-                        g.p.p1_left = 0; // left
-                    }
-                    Scancode::Right => {
-                        g.p.p1_right = 0; // right
-                    }
-                    Scancode::C | Scancode::Num5 => {
-                        g.p.coin_s1 = 0; // coin
-                    }
-                    Scancode::V => {
-                        g.p.coin_s2 = 0; // coin (slot 2)
-                    }
-                    Scancode::T => {
-                        g.p.board_test = 0; // board test
-                    }
                     Scancode::Tab => {
                         g.speed = 1;
                         // clear the queued audio to avoid audio delays
@@ -264,13 +253,147 @@ pub fn mainloop(g: &mut game) {
     }
 
     if !g.is_paused && g.has_focus {
-        pac_update(g, g.dt * g.speed as u32);
+        let ms = g.dt * g.speed as u32;
+        // room for the samples of every frame run, plus a partial one
+        let audio_len = ms as usize * g.p.sample_rate as usize / 1000 + PAC_WSG_FRAME_LEN;
+        if g.audio_buffer.len() < audio_len {
+            g.audio_buffer.resize(audio_len, 0);
+        }
+
+        let mut out = pac_output::new(&mut g.screen_buffer, &mut g.audio_buffer);
+        pac_update(&mut g.p, ms, &mut out);
+        let (frames, audio_len) = (out.frames, out.audio_len);
+
+        if audio_len > 0 {
+            g.audio_device.queue_audio(&g.audio_buffer[..audio_len]).unwrap();
+        }
+        if frames > 0 {
+            update_screen(g);
+        }
+    }
+
+    // stopped in the debugger: the time spent at the prompt is not emulated
+    if g.p.debug.stop.is_some() {
+        if !debugger_prompt(&mut g.debugger, &mut g.p) {
+            g.should_quit = true;
+        }
+        g.current_time = g.timer.ticks();
+        g.audio_device.clear();
     }
 
     g.last_time = g.current_time;
 }
 
 fn main() {
+    let args: Vec<String> = std::env::args().collect();
+
+    // [game]: short name of the game to run (pacman by default)
+    // --list: lists the supported games
+    // --rompath "dir1;dir2": directories searched for the rom files
+    // --verify-roms [dir]: audits the rom files without starting the game
+    // --disasm [start:end]: disassembles the program rom (in hex, every
+    // program rom file by default) without starting the game
+    // --debug: starts in the debugger
+    // --symbols file: names of the routines and variables, shown by the
+    // debugger and --disasm
+    let mut rom_path = default_rom_path();
+    let mut verify_roms_only = false;
+    let mut disasm_only = false;
+    let mut disasm_range: Option<(u16, u16)> = None;
+    let mut debug = false;
+    let mut symbols_path: Option<String> = None;
+    let mut game_name = PACMAN.name.to_string();
+    let mut i = 1;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--list" => {
+                for d in DRIVERS.iter() {
+                    println!("{:<10} {}", d.name, d.description);
+                }
+                return;
+            }
+            "--rompath" if i + 1 < args.len() => {
+                rom_path = parse_rom_path(&args[i + 1]);
+                i += 1;
+            }
+            "--verify-roms" => {
+                verify_roms_only = true;
+                if i + 1 < args.len() && !args[i + 1].starts_with("--") {
+                    rom_path = vec![args[i + 1].clone()];
+                    i += 1;
+                }
+            }
+            "--disasm" => {
+                disasm_only = true;
+                if i + 1 < args.len() && !args[i + 1].starts_with("--") {
+                    disasm_range = parse_range(&args[i + 1]);
+                    if disasm_range.is_none() {
+                        eprintln!("ERR: bad range {} (expected start:end, in hex)", args[i + 1]);
+                        std::process::exit(1);
+                    }
+                    i += 1;
+                }
+            }
+            "--debug" => {
+                debug = true;
+            }
+            "--symbols" if i + 1 < args.len() => {
+                symbols_path = Some(args[i + 1].clone());
+                i += 1;
+            }
+            arg if !arg.starts_with("--") => {
+                game_name = arg.to_string();
+            }
+            arg => {
+                eprintln!("ERR: unknown argument {}", arg);
+                eprintln!(
+                    "usage: pacman4rust [game] [--list] [--rompath \"dir1;dir2\"] [--verify-roms [dir]] [--disasm [start:end]] [--debug] [--symbols file]"
+                );
+                std::process::exit(1);
+            }
+        }
+        i += 1;
+    }
+
+    let driver = match find_driver(&game_name) {
+        Some(d) => d,
+        None => {
+            eprintln!("ERR: unknown game {} (see --list)", game_name);
+            std::process::exit(1);
+        }
+    };
+
+    if verify_roms_only {
+        let ok = verify_roms(driver, &rom_path);
+        std::process::exit(if ok { 0 } else { 1 });
+    }
+
+    if disasm_only {
+        let mut p = Box::new(pac::pac::new());
+        p.driver = driver;
+        if let Err(e) = load_roms(&mut p, &rom_path) {
+            eprintln!("ERR: {}", e);
+            std::process::exit(1);
+        }
+        pac_init(&mut p);
+        if let Some(path) = &symbols_path {
+            if let Err(e) = symbols_load(&mut p, path) {
+                eprintln!("ERR: {}", e);
+                std::process::exit(1);
+            }
+        }
+        match disasm_range {
+            Some((start, end)) => print_disasm(&mut p, start, end),
+            None => {
+                for info in driver.roms.iter().filter(|info| info.region == rom_region::Cpu) {
+                    println!("; {}", info.name);
+                    print_disasm(&mut p, info.offset as u16, (info.offset + info.size - 1) as u16);
+                }
+            }
+        }
+        return;
+    }
+
     let mut g = game::new();
 
     // print info on renderer:
@@ -285,11 +408,43 @@ fn main() {
     g.audio_device.resume(); // start playing
 
     // pac init
-    pac_init(&mut g);
+    let _ = g.renderer.window_mut().set_title(driver.description);
+    g.p.driver = driver;
+    if let Err(e) = load_roms(&mut g.p, &rom_path) {
+        eprintln!("ERR: {}", e);
+        let _ = sdl2::messagebox::show_simple_message_box(
+            sdl2::messagebox::MessageBoxFlag::ERROR,
+            "pacman4rust",
+            &e.to_string(),
+            g.renderer.window(),
+        );
+        std::process::exit(1);
+    }
+    if !driver.samples.is_empty() {
+        let sources = open_sources(&samples_path(&rom_path), &[driver.name]);
+        let loaded = samples_load(&mut g.p.samples, &sources, driver.samples);
+        if loaded < driver.samples.len() {
+            println!(
+                "WARN: {} of {} samples found for {}, the missing sounds are silent",
+                loaded,
+                driver.samples.len(),
+                driver.name
+            );
+        }
+    }
+    pac_init(&mut g.p);
+    if let Some(path) = &symbols_path {
+        match symbols_load(&mut g.p, path) {
+            Ok(count) => println!("INFO: {} symbols loaded from {}", count, path),
+            Err(e) => println!("WARN: cannot load the symbols: {}", e),
+        }
+    }
+    if debug {
+        pac_debug_enable(&mut g.p, true);
+        pac_debug_break(&mut g.p);
+    }
 
     g.p.sample_rate = 44100;
-    g.p.push_sample = push_sample;
-    g.p.update_screen = update_screen;
     update_screen(&mut g);
 
     // main loop
@@ -300,5 +455,5 @@ fn main() {
         mainloop(&mut g);
     }
 
-    pac_quit(&mut g);
+    pac_quit(&mut g.p);
 }
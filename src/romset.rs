@@ -0,0 +1,485 @@
+#![allow(non_camel_case_types)]
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+
+use crate::*;
+
+use std::fmt;
+use std::fs;
+
+pub enum rom_status {
+    Good,
+    BadDump { crc32: u32 },
+    WrongSize { size: usize },
+    Unreadable(String),
+    Missing,
+    // a file that is not part of the set. If its content matches a known
+    // file, that file's name is given.
+    Unknown { matches: Option<&'static str> },
+}
+
+// returns the entry of the rom set with the given file name
+pub fn find_rom(roms: &'static [rom_info], name: &str) -> Option<&'static rom_info> {
+    //println!("find_rom");
+
+    return roms.iter().find(|info| info.name == name);
+}
+
+// checks the content of a file against its entry in the rom set
+pub fn check_rom(info: &rom_info, data: &[u8]) -> rom_status {
+    //println!("check_rom");
+
+    if data.len() != info.size {
+        return rom_status::WrongSize { size: data.len() };
+    }
+    let crc = hash::crc32(data);
+    if crc != info.crc32 || (!info.sha256.is_empty() && hash::sha256_hex(data) != info.sha256) {
+        return rom_status::BadDump { crc32: crc };
+    }
+    return rom_status::Good;
+}
+
+// MARK: rom search path
+
+// default directory where the rom files are expected
+pub const PAC_ROM_DIR: &str = "roms";
+
+// environment variable holding the rom search path
+pub const ROM_PATH_ENV: &str = "PACMAN4RUST_ROMPATH";
+
+// splits a search path of the form "dir1;dir2"
+pub fn parse_rom_path(s: &str) -> Vec<String> {
+    //println!("parse_rom_path");
+
+    return s
+        .split(';')
+        .filter(|dir| !dir.is_empty())
+        .map(|dir| dir.to_string())
+        .collect();
+}
+
+// the search path from the environment, or the default rom directory
+pub fn default_rom_path() -> Vec<String> {
+    //println!("default_rom_path");
+
+    return match std::env::var(ROM_PATH_ENV) {
+        Ok(s) if !parse_rom_path(&s).is_empty() => parse_rom_path(&s),
+        _ => vec![PAC_ROM_DIR.to_string()],
+    };
+}
+
+// a place where rom files are looked for: a directory of loose files, or the
+// zip archive of the set found in such a directory
+pub enum rom_source {
+    Dir(String),
+    Zip(zip_archive),
+}
+
+// set names searched for the files of a driver: its own, then its parent's
+pub fn driver_set_names(d: &driver::driver) -> Vec<&'static str> {
+    //println!("driver_set_names");
+
+    let mut names = vec![d.name];
+    if let Some(parent) = d.parent {
+        names.push(parent);
+    }
+    return names;
+}
+
+// opens every directory of the search path and the "<set_name>.zip"
+// archives in it, in that order. A clone set lists its parent after its own
+// name, as MAME's split archives only hold the files that differ.
+pub fn open_sources(rom_path: &[String], set_names: &[&str]) -> Vec<rom_source> {
+    //println!("open_sources");
+
+    let mut sources: Vec<rom_source> = Vec::new();
+    for dir in rom_path {
+        sources.push(rom_source::Dir(dir.clone()));
+
+        for set_name in set_names {
+            let zip_path = format!("{}/{}.zip", dir, set_name);
+            if fs::metadata(&zip_path).is_err() {
+                continue;
+            }
+            match zip_open(&zip_path) {
+                Ok(zip) => sources.push(rom_source::Zip(zip)),
+                Err(e) => println!("WARN: cannot read {}: {}", zip_path, e),
+            }
+        }
+    }
+    return sources;
+}
+
+// reads the file "name" of a directory. As in zip archives, the case of
+// the name does not matter. Returns its path and content, None if missing.
+fn dir_read(dir: &str, name: &str) -> Option<(String, Result<Vec<u8>, String>)> {
+    let path = format!("{}/{}", dir, name);
+    match fs::read(&path) {
+        Ok(data) => return Some((path, Ok(data))),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Some((path, Err(e.to_string()))),
+    }
+
+    let entry = fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .find(|entry| entry.file_name().to_string_lossy().eq_ignore_ascii_case(name))?;
+    let path = format!("{}/{}", dir, entry.file_name().to_string_lossy());
+    return Some((path.clone(), fs::read(&path).map_err(|e| e.to_string())));
+}
+
+// looks for a file of the set in the sources. Files match by name, whatever
+// its case (and folder, in zip archives), or, in zip archives, by crc32 and
+// size if renamed.
+// Returns where the file was found and its content.
+pub fn find_file(
+    sources: &[rom_source],
+    info: &rom_info,
+) -> Option<(String, Result<Vec<u8>, String>)> {
+    //println!("find_file");
+
+    for source in sources {
+        match source {
+            rom_source::Dir(dir) => {
+                if let Some(found) = dir_read(dir, info.name) {
+                    return Some(found);
+                }
+            }
+            rom_source::Zip(zip) => {
+                let entry = zip
+                    .entries
+                    .iter()
+                    .find(|entry| entry.file_name().eq_ignore_ascii_case(info.name))
+                    .or_else(|| {
+                        zip.entries
+                            .iter()
+                            .find(|entry| entry.crc32 == info.crc32 && entry.size == info.size)
+                    });
+                if let Some(entry) = entry {
+                    let location = format!("{}:{}", zip.path, entry.name);
+                    return Some((location, zip_read(zip, entry)));
+                }
+            }
+        }
+    }
+    return None;
+}
+
+// looks for a file that is not part of a rom set (such as a sound sample) in
+// the sources, by name only
+pub fn find_named_file(
+    sources: &[rom_source],
+    name: &str,
+) -> Option<(String, Result<Vec<u8>, String>)> {
+    //println!("find_named_file");
+
+    for source in sources {
+        match source {
+            rom_source::Dir(dir) => {
+                if let Some(found) = dir_read(dir, name) {
+                    return Some(found);
+                }
+            }
+            rom_source::Zip(zip) => {
+                let entry = zip
+                    .entries
+                    .iter()
+                    .find(|entry| entry.file_name().eq_ignore_ascii_case(name));
+                if let Some(entry) = entry {
+                    let location = format!("{}:{}", zip.path, entry.name);
+                    return Some((location, zip_read(zip, entry)));
+                }
+            }
+        }
+    }
+    return None;
+}
+
+// lists the files of a source (name, location, content)
+fn source_files(source: &rom_source) -> Vec<(String, String, Option<Vec<u8>>)> {
+    let mut files = Vec::new();
+    match source {
+        rom_source::Dir(dir) => {
+            if let Ok(entries) = fs::read_dir(dir) {
+                for entry in entries.flatten() {
+                    let name = entry.file_name().to_string_lossy().to_string();
+                    let is_file = entry.file_type().map(|t| t.is_file()).unwrap_or(false);
+                    // the placeholder shipped in the roms directory is not a
+                    // rom, and archives are sources of their own
+                    if !is_file || name.ends_with(".txt") || name.ends_with(".zip") {
+                        continue;
+                    }
+                    let path = format!("{}/{}", dir, name);
+                    let data = fs::read(&path).ok();
+                    files.push((name, path, data));
+                }
+            }
+        }
+        rom_source::Zip(zip) => {
+            for entry in &zip.entries {
+                if entry.name.ends_with('/') {
+                    continue;
+                }
+                let location = format!("{}:{}", zip.path, entry.name);
+                files.push((entry.file_name().to_string(), location, zip_read(zip, entry).ok()));
+            }
+        }
+    }
+    files.sort();
+    return files;
+}
+
+// audits every file of the rom set found in the sources, plus any unexpected
+// file found there. Each line of the report gives the file name, its status
+// and where it was found.
+pub fn audit(
+    roms: &'static [rom_info],
+    sources: &[rom_source],
+) -> Vec<(String, rom_status, String)> {
+    //println!("audit");
+
+    let mut report: Vec<(String, rom_status, String)> = Vec::new();
+    let mut used: Vec<String> = Vec::new();
+
+    for info in roms {
+        let (status, location) = match find_file(sources, info) {
+            Some((location, Ok(data))) => (check_rom(info, &data), location),
+            Some((location, Err(e))) => (rom_status::Unreadable(e), location),
+            None => (rom_status::Missing, String::new()),
+        };
+        used.push(location.clone());
+        report.push((info.name.to_string(), status, location));
+    }
+
+    for source in sources {
+        for (name, location, data) in source_files(source) {
+            if used.contains(&location) {
+                continue;
+            }
+            let matches = data
+                .and_then(|data| {
+                    let crc = hash::crc32(&data);
+                    roms.iter()
+                        .find(|info| info.size == data.len() && info.crc32 == crc)
+                })
+                .map(|info| info.name);
+            report.push((name, rom_status::Unknown { matches }, location));
+        }
+    }
+
+    return report;
+}
+
+impl fmt::Display for rom_status {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            rom_status::Good => write!(f, "good"),
+            rom_status::BadDump { crc32 } => write!(f, "bad dump (crc32 {:08x})", crc32),
+            rom_status::WrongSize { size } => write!(f, "bad dump (wrong size: {} bytes)", size),
+            rom_status::Unreadable(e) => write!(f, "unreadable ({})", e),
+            rom_status::Missing => write!(f, "missing"),
+            rom_status::Unknown { matches: None } => write!(f, "unknown"),
+            rom_status::Unknown {
+                matches: Some(name),
+            } => write!(f, "unknown (same content as {})", name),
+        }
+    }
+}
+
+// prints the audit of the rom set of a driver to stdout. Returns true if the
+// whole set is good.
+pub fn verify_roms(d: &driver::driver, rom_path: &[String]) -> bool {
+    //println!("verify_roms");
+
+    println!(
+        "Verifying the rom set of {} ({}) in \"{}\"",
+        d.description,
+        d.name,
+        rom_path.join(";")
+    );
+
+    let sources = open_sources(rom_path, &driver_set_names(d));
+    let report = audit(d.roms, &sources);
+    let (mut good, mut bad, mut missing, mut unknown) = (0, 0, 0, 0);
+    for (name, status, location) in &report {
+        match status {
+            rom_status::Good => good += 1,
+            rom_status::BadDump { .. }
+            | rom_status::WrongSize { .. }
+            | rom_status::Unreadable(_) => bad += 1,
+            rom_status::Missing => missing += 1,
+            rom_status::Unknown { .. } => unknown += 1,
+        }
+        let expected = match status {
+            rom_status::BadDump { .. } | rom_status::WrongSize { .. } => {
+                let info = find_rom(d.roms, name).unwrap();
+                format!(", expected crc32 {:08x}, {} bytes", info.crc32, info.size)
+            }
+            _ => String::new(),
+        };
+        if location.is_empty() {
+            println!("  {:<12} {}{}", name, status, expected);
+        } else {
+            println!("  {:<12} {}{} [{}]", name, status, expected, location);
+        }
+    }
+    println!(
+        "{} good, {} bad, {} missing, {} unknown",
+        good, bad, missing, unknown
+    );
+
+    return bad == 0 && missing == 0;
+}
+
+// MARK: rom loading
+
+// reasons why a rom file could not be loaded
+pub enum rom_error_kind {
+    Missing,
+    Truncated { expected: usize, actual: usize },
+    WrongSize { expected: usize, actual: usize },
+    Unreadable(String),
+}
+
+pub struct rom_file_error {
+    pub path: String,
+    pub kind: rom_error_kind,
+}
+
+impl fmt::Display for rom_file_error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.kind {
+            rom_error_kind::Missing => write!(f, "{}: missing", self.path),
+            rom_error_kind::Truncated { expected, actual } => write!(
+                f,
+                "{}: truncated ({} bytes, expected {})",
+                self.path, actual, expected
+            ),
+            rom_error_kind::WrongSize { expected, actual } => write!(
+                f,
+                "{}: wrong size ({} bytes, expected {})",
+                self.path, actual, expected
+            ),
+            rom_error_kind::Unreadable(e) => write!(f, "{}: {}", self.path, e),
+        }
+    }
+}
+
+// every file of the rom set that failed to load, and the directories that
+// were searched
+pub struct rom_error {
+    pub driver: &'static driver::driver,
+    pub files: Vec<rom_file_error>,
+    pub rom_path: Vec<String>,
+}
+
+impl fmt::Display for rom_error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Could not load the rom set of {}:", self.driver.description)?;
+        for file in &self.files {
+            writeln!(f, "  {}", file)?;
+        }
+        write!(
+            f,
+            "Copy the original rom files, or {}.zip, to one of: {}",
+            self.driver.name,
+            self.rom_path.join(", ")
+        )
+    }
+}
+
+// copies a file of the rom set, looked for in "sources", into memory. The
+// file must be exactly as long as "memory".
+pub fn load_file(
+    sources: &[rom_source],
+    info: &rom_info,
+    memory: &mut [u8],
+) -> Result<(), rom_file_error> {
+    //println!("load_file");
+
+    let (path, data) = match find_file(sources, info) {
+        Some((path, Ok(data))) => (path, data),
+        Some((path, Err(e))) => {
+            return Err(rom_file_error {
+                path,
+                kind: rom_error_kind::Unreadable(e),
+            })
+        }
+        None => {
+            return Err(rom_file_error {
+                path: info.name.to_string(),
+                kind: rom_error_kind::Missing,
+            })
+        }
+    };
+
+    if data.len() != memory.len() {
+        let (expected, actual) = (memory.len(), data.len());
+        let kind = if actual < expected {
+            rom_error_kind::Truncated { expected, actual }
+        } else {
+            rom_error_kind::WrongSize { expected, actual }
+        };
+        return Err(rom_file_error { path, kind });
+    }
+
+    // copying the bytes in memory:
+    memory.copy_from_slice(&data);
+
+    // a file that loads but doesn't match the known dump is most likely a
+    // bad dump: warn about it, as the game may still run.
+    match check_rom(info, memory) {
+        rom_status::Good => {}
+        status => println!(
+            "WARN: {} is not a known good dump: {}, expected crc32 {:08x}",
+            path, status, info.crc32
+        ),
+    }
+
+    return Ok(());
+}
+
+// loads the rom set of the board's driver into its memory, collecting every
+// file that fails. Called before pac_init, which decrypts the roms.
+pub fn load_roms(p: &mut pac::pac, rom_path: &[String]) -> Result<(), rom_error> {
+    //println!("load_roms");
+
+    let d = p.driver;
+    let sources = open_sources(rom_path, &driver_set_names(d));
+    let mut errors: Vec<rom_file_error> = Vec::new();
+    for info in d.roms {
+        if info.region == rom_region::Gfx {
+            let mut data: Vec<u8> = vec![0; info.size];
+            if let Err(e) = load_file(&sources, info, &mut data) {
+                errors.push(e);
+            }
+            let half = info.size / 2;
+            p.tile_rom[info.offset..info.offset + half].copy_from_slice(&data[..half]);
+            p.sprite_rom[info.offset..info.offset + half].copy_from_slice(&data[half..]);
+            continue;
+        }
+        let region: &mut [u8] = match info.region {
+            rom_region::Cpu => &mut p.rom,
+            rom_region::Color => &mut p.color_rom,
+            rom_region::Palette => &mut p.palette_rom,
+            rom_region::Tile => &mut p.tile_rom,
+            rom_region::Sprite => &mut p.sprite_rom,
+            rom_region::Gfx => unreachable!(),
+            rom_region::Sound1 => &mut p.sound_rom1,
+            rom_region::Sound2 => &mut p.sound_rom2,
+        };
+        let memory = &mut region[info.offset..info.offset + info.size];
+        if let Err(e) = load_file(&sources, info, memory) {
+            errors.push(e);
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(rom_error {
+            driver: d,
+            files: errors,
+            rom_path: rom_path.to_vec(),
+        });
+    }
+    return Ok(());
+}
@@ -0,0 +1,127 @@
+#![allow(non_camel_case_types)]
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+
+use crate::*;
+
+// Symbol files: the names of the routines and variables of a game, from a
+// community disassembly, shown by the debugger and --disasm. One symbol by
+// line, with an optional comment (after ";", or the words after the name):
+//
+//   2c5e        print_text      prints message b
+//   4d00:4d07   ghost_pos       y, x of each ghost (a range)
+//   main_state  equ 0x4e00      ; as in assembly sources
+//
+// Addresses are in hex in the first form; the "equ" form reads numbers as
+// the assembler does (0x2c5e, $2c5e, 2c5eh, decimal otherwise).
+
+fn parse_hex(s: &str) -> Option<u16> {
+    let digits = s.trim_start_matches("0x").trim_start_matches('$');
+    return u16::from_str_radix(digits, 16).ok();
+}
+
+fn parse_number(s: &str) -> Option<u16> {
+    let lower = s.to_ascii_lowercase();
+    if lower.starts_with("0x") || lower.starts_with('$') {
+        return parse_hex(&lower);
+    } else if let Some(digits) = lower.strip_suffix('h') {
+        return u16::from_str_radix(digits, 16).ok();
+    }
+    return lower.parse::<u16>().ok();
+}
+
+// parses a symbol file. The symbols are sorted by address.
+pub fn symbols_parse(text: &str) -> Result<Vec<pac_symbol>, String> {
+    //println!("symbols_parse");
+
+    let mut symbols: Vec<pac_symbol> = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let (line, comment) = match line.split_once(';') {
+            Some((line, comment)) => (line, comment.trim()),
+            None => (line, ""),
+        };
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let error = || format!("line {}: expected <addr>[:<end>] <name> or <name> equ <addr>", i + 1);
+
+        let (start, end, name, rest) = match words[..] {
+            [] => continue,
+            [name, equ, value, ref rest @ ..] if equ.eq_ignore_ascii_case("equ") || equ == "=" => {
+                let addr = parse_number(value).ok_or_else(error)?;
+                (addr, addr, name, rest)
+            }
+            [range, name, ref rest @ ..] => {
+                let (start, end) = match range.split_once(':') {
+                    Some((start, end)) => (parse_hex(start), parse_hex(end)),
+                    None => (parse_hex(range), parse_hex(range)),
+                };
+                match (start, end) {
+                    (Some(start), Some(end)) if start <= end => (start, end, name, rest),
+                    _ => return Err(error()),
+                }
+            }
+            _ => return Err(error()),
+        };
+
+        let comment = if comment.is_empty() { rest.join(" ") } else { comment.to_string() };
+        symbols.push(pac_symbol {
+            start,
+            end,
+            name: Box::leak(name.trim_end_matches(':').to_string().into_boxed_str()),
+            comment: Box::leak(comment.into_boxed_str()),
+        });
+    }
+
+    symbols.sort_by_key(|s| s.start);
+    return Ok(symbols);
+}
+
+// loads a symbol file into the board, for as long as it runs
+pub fn symbols_load(p: &mut pac::pac, path: &str) -> Result<usize, String> {
+    //println!("symbols_load");
+
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    let symbols = symbols_parse(&text).map_err(|e| format!("{}: {}", path, e))?;
+    p.debug.symbols = Box::leak(symbols.into_boxed_slice());
+    return Ok(p.debug.symbols.len());
+}
+
+// "addr" as a symbol: its name, plus the offset inside a range
+pub fn symbol_text(symbols: &'static [pac_symbol], addr: u16) -> Option<String> {
+    let s = pac_find_symbol(symbols, addr)?;
+    if addr == s.start {
+        return Some(s.name.to_string());
+    }
+    return Some(format!("{}+0x{:x}", s.name, addr - s.start));
+}
+
+// a symbol of data: outside the program roms (ram, video, io), or a range
+// (a variable of several bytes, a table)
+fn data_symbol(d: &driver::driver, s: &pac_symbol) -> bool {
+    let in_rom = d.roms.iter().filter(|info| info.region == rom_region::Cpu).any(|info| {
+        s.start as usize >= info.offset && (s.start as usize) < info.offset + info.size
+    });
+    return s.end > s.start || !in_rom;
+}
+
+// "addr" as an operand of the disassembly: a jump target or a memory operand
+// by its symbol, an immediate only by a symbol of data, as a number such as
+// the 0x0010 of "ld bc,0x0010" is seldom the routine at that address
+pub fn operand_text(
+    d: &driver::driver,
+    symbols: &'static [pac_symbol],
+    addr: u16,
+    kind: disasm_operand,
+) -> Option<String> {
+    if kind == disasm_operand::Immediate && !data_symbol(d, pac_find_symbol(symbols, addr)?) {
+        return None;
+    }
+    return symbol_text(symbols, addr);
+}
+
+// "addr" in hex, followed by its symbol if it has one
+pub fn addr_text(symbols: &'static [pac_symbol], addr: u16) -> String {
+    return match symbol_text(symbols, addr) {
+        Some(name) => format!("{:04x} ({})", addr, name),
+        None => format!("{:04x}", addr),
+    };
+}
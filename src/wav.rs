@@ -0,0 +1,128 @@
+#![allow(non_camel_case_types)]
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+
+use crate::*;
+
+// Loading of the sound samples played by the boards without a sound chip
+// (see samples.rs in the pac crate): MAME's samples sets, a wav file per
+// effect, recorded from the real board.
+
+// a wav file, converted to 16 bits mono
+pub struct wav {
+    pub rate: u32,
+    pub data: Vec<i16>,
+}
+
+// parses a PCM wav file of 8 or 16 bits. Stereo files are mixed down.
+pub fn wav_parse(data: &[u8]) -> Result<wav, String> {
+    //println!("wav_parse");
+
+    if data.len() < 12 || &data[0..4] != b"RIFF" || &data[8..12] != b"WAVE" {
+        return Err("not a wav file".to_string());
+    }
+
+    let mut channels: usize = 0;
+    let mut bits: usize = 0;
+    let mut rate: u32 = 0;
+    let mut pcm: Option<&[u8]> = None;
+
+    let mut pos: usize = 12;
+    while pos + 8 <= data.len() {
+        let id = &data[pos..pos + 4];
+        let len = u32::from_le_bytes([data[pos + 4], data[pos + 5], data[pos + 6], data[pos + 7]])
+            as usize;
+        let body = &data[pos + 8..(pos + 8 + len).min(data.len())];
+
+        if id == b"fmt " {
+            if body.len() < 16 {
+                return Err("truncated fmt chunk".to_string());
+            }
+            let format = u16::from_le_bytes([body[0], body[1]]);
+            if format != 1 {
+                return Err(format!("unsupported format {}", format));
+            }
+            channels = u16::from_le_bytes([body[2], body[3]]) as usize;
+            rate = u32::from_le_bytes([body[4], body[5], body[6], body[7]]);
+            bits = u16::from_le_bytes([body[14], body[15]]) as usize;
+        } else if id == b"data" {
+            pcm = Some(body);
+        }
+
+        // chunks are padded to an even size
+        pos += 8 + len + (len & 1);
+    }
+
+    if channels == 0 || rate == 0 || (bits != 8 && bits != 16) {
+        return Err("missing or unsupported fmt chunk".to_string());
+    }
+    let pcm = match pcm {
+        Some(pcm) => pcm,
+        None => return Err("missing data chunk".to_string()),
+    };
+
+    let frame_len = channels * bits / 8;
+    let mut out: Vec<i16> = Vec::with_capacity(pcm.len() / frame_len);
+    for frame in pcm.chunks_exact(frame_len) {
+        let mut sum: i32 = 0;
+        for ch in 0..channels {
+            sum += if bits == 8 {
+                // 8 bits samples are unsigned
+                (frame[ch] as i32 - 128) << 8
+            } else {
+                i16::from_le_bytes([frame[ch * 2], frame[ch * 2 + 1]]) as i32
+            };
+        }
+        out.push((sum / channels as i32) as i16);
+    }
+
+    return Ok(wav { rate, data: out });
+}
+
+// directories searched for the samples: each rom directory, and a "samples"
+// directory in it (where MAME keeps them)
+pub fn samples_path(rom_path: &[String]) -> Vec<String> {
+    //println!("samples_path");
+
+    let mut path: Vec<String> = Vec::new();
+    for dir in rom_path {
+        path.push(dir.clone());
+        path.push(format!("{}/samples", dir));
+    }
+    return path;
+}
+
+// loads the samples "<name>.wav" found in the sources. Returns the number of
+// samples loaded; the missing ones are left silent. The samples are kept
+// for the whole run.
+pub fn samples_load(s: &mut samples::samples, sources: &[rom_source], names: &[&str]) -> usize {
+    //println!("samples_load");
+
+    let mut loaded: usize = 0;
+    s.samples = [None; MAX_SAMPLES];
+    for (i, name) in names.iter().take(MAX_SAMPLES).enumerate() {
+        let file_name = format!("{}.wav", name);
+        let smp = match find_named_file(sources, &file_name) {
+            Some((location, Ok(data))) => match wav_parse(&data) {
+                Ok(w) => Some(sample {
+                    rate: w.rate,
+                    data: Box::leak(w.data.into_boxed_slice()),
+                }),
+                Err(e) => {
+                    println!("WARN: cannot read {}: {}", location, e);
+                    None
+                }
+            },
+            Some((location, Err(e))) => {
+                println!("WARN: cannot read {}: {}", location, e);
+                None
+            }
+            None => None,
+        };
+        if smp.is_some() {
+            loaded += 1;
+        }
+        s.samples[i] = smp;
+    }
+    return loaded;
+}
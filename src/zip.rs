@@ -0,0 +1,148 @@
+#![allow(non_camel_case_types)]
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+
+use crate::*;
+
+use std::fs;
+
+// minimal zip archive reader, enough to read MAME-style rom archives:
+// stored and deflated entries, no encryption, no zip64.
+
+const ZIP_LOCAL_HEADER_SIG: u32 = 0x04034b50;
+const ZIP_CENTRAL_HEADER_SIG: u32 = 0x02014b50;
+const ZIP_END_OF_CENTRAL_DIR_SIG: u32 = 0x06054b50;
+
+const ZIP_METHOD_STORED: u16 = 0;
+const ZIP_METHOD_DEFLATED: u16 = 8;
+
+pub struct zip_entry {
+    pub name: String, // full name, including folders
+    pub crc32: u32,
+    pub method: u16,
+    pub compressed_size: usize,
+    pub size: usize,
+    pub header_offset: usize, // offset of the local header
+}
+
+impl zip_entry {
+    // name of the entry without its folders
+    pub fn file_name(&self) -> &str {
+        return self.name.rsplit('/').next().unwrap_or(&self.name);
+    }
+}
+
+pub struct zip_archive {
+    pub path: String,
+    pub entries: Vec<zip_entry>,
+    data: Vec<u8>,
+}
+
+fn read_u16(data: &[u8], pos: usize) -> u16 {
+    return u16::from_le_bytes([data[pos], data[pos + 1]]);
+}
+
+fn read_u32(data: &[u8], pos: usize) -> u32 {
+    return u32::from_le_bytes([data[pos], data[pos + 1], data[pos + 2], data[pos + 3]]);
+}
+
+pub fn zip_open(path: &str) -> Result<zip_archive, String> {
+    //println!("zip_open");
+
+    let data = fs::read(path).map_err(|e| e.to_string())?;
+    return zip_parse(path, data);
+}
+
+// reads the central directory of an archive held in memory
+pub fn zip_parse(path: &str, data: Vec<u8>) -> Result<zip_archive, String> {
+    //println!("zip_parse");
+
+    // the end of central directory record is at the end of the file,
+    // followed by a comment of up to 65535 bytes
+    if data.len() < 22 {
+        return Err("not a zip archive".to_string());
+    }
+    let lowest = data.len().saturating_sub(22 + 0xFFFF);
+    let mut eocd = data.len() - 22;
+    loop {
+        if read_u32(&data, eocd) == ZIP_END_OF_CENTRAL_DIR_SIG {
+            break;
+        }
+        if eocd == lowest {
+            return Err("not a zip archive".to_string());
+        }
+        eocd -= 1;
+    }
+
+    let nb_entries = read_u16(&data, eocd + 10) as usize;
+    let mut pos = read_u32(&data, eocd + 16) as usize;
+
+    let mut entries: Vec<zip_entry> = Vec::with_capacity(nb_entries);
+    for _ in 0..nb_entries {
+        if pos + 46 > data.len() || read_u32(&data, pos) != ZIP_CENTRAL_HEADER_SIG {
+            return Err("corrupted central directory".to_string());
+        }
+        let name_len = read_u16(&data, pos + 28) as usize;
+        let extra_len = read_u16(&data, pos + 30) as usize;
+        let comment_len = read_u16(&data, pos + 32) as usize;
+        if pos + 46 + name_len > data.len() {
+            return Err("corrupted central directory".to_string());
+        }
+        let name = String::from_utf8_lossy(&data[pos + 46..pos + 46 + name_len])
+            .replace('\\', "/");
+
+        entries.push(zip_entry {
+            name,
+            crc32: read_u32(&data, pos + 16),
+            method: read_u16(&data, pos + 10),
+            compressed_size: read_u32(&data, pos + 20) as usize,
+            size: read_u32(&data, pos + 24) as usize,
+            header_offset: read_u32(&data, pos + 42) as usize,
+        });
+
+        pos += 46 + name_len + extra_len + comment_len;
+    }
+
+    return Ok(zip_archive {
+        path: path.to_string(),
+        entries,
+        data,
+    });
+}
+
+// extracts an entry, checking its crc32
+pub fn zip_read(zip: &zip_archive, entry: &zip_entry) -> Result<Vec<u8>, String> {
+    //println!("zip_read");
+
+    let data = &zip.data;
+    let pos = entry.header_offset;
+    if pos + 30 > data.len() || read_u32(data, pos) != ZIP_LOCAL_HEADER_SIG {
+        return Err(format!("{}: corrupted local header", entry.name));
+    }
+    // the local header may have a different extra field than the central one
+    let start = pos + 30 + read_u16(data, pos + 26) as usize + read_u16(data, pos + 28) as usize;
+    let end = start + entry.compressed_size;
+    if end > data.len() {
+        return Err(format!("{}: truncated archive", entry.name));
+    }
+    let compressed = &data[start..end];
+
+    let content = match entry.method {
+        ZIP_METHOD_STORED => compressed.to_vec(),
+        ZIP_METHOD_DEFLATED => {
+            inflate(compressed, entry.size).map_err(|e| format!("{}: {}", entry.name, e))?
+        }
+        method => {
+            return Err(format!(
+                "{}: unsupported compression method {}",
+                entry.name, method
+            ))
+        }
+    };
+
+    if content.len() != entry.size || hash::crc32(&content) != entry.crc32 {
+        return Err(format!("{}: crc error", entry.name));
+    }
+
+    return Ok(content);
+}
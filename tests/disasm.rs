@@ -0,0 +1,45 @@
+#![allow(non_camel_case_types)]
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+
+// Checks that the disassembly of the debugger and --disasm reads the
+// instructions as the cpu fetches them: the opcode from the decrypted
+// opcodes of Pengo and its operands from the data, and the decoded bank of
+// Ms. Pac-Man rather than the roms as loaded.
+
+use pacman4rust::*;
+
+// MARK: pengo
+
+#[test]
+fn pengo_opcodes() {
+    let mut p = Box::new(pac::pac::new());
+    p.driver = &PENGO;
+    pac_init(&mut p);
+    // jp 0x1234, with the opcode differing in the data
+    p.rom[0x0100..0x0103].copy_from_slice(&[0x00, 0x34, 0x12]);
+    p.aux_rom[0x0100..0x0103].copy_from_slice(&[0xc3, 0x00, 0x00]);
+
+    let (text, len) = disasm_at(&mut p, 0x0100);
+    assert_eq!((text.as_str(), len), ("jp 0x1234", 3));
+    assert_eq!(disasm_bytes(&mut p, 0x0100, len), "c3 34 12");
+}
+
+// MARK: ms. pac-man
+
+#[test]
+fn mspacman_bank() {
+    let mut p = Box::new(pac::pac::new());
+    p.driver = &MSPACMAN;
+    // ld a,0x80 in pacman.6e, and another instruction in the decoded bank
+    p.rom[0x0410..0x0412].copy_from_slice(&[0x3e, 0x80]);
+    pac_init(&mut p);
+    p.aux_rom[0x0410..0x0412].copy_from_slice(&[0x06, 0x11]);
+
+    let (text, len) = disasm_at(&mut p, 0x0410);
+    assert_eq!((text.as_str(), len), ("ld b,0x11", 2));
+    assert_eq!(disasm_bytes(&mut p, 0x0410, len), "06 11");
+    // reading the trap addresses doesn't switch the bank
+    disasm_at(&mut p, 0x0038);
+    assert_eq!(disasm_at(&mut p, 0x0410).0, "ld b,0x11");
+}
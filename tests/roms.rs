@@ -0,0 +1,175 @@
+#![allow(non_camel_case_types)]
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+
+// Checks the readers of the rom files against known answers: the crc32 and
+// sha-256 of a few strings, deflate streams made by zlib (a stored, a fixed
+// and a dynamic huffman block), a zip archive made by python's zipfile, and
+// the lookup of the files of a set in a directory and in an archive.
+
+use pacman4rust::*;
+
+// MARK: hashes
+
+#[test]
+fn crc32_known_answers() {
+    assert_eq!(crc32(b""), 0x00000000);
+    assert_eq!(crc32(b"abc"), 0x352441c2);
+    assert_eq!(crc32(b"123456789"), 0xcbf43926);
+}
+
+#[test]
+fn sha256_known_answers() {
+    assert_eq!(sha256_hex(b""), "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+    assert_eq!(sha256_hex(b"abc"), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+    // 56 bytes: the padding takes a second block
+    assert_eq!(
+        sha256_hex(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"),
+        "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+    );
+}
+
+// MARK: inflate
+
+#[test]
+fn inflate_stored() {
+    // final stored block: len 5, nlen !5, then the bytes
+    let stream = [0x01, 0x05, 0x00, 0xfa, 0xff, b'h', b'e', b'l', b'l', b'o'];
+    assert_eq!(inflate(&stream, 0).unwrap(), b"hello");
+}
+
+#[test]
+fn inflate_fixed() {
+    assert_eq!(inflate(&[0x4b, 0x4c, 0x4a, 0x06, 0x00], 0).unwrap(), b"abc");
+    // "abc" then a match of length 9 at distance 3
+    assert_eq!(inflate(&[0x4b, 0x4c, 0x4a, 0x4e, 0x84, 0x21, 0x00], 0).unwrap(), b"abcabcabcabc");
+}
+
+#[test]
+fn inflate_dynamic() {
+    let text: &[u8] = b"The Z80 runs at 3.072 MHz on the Pac-Man board, which has a tile and sprite video chip and a three voice waveform sound generator.";
+    let stream: [u8; 110] = [
+        0x1d, 0xca, 0x31, 0x0e, 0xc2, 0x40, 0x0c, 0x05, 0xd1, 0xab, 0xfc, 0x03, 0x40, 0x14, 0x41, 0x01,
+        0x47, 0xa0, 0x89, 0x44, 0x41, 0x45, 0x67, 0x76, 0x0d, 0xb6, 0x04, 0xeb, 0xc8, 0xeb, 0x24, 0x12,
+        0xa7, 0xc7, 0xa2, 0x9d, 0x37, 0x37, 0x61, 0xdc, 0xcf, 0x23, 0x7c, 0x69, 0x1d, 0x14, 0x38, 0x0e,
+        0xe3, 0xe9, 0x80, 0xe9, 0xf2, 0x85, 0x35, 0x44, 0xe2, 0x95, 0xca, 0x7e, 0xa2, 0x86, 0x87, 0x91,
+        0xd7, 0x1d, 0x36, 0xd1, 0x22, 0x10, 0xca, 0x19, 0xa1, 0x6f, 0x06, 0xb5, 0x8a, 0x3e, 0xbb, 0x06,
+        0x63, 0xd5, 0xca, 0x86, 0x22, 0x3a, 0xff, 0x6b, 0x0e, 0xe2, 0x9c, 0xd9, 0xb4, 0x30, 0x36, 0x5a,
+        0xf9, 0x69, 0xfe, 0x41, 0xb7, 0x25, 0xf1, 0xc5, 0x8d, 0x9d, 0xc2, 0x7c, 0xf8, 0x01,
+    ];
+    assert_eq!((stream[0] >> 1) & 3, 2, "a dynamic huffman block");
+    assert_eq!(inflate(&stream, text.len()).unwrap(), text);
+}
+
+#[test]
+fn inflate_blocks() {
+    // a stored block that is not the last, then a fixed one
+    let stream = [0x00, 0x03, 0x00, 0xfc, 0xff, b'h', b'e', b'l', 0xcb, 0xc9, 0x07, 0x00];
+    assert_eq!(inflate(&stream, 0).unwrap(), b"hello");
+
+    // block type 3 does not exist, and a stream may end too soon
+    assert!(inflate(&[0x07], 0).is_err());
+    assert!(inflate(&[0x4b, 0x4c], 0).is_err());
+}
+
+// MARK: zip
+
+// made by python's zipfile: "pacman/PACMAN.6E" holding "hello", stored, and
+// "renamed.bin" holding "abcabcabcabc", deflated
+const test_zip: [u8; 240] = [
+    0x50, 0x4b, 0x03, 0x04, 0x14, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x21, 0x00, 0x86, 0xa6,
+    0x10, 0x36, 0x05, 0x00, 0x00, 0x00, 0x05, 0x00, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x70, 0x61,
+    0x63, 0x6d, 0x61, 0x6e, 0x2f, 0x50, 0x41, 0x43, 0x4d, 0x41, 0x4e, 0x2e, 0x36, 0x45, 0x68, 0x65,
+    0x6c, 0x6c, 0x6f, 0x50, 0x4b, 0x03, 0x04, 0x14, 0x00, 0x00, 0x00, 0x08, 0x00, 0x00, 0x00, 0x21,
+    0x00, 0x34, 0x2a, 0x6e, 0x5a, 0x07, 0x00, 0x00, 0x00, 0x0c, 0x00, 0x00, 0x00, 0x0b, 0x00, 0x00,
+    0x00, 0x72, 0x65, 0x6e, 0x61, 0x6d, 0x65, 0x64, 0x2e, 0x62, 0x69, 0x6e, 0x4b, 0x4c, 0x4a, 0x4e,
+    0x84, 0x21, 0x00, 0x50, 0x4b, 0x01, 0x02, 0x14, 0x03, 0x14, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x21, 0x00, 0x86, 0xa6, 0x10, 0x36, 0x05, 0x00, 0x00, 0x00, 0x05, 0x00, 0x00, 0x00, 0x10,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x80, 0x01, 0x00, 0x00, 0x00,
+    0x00, 0x70, 0x61, 0x63, 0x6d, 0x61, 0x6e, 0x2f, 0x50, 0x41, 0x43, 0x4d, 0x41, 0x4e, 0x2e, 0x36,
+    0x45, 0x50, 0x4b, 0x01, 0x02, 0x14, 0x03, 0x14, 0x00, 0x00, 0x00, 0x08, 0x00, 0x00, 0x00, 0x21,
+    0x00, 0x34, 0x2a, 0x6e, 0x5a, 0x07, 0x00, 0x00, 0x00, 0x0c, 0x00, 0x00, 0x00, 0x0b, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x80, 0x01, 0x33, 0x00, 0x00, 0x00, 0x72,
+    0x65, 0x6e, 0x61, 0x6d, 0x65, 0x64, 0x2e, 0x62, 0x69, 0x6e, 0x50, 0x4b, 0x05, 0x06, 0x00, 0x00,
+    0x00, 0x00, 0x02, 0x00, 0x02, 0x00, 0x77, 0x00, 0x00, 0x00, 0x63, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+
+// the entries of a set of two files, found in the test archive and directory
+const test_roms: [rom_info; 2] = [
+    rom_info {
+        name: "pacman.6e",
+        region: rom_region::Cpu,
+        offset: 0x0000,
+        size: 5,
+        crc32: 0x3610a686,
+        sha256: "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824",
+    },
+    rom_info {
+        name: "pacman.6f",
+        region: rom_region::Cpu,
+        offset: 0x1000,
+        size: 12,
+        crc32: 0x5a6e2a34,
+        sha256: "",
+    },
+];
+
+#[test]
+fn zip_entries() {
+    let zip = zip_parse("test.zip", test_zip.to_vec()).unwrap();
+    let entries: Vec<(&str, &str, u16, usize)> = zip
+        .entries
+        .iter()
+        .map(|e| (e.name.as_str(), e.file_name(), e.method, e.size))
+        .collect();
+    assert_eq!(
+        entries,
+        vec![("pacman/PACMAN.6E", "PACMAN.6E", 0, 5), ("renamed.bin", "renamed.bin", 8, 12)]
+    );
+    assert_eq!(zip_read(&zip, &zip.entries[0]).unwrap(), b"hello");
+    assert_eq!(zip_read(&zip, &zip.entries[1]).unwrap(), b"abcabcabcabc");
+
+    // a damaged entry fails its crc
+    let mut data = test_zip.to_vec();
+    data[0x2e] = b'j';
+    let zip = zip_parse("test.zip", data).unwrap();
+    assert!(zip_read(&zip, &zip.entries[0]).is_err());
+
+    assert!(zip_parse("test.zip", b"not a zip archive, only text".to_vec()).is_err());
+}
+
+#[test]
+fn find_in_zip() {
+    let sources = [rom_source::Zip(zip_parse("test.zip", test_zip.to_vec()).unwrap())];
+
+    // by name, whatever its case and folder
+    let (location, data) = find_file(&sources, &test_roms[0]).unwrap();
+    assert_eq!(location, "test.zip:pacman/PACMAN.6E");
+    assert!(matches!(check_rom(&test_roms[0], &data.unwrap()), rom_status::Good));
+
+    // renamed: by crc32 and size
+    let (location, data) = find_file(&sources, &test_roms[1]).unwrap();
+    assert_eq!(location, "test.zip:renamed.bin");
+    assert_eq!(data.unwrap(), b"abcabcabcabc");
+}
+
+#[test]
+fn find_in_dir() {
+    let dir = std::env::temp_dir().join(format!("pacman4rust-roms-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("PACMAN.6E"), b"hello").unwrap();
+    let dir_name = dir.to_string_lossy().to_string();
+    let sources = [rom_source::Dir(dir_name.clone())];
+
+    // the case of the name does not matter, as in archives
+    let found = find_file(&sources, &test_roms[0]);
+    let named = find_named_file(&sources, "Pacman.6e");
+    let missing = find_file(&sources, &test_roms[1]);
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let (location, data) = found.unwrap();
+    assert_eq!(location, format!("{}/PACMAN.6E", dir_name));
+    assert_eq!(data.unwrap(), b"hello");
+    assert!(named.is_some());
+    assert!(missing.is_none());
+}
@@ -0,0 +1,900 @@
+#![allow(non_camel_case_types)]
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+
+// Z80 assembler, for rom patches and test programs written as source.
+// One instruction or directive per line, with an optional "label:" before
+// it and a "; comment" after it. It knows every documented mnemonic, in the
+// syntax of the disassembler (lowercase or not), plus the undocumented ones
+// the disassembler shows (sll, ixh/ixl/iyh/iyl, in (c), out (c),0, the
+// register copies of the DDCB/FDCB rotates).
+//
+// directives:
+// - org expr: address of what follows
+// - db expr|"string", ...: bytes
+// - dw expr, ...: words, little endian
+// - ds count[, fill]: "count" bytes of "fill" (0)
+// - name equ expr (or name = expr): a constant
+//
+// Expressions are evaluated as in C (+ - * / % << >> & ^ | ~, parentheses),
+// on numbers (decimal, 0x1f, $1f, 1fh, 0b101, %101), characters ('a'),
+// symbols and "$", the address of the current instruction. Symbols may be
+// used before they are defined, except by org, ds and equ.
+
+use std::collections::HashMap;
+use std::fmt;
+
+// bytes assembled from one address on
+#[derive(Debug, PartialEq)]
+pub struct asm_block {
+    pub org: u16,
+    pub bytes: Vec<u8>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct asm_error {
+    pub line: usize, // from 1
+    pub message: String,
+}
+
+impl fmt::Display for asm_error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+const cc_names: [&str; 8] = ["nz", "z", "nc", "c", "po", "pe", "p", "m"];
+const alu_names: [&str; 8] = ["add", "adc", "sub", "sbc", "and", "xor", "or", "cp"];
+const rot_names: [&str; 8] = ["rlc", "rrc", "rl", "rr", "sla", "sra", "sll", "srl"];
+
+// opcodes without operands
+const implied: [(&str, &[u8]); 35] = [
+    ("nop", &[0x00]),
+    ("rlca", &[0x07]),
+    ("rrca", &[0x0f]),
+    ("rla", &[0x17]),
+    ("rra", &[0x1f]),
+    ("daa", &[0x27]),
+    ("cpl", &[0x2f]),
+    ("scf", &[0x37]),
+    ("ccf", &[0x3f]),
+    ("halt", &[0x76]),
+    ("exx", &[0xd9]),
+    ("di", &[0xf3]),
+    ("ei", &[0xfb]),
+    ("ret", &[0xc9]),
+    ("neg", &[0xed, 0x44]),
+    ("retn", &[0xed, 0x45]),
+    ("reti", &[0xed, 0x4d]),
+    ("rrd", &[0xed, 0x67]),
+    ("rld", &[0xed, 0x6f]),
+    ("ldi", &[0xed, 0xa0]),
+    ("cpi", &[0xed, 0xa1]),
+    ("ini", &[0xed, 0xa2]),
+    ("outi", &[0xed, 0xa3]),
+    ("ldd", &[0xed, 0xa8]),
+    ("cpd", &[0xed, 0xa9]),
+    ("ind", &[0xed, 0xaa]),
+    ("outd", &[0xed, 0xab]),
+    ("ldir", &[0xed, 0xb0]),
+    ("cpir", &[0xed, 0xb1]),
+    ("inir", &[0xed, 0xb2]),
+    ("otir", &[0xed, 0xb3]),
+    ("lddr", &[0xed, 0xb8]),
+    ("cpdr", &[0xed, 0xb9]),
+    ("indr", &[0xed, 0xba]),
+    ("otdr", &[0xed, 0xbb]),
+];
+
+// MARK: operands
+
+#[derive(Copy, Clone, PartialEq)]
+enum operand<'s> {
+    reg(u8),                // b c d e h l a, by their 3 bits code
+    half(u8, u8),           // ixh ixl iyh iyl: prefix, code of h or l
+    mem_hl,                 // (hl)
+    mem_index(u8, &'s str), // (ix+d) (iy+d): prefix, displacement
+    pair(&'static str),     // bc de hl sp af af' ix iy
+    mem_pair(&'static str), // (bc) (de) (sp)
+    mem_c,                  // (c)
+    i_reg,
+    r_reg,
+    imm(&'s str),
+    mem(&'s str), // (nn)
+}
+
+// the text between the parentheses, if they enclose the whole operand
+fn enclosed(s: &str) -> Option<&str> {
+    if !s.starts_with('(') || !s.ends_with(')') {
+        return None;
+    }
+    let mut depth = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 && i != s.len() - 1 {
+                    return None;
+                }
+            }
+            _ => {}
+        }
+    }
+    return Some(s[1..s.len() - 1].trim());
+}
+
+fn parse_operand(s: &str) -> operand<'_> {
+    let lower = s.to_ascii_lowercase();
+    let pairs: [&'static str; 8] = ["bc", "de", "hl", "sp", "af", "af'", "ix", "iy"];
+    if let Some(i) = ["b", "c", "d", "e", "h", "l", "", "a"].iter().position(|r| *r == lower) {
+        return operand::reg(i as u8);
+    }
+    if let Some(name) = pairs.iter().find(|name| **name == lower) {
+        return operand::pair(name);
+    }
+    match lower.as_str() {
+        "ixh" => return operand::half(0xdd, 4),
+        "ixl" => return operand::half(0xdd, 5),
+        "iyh" => return operand::half(0xfd, 4),
+        "iyl" => return operand::half(0xfd, 5),
+        "i" => return operand::i_reg,
+        "r" => return operand::r_reg,
+        _ => {}
+    }
+
+    if let Some(inner) = enclosed(s) {
+        let inner_lower = inner.to_ascii_lowercase();
+        match inner_lower.as_str() {
+            "hl" => return operand::mem_hl,
+            "bc" => return operand::mem_pair("bc"),
+            "de" => return operand::mem_pair("de"),
+            "sp" => return operand::mem_pair("sp"),
+            "c" => return operand::mem_c,
+            _ => {}
+        }
+        for (name, prefix) in [("ix", 0xdd), ("iy", 0xfd)] {
+            if inner_lower.starts_with(name) {
+                let disp = inner[2..].trim();
+                if disp.is_empty() || disp.starts_with('+') || disp.starts_with('-') {
+                    return operand::mem_index(prefix, disp);
+                }
+            }
+        }
+        return operand::mem(inner);
+    }
+    return operand::imm(s);
+}
+
+// splits the operands at the commas outside of parentheses and quotes
+fn split_operands(s: &str) -> Vec<&str> {
+    let mut ops: Vec<&str> = Vec::new();
+    if s.trim().is_empty() {
+        return ops;
+    }
+    let mut depth = 0;
+    let mut quote: Option<char> = None;
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            // the quote of af' is not a string
+            (None, '\'') if s[start..i].trim().eq_ignore_ascii_case("af") => {}
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (None, '(') => depth += 1,
+            (None, ')') => depth -= 1,
+            (None, ',') if depth == 0 => {
+                ops.push(s[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    ops.push(s[start..].trim());
+    return ops;
+}
+
+// MARK: expressions
+
+struct expr_parser<'a> {
+    s: &'a [u8],
+    pos: usize,
+    asm: &'a assembler,
+}
+
+impl<'a> expr_parser<'a> {
+    fn skip_spaces(&mut self) {
+        while self.pos < self.s.len() && self.s[self.pos].is_ascii_whitespace() {
+            self.pos += 1;
+        }
+    }
+
+    // the next binary operator, if it is one of "ops"
+    fn binary_op(&mut self, ops: &[&str]) -> Option<&'static str> {
+        self.skip_spaces();
+        for op in ["<<", ">>", "|", "^", "&", "+", "-", "*", "/", "%"] {
+            if ops.contains(&op) && self.s[self.pos..].starts_with(op.as_bytes()) {
+                self.pos += op.len();
+                return Some(op);
+            }
+        }
+        return None;
+    }
+
+    // binary operators, by precedence
+    fn binary(&mut self, level: usize) -> Result<i64, String> {
+        let levels: [&[&str]; 6] = [&["|"], &["^"], &["&"], &["<<", ">>"], &["+", "-"], &["*", "/", "%"]];
+        if level == levels.len() {
+            return self.unary();
+        }
+        let mut val = self.binary(level + 1)?;
+        while let Some(op) = self.binary_op(levels[level]) {
+            let rhs = self.binary(level + 1)?;
+            val = match op {
+                "|" => val | rhs,
+                "^" => val ^ rhs,
+                "&" => val & rhs,
+                "<<" => val.wrapping_shl(rhs as u32),
+                ">>" => val.wrapping_shr(rhs as u32),
+                "+" => val.wrapping_add(rhs),
+                "-" => val.wrapping_sub(rhs),
+                "*" => val.wrapping_mul(rhs),
+                _ if rhs == 0 => return Err("division by zero".to_string()),
+                "/" => val / rhs,
+                _ => val % rhs,
+            };
+        }
+        return Ok(val);
+    }
+
+    fn unary(&mut self) -> Result<i64, String> {
+        self.skip_spaces();
+        return match self.s.get(self.pos) {
+            Some(b'-') => {
+                self.pos += 1;
+                Ok(self.unary()?.wrapping_neg())
+            }
+            Some(b'+') => {
+                self.pos += 1;
+                self.unary()
+            }
+            Some(b'~') => {
+                self.pos += 1;
+                Ok(!self.unary()?)
+            }
+            _ => self.primary(),
+        };
+    }
+
+    fn primary(&mut self) -> Result<i64, String> {
+        self.skip_spaces();
+        let start = self.pos;
+        let c = match self.s.get(self.pos) {
+            Some(c) => *c,
+            None => return Err("missing value".to_string()),
+        };
+
+        if c == b'(' {
+            self.pos += 1;
+            let val = self.binary(0)?;
+            self.skip_spaces();
+            if self.s.get(self.pos) != Some(&b')') {
+                return Err("missing )".to_string());
+            }
+            self.pos += 1;
+            return Ok(val);
+        }
+        if c == b'\'' {
+            if self.s.len() < self.pos + 3 || self.s[self.pos + 2] != b'\'' {
+                return Err("bad character".to_string());
+            }
+            self.pos += 3;
+            return Ok(self.s[start + 1] as i64);
+        }
+
+        let is_word = |c: u8| c.is_ascii_alphanumeric() || c == b'_' || c == b'.';
+        self.pos += 1;
+        while self.pos < self.s.len() && is_word(self.s[self.pos]) {
+            self.pos += 1;
+        }
+        let word = std::str::from_utf8(&self.s[start..self.pos]).unwrap();
+
+        let number = |digits: &str, radix: u32| {
+            i64::from_str_radix(digits, radix).map_err(|_| format!("bad number {}", word))
+        };
+        let lower = word.to_ascii_lowercase();
+        if c == b'$' && word.len() == 1 {
+            return Ok(self.asm.pc as i64);
+        } else if c == b'$' {
+            return number(&word[1..], 16);
+        } else if c == b'%' {
+            return number(&word[1..], 2);
+        } else if c.is_ascii_digit() {
+            if lower.starts_with("0x") {
+                return number(&word[2..], 16);
+            } else if lower.ends_with('h') {
+                return number(&word[..word.len() - 1], 16);
+            } else if lower.starts_with("0b") {
+                return number(&word[2..], 2);
+            }
+            return number(word, 10);
+        } else if is_word(c) {
+            return self.asm.symbol(word);
+        }
+        return Err(format!("unexpected {}", c as char));
+    }
+}
+
+// MARK: assembler
+
+// the state of a pass over the source
+struct assembler {
+    symbols: HashMap<String, i64>,
+    pc: u16,
+    final_pass: bool, // symbols must be known, and values in range
+    blocks: Vec<asm_block>,
+}
+
+impl assembler {
+    fn symbol(&self, name: &str) -> Result<i64, String> {
+        return match self.symbols.get(name) {
+            Some(val) => Ok(*val),
+            None if !self.final_pass => Ok(0),
+            None => Err(format!("unknown symbol {}", name)),
+        };
+    }
+
+    fn eval(&self, text: &str) -> Result<i64, String> {
+        let mut parser = expr_parser {
+            s: text.as_bytes(),
+            pos: 0,
+            asm: self,
+        };
+        let val = parser.binary(0)?;
+        parser.skip_spaces();
+        if parser.pos != text.len() {
+            return Err(format!("bad expression {}", text));
+        }
+        return Ok(val);
+    }
+
+    // an expression whose symbols are already defined, in the first pass too
+    fn eval_defined(&self, text: &str) -> Result<i64, String> {
+        let known = assembler {
+            symbols: self.symbols.clone(),
+            pc: self.pc,
+            final_pass: true,
+            blocks: Vec::new(),
+        };
+        return known.eval(text);
+    }
+
+    fn byte(&self, text: &str) -> Result<u8, String> {
+        let val = self.eval(text)?;
+        if self.final_pass && !(-128..=255).contains(&val) {
+            return Err(format!("{} out of range of a byte", text));
+        }
+        return Ok(val as u8);
+    }
+
+    fn word(&self, text: &str) -> Result<[u8; 2], String> {
+        let val = self.eval(text)?;
+        if self.final_pass && !(-32768..=65535).contains(&val) {
+            return Err(format!("{} out of range of a word", text));
+        }
+        return Ok((val as u16).to_le_bytes());
+    }
+
+    fn disp(&self, text: &str) -> Result<u8, String> {
+        if text.is_empty() {
+            return Ok(0);
+        }
+        let val = self.eval(text)?;
+        if self.final_pass && !(-128..=127).contains(&val) {
+            return Err(format!("displacement {} out of range", text));
+        }
+        return Ok(val as u8);
+    }
+
+    // offset of a relative jump to "text", from the end of the instruction
+    fn rel(&self, text: &str) -> Result<u8, String> {
+        let target = self.eval(text)?;
+        let offset = target - (self.pc as i64 + 2);
+        if self.final_pass && !(-128..=127).contains(&offset) {
+            return Err(format!("jump to {} out of range", text));
+        }
+        return Ok(offset as u8);
+    }
+
+    fn emit(&mut self, bytes: &[u8]) {
+        if self.blocks.is_empty() {
+            self.blocks.push(asm_block {
+                org: self.pc,
+                bytes: Vec::new(),
+            });
+        }
+        self.blocks.last_mut().unwrap().bytes.extend_from_slice(bytes);
+        self.pc = self.pc.wrapping_add(bytes.len() as u16);
+    }
+
+    fn define(&mut self, name: &str, val: i64) -> Result<(), String> {
+        if !self.final_pass && self.symbols.insert(name.to_string(), val).is_some() {
+            return Err(format!("{} defined twice", name));
+        }
+        self.symbols.insert(name.to_string(), val);
+        return Ok(());
+    }
+
+    fn line(&mut self, line: &str) -> Result<(), String> {
+        let mut line = strip_comment(line).trim();
+
+        // label
+        if let Some(colon) = line.find(':') {
+            let name = line[..colon].trim();
+            if is_symbol(name) {
+                self.define(name, self.pc as i64)?;
+                line = line[colon + 1..].trim();
+            }
+        }
+        if line.is_empty() {
+            return Ok(());
+        }
+
+        let (mnemonic, rest) = match line.find(char::is_whitespace) {
+            Some(i) => (&line[..i], line[i..].trim()),
+            None => (line, ""),
+        };
+
+        // constants: "name equ expr" or "name = expr"
+        let (second, value) = match rest.find(char::is_whitespace) {
+            Some(i) => (&rest[..i], rest[i..].trim()),
+            None => (rest, ""),
+        };
+        if second.eq_ignore_ascii_case("equ") || second == "=" {
+            if !is_symbol(mnemonic) {
+                return Err(format!("bad symbol name {}", mnemonic));
+            }
+            let val = if self.final_pass { self.eval(value)? } else { self.eval_defined(value)? };
+            return self.define(mnemonic, val);
+        }
+        if let Some(value) = line.split_once('=').map(|(name, value)| (name.trim(), value)) {
+            if is_symbol(value.0) {
+                let val = self.eval_defined(value.1)?;
+                return self.define(value.0, val);
+            }
+        }
+
+        let mnemonic = mnemonic.to_ascii_lowercase();
+        let ops = split_operands(rest);
+        match mnemonic.as_str() {
+            "org" => {
+                let org = self.eval_defined(rest)?;
+                if !(0..=0xffff).contains(&org) {
+                    return Err(format!("org {} out of range", rest));
+                }
+                self.pc = org as u16;
+                self.blocks.push(asm_block {
+                    org: self.pc,
+                    bytes: Vec::new(),
+                });
+            }
+            "db" | "defb" => {
+                for op in ops {
+                    if op.len() >= 2 && op.starts_with('"') && op.ends_with('"') {
+                        self.emit(&op.as_bytes()[1..op.len() - 1]);
+                    } else {
+                        let val = self.byte(op)?;
+                        self.emit(&[val]);
+                    }
+                }
+            }
+            "dw" | "defw" => {
+                for op in ops {
+                    let val = self.word(op)?;
+                    self.emit(&val);
+                }
+            }
+            "ds" | "defs" => {
+                let count = self.eval_defined(ops.first().copied().unwrap_or(""))?;
+                let fill = match ops.get(1) {
+                    Some(op) => self.byte(op)?,
+                    None => 0,
+                };
+                if !(0..=0x10000).contains(&count) {
+                    return Err(format!("ds {} out of range", count));
+                }
+                self.emit(&vec![fill; count as usize]);
+            }
+            _ => {
+                let operands: Vec<operand> = ops.iter().map(|op| parse_operand(op)).collect();
+                let bytes = self.encode(&mnemonic, &operands)?;
+                self.emit(&bytes);
+            }
+        }
+        return Ok(());
+    }
+}
+
+fn strip_comment(line: &str) -> &str {
+    let mut quote: Option<char> = None;
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, ';') => return &line[..i],
+            (None, '"') => quote = Some(c),
+            // a quote after af is af'
+            (None, '\'') if !line[..i].to_ascii_lowercase().ends_with("af") => quote = Some(c),
+            _ => {}
+        }
+    }
+    return line;
+}
+
+fn is_symbol(s: &str) -> bool {
+    let mut chars = s.chars();
+    return match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' || c == '.' => {
+            chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+        }
+        _ => false,
+    };
+}
+
+// MARK: encoding
+
+// an operand that instructions on 8 bits registers take: prefix (0 if none),
+// 3 bits code, displacement of (ix+d)
+fn r_operand<'s>(op: &operand<'s>) -> Option<(u8, u8, Option<&'s str>)> {
+    return match *op {
+        operand::reg(code) => Some((0, code, None)),
+        operand::half(prefix, code) => Some((prefix, code, None)),
+        operand::mem_hl => Some((0, 6, None)),
+        operand::mem_index(prefix, disp) => Some((prefix, 6, Some(disp))),
+        _ => None,
+    };
+}
+
+// 2 bits code of a register pair, with the prefix of ix and iy. "table" is
+// the pair in place of sp (af for push and pop).
+fn rp_operand(op: &operand, last: &str) -> Option<(u8, u8)> {
+    if let operand::pair(name) = *op {
+        return match name {
+            "bc" => Some((0, 0)),
+            "de" => Some((0, 1)),
+            "hl" => Some((0, 2)),
+            "ix" => Some((0xdd, 2)),
+            "iy" => Some((0xfd, 2)),
+            _ if name == last => Some((0, 3)),
+            _ => None,
+        };
+    }
+    return None;
+}
+
+// index prefix of an operand, 0 if it has none
+fn prefix_of(op: &operand) -> u8 {
+    return match *op {
+        operand::half(prefix, _) | operand::mem_index(prefix, _) => prefix,
+        operand::pair("ix") => 0xdd,
+        operand::pair("iy") => 0xfd,
+        _ => 0,
+    };
+}
+
+impl assembler {
+    // an opcode on an 8 bits operand, with its prefix and displacement
+    fn r_inst(&self, prefix: u8, opcode: u8, disp: Option<&str>) -> Result<Vec<u8>, String> {
+        let mut bytes: Vec<u8> = Vec::new();
+        if prefix != 0 {
+            bytes.push(prefix);
+        }
+        bytes.push(opcode);
+        if let Some(disp) = disp {
+            bytes.push(self.disp(disp)?);
+        }
+        return Ok(bytes);
+    }
+
+    fn encode(&self, mnemonic: &str, ops: &[operand]) -> Result<Vec<u8>, String> {
+        let bad = || Err(format!("bad operands for {}", mnemonic));
+
+        if let Some((_, bytes)) = implied.iter().find(|(name, _)| *name == mnemonic) {
+            // "ret cc" takes an operand
+            if ops.is_empty() {
+                return Ok(bytes.to_vec());
+            }
+        }
+
+        // alu operations, with or without "a,"
+        if let Some(y) = alu_names.iter().position(|name| *name == mnemonic) {
+            let y = y as u8;
+            let src = match ops {
+                [operand::reg(7), src] => src,
+                [src] => src,
+                [dst, src] if y == 0 || y == 1 || y == 3 => {
+                    // add/adc/sbc on 16 bits
+                    return self.encode_alu16(mnemonic, dst, src);
+                }
+                _ => return bad(),
+            };
+            if let Some((prefix, code, disp)) = r_operand(src) {
+                return self.r_inst(prefix, 0x80 | y << 3 | code, disp);
+            }
+            if let operand::imm(n) = src {
+                return Ok(vec![0xc6 | y << 3, self.byte(n)?]);
+            }
+            return bad();
+        }
+
+        // rotates and bit operations
+        let rot = rot_names.iter().position(|name| *name == mnemonic);
+        let bit = ["bit", "res", "set"].iter().position(|name| *name == mnemonic);
+        if rot.is_some() || bit.is_some() {
+            let (x, y, ops) = match (rot, bit, ops) {
+                (Some(y), _, ops) => (0, y as u8, ops),
+                (_, Some(x), [operand::imm(b), ops @ ..]) => {
+                    let b = self.eval(b)?;
+                    if !(0..=7).contains(&b) {
+                        return Err(format!("bad bit number {}", b));
+                    }
+                    (x as u8 + 1, b as u8, ops)
+                }
+                _ => return bad(),
+            };
+            return match ops {
+                [operand::mem_index(prefix, disp), operand::reg(z)] if x != 1 => {
+                    Ok(vec![*prefix, 0xcb, self.disp(disp)?, x << 6 | y << 3 | z])
+                }
+                [operand::mem_index(prefix, disp)] => {
+                    Ok(vec![*prefix, 0xcb, self.disp(disp)?, x << 6 | y << 3 | 6])
+                }
+                [operand::reg(z)] => Ok(vec![0xcb, x << 6 | y << 3 | z]),
+                [operand::mem_hl] => Ok(vec![0xcb, x << 6 | y << 3 | 6]),
+                _ => bad(),
+            };
+        }
+
+        return match mnemonic {
+            "ld" => self.encode_ld(ops),
+            "inc" | "dec" => {
+                let dec = (mnemonic == "dec") as u8;
+                if let [op] = ops {
+                    if let Some((prefix, code, disp)) = r_operand(op) {
+                        return self.r_inst(prefix, 0x04 | code << 3 | dec, disp);
+                    }
+                    if let Some((prefix, p)) = rp_operand(op, "sp") {
+                        return self.r_inst(prefix, 0x03 | p << 4 | dec << 3, None);
+                    }
+                }
+                bad()
+            }
+            "push" | "pop" => {
+                let base = if mnemonic == "push" { 0xc5 } else { 0xc1 };
+                match ops {
+                    [op] => match rp_operand(op, "af") {
+                        Some((prefix, p)) => self.r_inst(prefix, base | p << 4, None),
+                        None => bad(),
+                    },
+                    _ => bad(),
+                }
+            }
+            "ex" => match ops {
+                [operand::pair("af"), operand::pair("af'")] => Ok(vec![0x08]),
+                [operand::pair("de"), operand::pair("hl")] => Ok(vec![0xeb]),
+                [operand::mem_pair("sp"), op] => match rp_operand(op, "") {
+                    Some((prefix, 2)) => self.r_inst(prefix, 0xe3, None),
+                    _ => bad(),
+                },
+                _ => bad(),
+            },
+            "jp" => match ops {
+                [operand::mem_hl] => Ok(vec![0xe9]),
+                [operand::mem_index(prefix, "")] => Ok(vec![*prefix, 0xe9]),
+                [operand::imm(nn)] => self.with_word(&[0xc3], nn),
+                [cc, operand::imm(nn)] => match cond(cc, 8) {
+                    Some(y) => self.with_word(&[0xc2 | y << 3], nn),
+                    None => bad(),
+                },
+                _ => bad(),
+            },
+            "call" => match ops {
+                [operand::imm(nn)] => self.with_word(&[0xcd], nn),
+                [cc, operand::imm(nn)] => match cond(cc, 8) {
+                    Some(y) => self.with_word(&[0xc4 | y << 3], nn),
+                    None => bad(),
+                },
+                _ => bad(),
+            },
+            "ret" => match ops {
+                [cc] => match cond(cc, 8) {
+                    Some(y) => Ok(vec![0xc0 | y << 3]),
+                    None => bad(),
+                },
+                _ => bad(),
+            },
+            "jr" => match ops {
+                [operand::imm(e)] => Ok(vec![0x18, self.rel(e)?]),
+                [cc, operand::imm(e)] => match cond(cc, 4) {
+                    Some(y) => Ok(vec![0x20 | y << 3, self.rel(e)?]),
+                    None => bad(),
+                },
+                _ => bad(),
+            },
+            "djnz" => match ops {
+                [operand::imm(e)] => Ok(vec![0x10, self.rel(e)?]),
+                _ => bad(),
+            },
+            "rst" => match ops {
+                [operand::imm(n)] => {
+                    let n = self.eval(n)?;
+                    if n & !0x38 != 0 {
+                        return Err(format!("bad rst 0x{:x}", n));
+                    }
+                    Ok(vec![0xc7 | n as u8])
+                }
+                _ => bad(),
+            },
+            "im" => match ops {
+                [operand::imm(n)] => match self.eval(n)? {
+                    0 => Ok(vec![0xed, 0x46]),
+                    1 => Ok(vec![0xed, 0x56]),
+                    2 => Ok(vec![0xed, 0x5e]),
+                    _ => bad(),
+                },
+                _ => bad(),
+            },
+            "in" => match ops {
+                [operand::reg(7), operand::mem(n)] => Ok(vec![0xdb, self.byte(n)?]),
+                [operand::reg(y), operand::mem_c] => Ok(vec![0xed, 0x40 | y << 3]),
+                [operand::mem_c] => Ok(vec![0xed, 0x70]),
+                _ => bad(),
+            },
+            "out" => match ops {
+                [operand::mem(n), operand::reg(7)] => Ok(vec![0xd3, self.byte(n)?]),
+                [operand::mem_c, operand::reg(y)] => Ok(vec![0xed, 0x41 | y << 3]),
+                [operand::mem_c, operand::imm("0")] => Ok(vec![0xed, 0x71]),
+                _ => bad(),
+            },
+            _ => Err(format!("unknown mnemonic {}", mnemonic)),
+        };
+    }
+
+    fn with_word(&self, opcode: &[u8], nn: &str) -> Result<Vec<u8>, String> {
+        let mut bytes = opcode.to_vec();
+        bytes.extend_from_slice(&self.word(nn)?);
+        return Ok(bytes);
+    }
+
+    // add hl,rr, add ix,rr, adc hl,rr and sbc hl,rr
+    fn encode_alu16(&self, mnemonic: &str, dst: &operand, src: &operand) -> Result<Vec<u8>, String> {
+        let (dst_prefix, dst_p) = match rp_operand(dst, "") {
+            Some((prefix, 2)) => (prefix, 2),
+            _ => return Err(format!("bad operands for {}", mnemonic)),
+        };
+        let p = match rp_operand(src, "sp") {
+            Some((prefix, p)) if p != 2 || prefix == dst_prefix => p,
+            _ => return Err(format!("bad operands for {}", mnemonic)),
+        };
+        return match mnemonic {
+            "add" => self.r_inst(dst_prefix, 0x09 | p << 4, None),
+            _ if dst_prefix != 0 || dst_p != 2 => Err(format!("bad operands for {}", mnemonic)),
+            "adc" => Ok(vec![0xed, 0x4a | p << 4]),
+            _ => Ok(vec![0xed, 0x42 | p << 4]),
+        };
+    }
+
+    fn encode_ld(&self, ops: &[operand]) -> Result<Vec<u8>, String> {
+        let bad = || Err("bad operands for ld".to_string());
+        let (dst, src) = match ops {
+            [dst, src] => (dst, src),
+            _ => return bad(),
+        };
+
+        // 8 bits registers and memory through hl, ix or iy
+        if let (Some((dp, d, ddisp)), Some((sp, s, sdisp))) = (r_operand(dst), r_operand(src)) {
+            let index_mem = ddisp.is_some() || sdisp.is_some();
+            let prefix = dp.max(sp);
+            // h and l can't be mixed with ixh/ixl/iyh/iyl, nor memory with
+            // the halves, nor ix with iy. (hl) to (hl) is halt.
+            let h_or_l = |p: u8, c: u8| p == 0 && (c == 4 || c == 5);
+            if (dp != 0 && sp != 0 && (dp != sp || index_mem))
+                || (prefix != 0 && !index_mem && (h_or_l(dp, d) || h_or_l(sp, s)))
+                || (d == 6 && s == 6)
+            {
+                return bad();
+            }
+            return self.r_inst(prefix, 0x40 | d << 3 | s, ddisp.or(sdisp));
+        }
+        if let (Some((prefix, d, disp)), operand::imm(n)) = (r_operand(dst), src) {
+            let mut bytes = self.r_inst(prefix, 0x06 | d << 3, disp)?;
+            bytes.push(self.byte(n)?);
+            return Ok(bytes);
+        }
+
+        return match (dst, src) {
+            (operand::reg(7), operand::mem_pair("bc")) => Ok(vec![0x0a]),
+            (operand::reg(7), operand::mem_pair("de")) => Ok(vec![0x1a]),
+            (operand::reg(7), operand::mem(nn)) => self.with_word(&[0x3a], nn),
+            (operand::mem_pair("bc"), operand::reg(7)) => Ok(vec![0x02]),
+            (operand::mem_pair("de"), operand::reg(7)) => Ok(vec![0x12]),
+            (operand::mem(nn), operand::reg(7)) => self.with_word(&[0x32], nn),
+            (operand::reg(7), operand::i_reg) => Ok(vec![0xed, 0x57]),
+            (operand::reg(7), operand::r_reg) => Ok(vec![0xed, 0x5f]),
+            (operand::i_reg, operand::reg(7)) => Ok(vec![0xed, 0x47]),
+            (operand::r_reg, operand::reg(7)) => Ok(vec![0xed, 0x4f]),
+            (operand::pair("sp"), op) if rp_operand(op, "").map(|(_, p)| p) == Some(2) => {
+                self.r_inst(prefix_of(op), 0xf9, None)
+            }
+            (dst, operand::imm(nn)) => match rp_operand(dst, "sp") {
+                Some((prefix, p)) => {
+                    let mut bytes = self.r_inst(prefix, 0x01 | p << 4, None)?;
+                    bytes.extend_from_slice(&self.word(nn)?);
+                    Ok(bytes)
+                }
+                None => bad(),
+            },
+            (dst, operand::mem(nn)) => match rp_operand(dst, "sp") {
+                Some((prefix, 2)) => {
+                    let mut bytes = self.r_inst(prefix, 0x2a, None)?;
+                    bytes.extend_from_slice(&self.word(nn)?);
+                    Ok(bytes)
+                }
+                Some((_, p)) => self.with_word(&[0xed, 0x4b | p << 4], nn),
+                None => bad(),
+            },
+            (operand::mem(nn), src) => match rp_operand(src, "sp") {
+                Some((prefix, 2)) => {
+                    let mut bytes = self.r_inst(prefix, 0x22, None)?;
+                    bytes.extend_from_slice(&self.word(nn)?);
+                    Ok(bytes)
+                }
+                Some((_, p)) => self.with_word(&[0xed, 0x43 | p << 4], nn),
+                None => bad(),
+            },
+            _ => bad(),
+        };
+    }
+}
+
+// 3 bits code of a condition, among the first "count"
+fn cond(op: &operand, count: usize) -> Option<u8> {
+    let name = match *op {
+        operand::reg(1) => "c",
+        operand::imm(name) => name,
+        _ => return None,
+    };
+    return cc_names[..count]
+        .iter()
+        .position(|cc| cc.eq_ignore_ascii_case(name))
+        .map(|y| y as u8);
+}
+
+// assembles "source". Returns the bytes assembled, in a block for each org.
+pub fn z80_asm(source: &str) -> Result<Vec<asm_block>, asm_error> {
+    //println!("z80_asm");
+
+    let mut asm = assembler {
+        symbols: HashMap::new(),
+        pc: 0,
+        final_pass: false,
+        blocks: Vec::new(),
+    };
+
+    // the first pass finds the address of the labels, the second one
+    // assembles with them
+    for final_pass in [false, true] {
+        asm.pc = 0;
+        asm.final_pass = final_pass;
+        asm.blocks.clear();
+        for (i, line) in source.lines().enumerate() {
+            if let Err(message) = asm.line(line) {
+                return Err(asm_error { line: i + 1, message });
+            }
+        }
+    }
+
+    asm.blocks.retain(|block| !block.bytes.is_empty());
+    return Ok(asm.blocks);
+}
@@ -0,0 +1,365 @@
+#![allow(non_camel_case_types)]
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+
+// Z80 disassembler: every opcode, prefixed (CB, ED, DD/FD, DDCB/FDCB) or
+// not, undocumented ones included (sll, ixh/ixl/iyh/iyl, the register
+// copies of the DDCB/FDCB rotates, in (c), out (c),0). Opcodes are decoded
+// from their x/y/z/p/q fields, as described in "Decoding Z80 Opcodes" by
+// Cristian Dinu. Numbers are in hex, and relative jumps show their target.
+// A DD/FD prefix followed by another prefix is shown as "db", as it has no
+// effect; one followed by an opcode not using hl is shown as that opcode.
+// With z80_disasm_symbols, the jump targets, the (nn) memory operands and
+// the 16 bits immediates are shown by the name a symbol table gives them.
+// The table is told which of them an operand is: an immediate may be a
+// plain number.
+
+const r_names: [&str; 8] = ["b", "c", "d", "e", "h", "l", "(hl)", "a"];
+const rp_names: [&str; 4] = ["bc", "de", "hl", "sp"];
+const rp2_names: [&str; 4] = ["bc", "de", "hl", "af"];
+const cc_names: [&str; 8] = ["nz", "z", "nc", "c", "po", "pe", "p", "m"];
+const alu_names: [&str; 8] = ["add a,", "adc a,", "sub ", "sbc a,", "and ", "xor ", "or ", "cp "];
+const rot_names: [&str; 8] = ["rlc", "rrc", "rl", "rr", "sla", "sra", "sll", "srl"];
+const im_names: [&str; 8] = ["0", "0", "1", "2", "0", "0", "1", "2"];
+const bli_names: [[&str; 4]; 4] = [
+    ["ldi", "cpi", "ini", "outi"],
+    ["ldd", "cpd", "ind", "outd"],
+    ["ldir", "cpir", "inir", "otir"],
+    ["lddr", "cpdr", "indr", "otdr"],
+];
+
+// the operands of an instruction that a symbol may name
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum disasm_operand {
+    Target,    // of a jump, a call, jr or djnz
+    Memory,    // (nn)
+    Immediate, // nn, as in ld rr,nn
+}
+
+// reads the bytes of the instruction, counting them
+struct disasm_reader<'r, F: FnMut(u16) -> u8> {
+    read: &'r mut F,
+    symbol: &'r dyn Fn(u16, disasm_operand) -> Option<String>,
+    addr: u16,
+    len: u16,
+}
+
+impl<'r, F: FnMut(u16) -> u8> disasm_reader<'r, F> {
+    fn next(&mut self) -> u8 {
+        let val = (self.read)(self.addr.wrapping_add(self.len));
+        self.len += 1;
+        return val;
+    }
+
+    fn peek(&mut self) -> u8 {
+        return (self.read)(self.addr.wrapping_add(self.len));
+    }
+
+    fn n(&mut self) -> String {
+        return format!("0x{:02x}", self.next());
+    }
+
+    fn nn(&mut self, kind: disasm_operand) -> String {
+        let lo = self.next() as u16;
+        let hi = self.next() as u16;
+        return self.address(hi << 8 | lo, kind);
+    }
+
+    // target of a relative jump, from the end of the instruction
+    fn e(&mut self) -> String {
+        let d = self.next() as i8;
+        let target = self.addr.wrapping_add(self.len).wrapping_add(d as u16);
+        return self.address(target, disasm_operand::Target);
+    }
+
+    fn address(&self, addr: u16, kind: disasm_operand) -> String {
+        return (self.symbol)(addr, kind).unwrap_or_else(|| format!("0x{:04x}", addr));
+    }
+}
+
+// the operands of the instruction being decoded: hl, h, l and (hl) are
+// replaced by the index register, its halves and (iz+d) after a DD/FD prefix
+struct operands {
+    index: Option<&'static str>, // "ix" or "iy"
+    disp: i8,
+    uses_mem: bool, // (iz+d) is an operand: h and l are not replaced
+}
+
+impl operands {
+    fn r(&self, i: u8) -> String {
+        return match (self.index, i) {
+            (Some(iz), 6) => mem_operand(iz, self.disp),
+            (Some(iz), 4) if !self.uses_mem => format!("{}h", iz),
+            (Some(iz), 5) if !self.uses_mem => format!("{}l", iz),
+            _ => r_names[i as usize].to_string(),
+        };
+    }
+
+    fn hl(&self) -> &'static str {
+        return self.index.unwrap_or("hl");
+    }
+
+    fn rp(&self, p: u8) -> &'static str {
+        return if p == 2 { self.hl() } else { rp_names[p as usize] };
+    }
+
+    fn rp2(&self, p: u8) -> &'static str {
+        return if p == 2 { self.hl() } else { rp2_names[p as usize] };
+    }
+}
+
+fn mem_operand(iz: &str, disp: i8) -> String {
+    if disp < 0 {
+        return format!("({}-0x{:02x})", iz, -(disp as i16));
+    }
+    return format!("({}+0x{:02x})", iz, disp);
+}
+
+// unprefixed opcodes using (hl): with a DD/FD prefix, they are followed by
+// the displacement
+fn uses_hl_mem(op: u8) -> bool {
+    let (x, y, z) = (op >> 6, (op >> 3) & 7, op & 7);
+    return match x {
+        0 => y == 6 && (z == 4 || z == 5 || z == 6),
+        1 => (y == 6 || z == 6) && op != 0x76,
+        2 => z == 6,
+        _ => false,
+    };
+}
+
+// unprefixed opcodes using hl, h or l, that a DD/FD prefix changes
+fn uses_hl(op: u8) -> bool {
+    let (x, y, z, p, q) = (op >> 6, (op >> 3) & 7, op & 7, (op >> 4) & 3, (op >> 3) & 1);
+    return uses_hl_mem(op)
+        || match x {
+            0 => match z {
+                1 => p == 2 || q == 1,
+                2 | 3 => p == 2,
+                4..=6 => y == 4 || y == 5,
+                _ => false,
+            },
+            1 => y == 4 || y == 5 || z == 4 || z == 5,
+            2 => z == 4 || z == 5,
+            3 => op == 0xe1 || op == 0xe3 || op == 0xe5 || op == 0xe9 || op == 0xf9,
+            _ => false,
+        };
+}
+
+// disassembles the instruction at "addr", whose bytes are given by "read".
+// Returns its text and its length in bytes.
+pub fn z80_disasm<F: FnMut(u16) -> u8>(read: &mut F, addr: u16) -> (String, u16) {
+    //println!("z80_disasm");
+
+    return z80_disasm_symbols(read, addr, &|_, _| None);
+}
+
+// as z80_disasm, showing an operand by the name "symbol" gives it, if any
+pub fn z80_disasm_symbols<F: FnMut(u16) -> u8>(
+    read: &mut F,
+    addr: u16,
+    symbol: &dyn Fn(u16, disasm_operand) -> Option<String>,
+) -> (String, u16) {
+    //println!("z80_disasm_symbols");
+
+    let mut r = disasm_reader {
+        read,
+        symbol,
+        addr,
+        len: 0,
+    };
+    let mut op = r.next();
+    let mut ops = operands {
+        index: None,
+        disp: 0,
+        uses_mem: false,
+    };
+
+    if op == 0xdd || op == 0xfd {
+        let next = r.peek();
+        if next == 0xdd || next == 0xfd || next == 0xed {
+            return (format!("db 0x{:02x}", op), 1);
+        }
+        if !uses_hl(next) && next != 0xcb {
+            // the prefix is ignored
+            op = r.next();
+        } else {
+            ops.index = Some(if op == 0xdd { "ix" } else { "iy" });
+            op = r.next();
+            if op == 0xcb {
+                ops.disp = r.next() as i8;
+                let op = r.next();
+                let text = disasm_indexed_cb(&ops, op);
+                return (text, r.len);
+            }
+            if uses_hl_mem(op) {
+                ops.uses_mem = true;
+                ops.disp = r.next() as i8;
+            }
+        }
+    }
+
+    let text = match op {
+        0xcb => {
+            let op = r.next();
+            disasm_cb(&ops, op)
+        }
+        0xed => {
+            let op = r.next();
+            disasm_ed(&mut r, op)
+        }
+        _ => disasm_main(&mut r, &ops, op),
+    };
+    return (text, r.len);
+}
+
+// unprefixed opcodes, or with a DD/FD prefix
+fn disasm_main<F: FnMut(u16) -> u8>(r: &mut disasm_reader<F>, ops: &operands, op: u8) -> String {
+    let (x, y, z, p, q) = (op >> 6, (op >> 3) & 7, op & 7, (op >> 4) & 3, (op >> 3) & 1);
+
+    return match x {
+        0 => match z {
+            0 => match y {
+                0 => "nop".to_string(),
+                1 => "ex af,af'".to_string(),
+                2 => format!("djnz {}", r.e()),
+                3 => format!("jr {}", r.e()),
+                _ => format!("jr {},{}", cc_names[y as usize - 4], r.e()),
+            },
+            1 => match q {
+                0 => format!("ld {},{}", ops.rp(p), r.nn(disasm_operand::Immediate)),
+                _ => format!("add {},{}", ops.hl(), ops.rp(p)),
+            },
+            2 => match (q, p) {
+                (0, 0) => "ld (bc),a".to_string(),
+                (0, 1) => "ld (de),a".to_string(),
+                (0, 2) => format!("ld ({}),{}", r.nn(disasm_operand::Memory), ops.hl()),
+                (0, _) => format!("ld ({}),a", r.nn(disasm_operand::Memory)),
+                (_, 0) => "ld a,(bc)".to_string(),
+                (_, 1) => "ld a,(de)".to_string(),
+                (_, 2) => format!("ld {},({})", ops.hl(), r.nn(disasm_operand::Memory)),
+                (_, _) => format!("ld a,({})", r.nn(disasm_operand::Memory)),
+            },
+            3 => match q {
+                0 => format!("inc {}", ops.rp(p)),
+                _ => format!("dec {}", ops.rp(p)),
+            },
+            4 => format!("inc {}", ops.r(y)),
+            5 => format!("dec {}", ops.r(y)),
+            6 => {
+                let dst = ops.r(y);
+                format!("ld {},{}", dst, r.n())
+            }
+            _ => ["rlca", "rrca", "rla", "rra", "daa", "cpl", "scf", "ccf"][y as usize].to_string(),
+        },
+        1 => match op {
+            0x76 => "halt".to_string(),
+            _ => format!("ld {},{}", ops.r(y), ops.r(z)),
+        },
+        2 => format!("{}{}", alu_names[y as usize], ops.r(z)),
+        _ => match z {
+            0 => format!("ret {}", cc_names[y as usize]),
+            1 => match (q, p) {
+                (0, _) => format!("pop {}", ops.rp2(p)),
+                (_, 0) => "ret".to_string(),
+                (_, 1) => "exx".to_string(),
+                (_, 2) => format!("jp ({})", ops.hl()),
+                (_, _) => format!("ld sp,{}", ops.hl()),
+            },
+            2 => format!("jp {},{}", cc_names[y as usize], r.nn(disasm_operand::Target)),
+            3 => match y {
+                0 => format!("jp {}", r.nn(disasm_operand::Target)),
+                2 => format!("out ({}),a", r.n()),
+                3 => format!("in a,({})", r.n()),
+                4 => format!("ex (sp),{}", ops.hl()),
+                5 => "ex de,hl".to_string(),
+                6 => "di".to_string(),
+                7 => "ei".to_string(),
+                _ => unreachable!(), // 0xcb, decoded by the caller
+            },
+            4 => format!("call {},{}", cc_names[y as usize], r.nn(disasm_operand::Target)),
+            5 => match (q, p) {
+                (0, _) => format!("push {}", ops.rp2(p)),
+                (_, 0) => format!("call {}", r.nn(disasm_operand::Target)),
+                _ => unreachable!(), // prefixes, decoded by the caller
+            },
+            6 => format!("{}{}", alu_names[y as usize], r.n()),
+            _ => format!("rst 0x{:02x}", y * 8),
+        },
+    };
+}
+
+// CB opcodes
+fn disasm_cb(ops: &operands, op: u8) -> String {
+    let (x, y, z) = (op >> 6, (op >> 3) & 7, op & 7);
+
+    return match x {
+        0 => format!("{} {}", rot_names[y as usize], ops.r(z)),
+        1 => format!("bit {},{}", y, ops.r(z)),
+        2 => format!("res {},{}", y, ops.r(z)),
+        _ => format!("set {},{}", y, ops.r(z)),
+    };
+}
+
+// DDCB/FDCB opcodes. Except for bit, those not on (iz+d) also copy the
+// result to a register.
+fn disasm_indexed_cb(ops: &operands, op: u8) -> String {
+    let (x, y, z) = (op >> 6, (op >> 3) & 7, op & 7);
+    let mem = mem_operand(ops.index.unwrap_or("ix"), ops.disp);
+    let reg = if z == 6 || x == 1 {
+        String::new()
+    } else {
+        format!(",{}", r_names[z as usize])
+    };
+
+    return match x {
+        0 => format!("{} {}{}", rot_names[y as usize], mem, reg),
+        1 => format!("bit {},{}", y, mem),
+        2 => format!("res {},{}{}", y, mem, reg),
+        _ => format!("set {},{}{}", y, mem, reg),
+    };
+}
+
+// ED opcodes. The undefined ones do nothing and are shown as bytes.
+fn disasm_ed<F: FnMut(u16) -> u8>(r: &mut disasm_reader<F>, op: u8) -> String {
+    let (x, y, z, p, q) = (op >> 6, (op >> 3) & 7, op & 7, (op >> 4) & 3, (op >> 3) & 1);
+
+    if x == 1 {
+        return match z {
+            0 => match y {
+                6 => "in (c)".to_string(),
+                _ => format!("in {},(c)", r_names[y as usize]),
+            },
+            1 => match y {
+                6 => "out (c),0".to_string(),
+                _ => format!("out (c),{}", r_names[y as usize]),
+            },
+            2 => match q {
+                0 => format!("sbc hl,{}", rp_names[p as usize]),
+                _ => format!("adc hl,{}", rp_names[p as usize]),
+            },
+            3 => match q {
+                0 => format!("ld ({}),{}", r.nn(disasm_operand::Memory), rp_names[p as usize]),
+                _ => format!("ld {},({})", rp_names[p as usize], r.nn(disasm_operand::Memory)),
+            },
+            4 => "neg".to_string(),
+            5 => match y {
+                1 => "reti".to_string(),
+                _ => "retn".to_string(),
+            },
+            6 => format!("im {}", im_names[y as usize]),
+            _ => match y {
+                0 => "ld i,a".to_string(),
+                1 => "ld r,a".to_string(),
+                2 => "ld a,i".to_string(),
+                3 => "ld a,r".to_string(),
+                4 => "rrd".to_string(),
+                5 => "rld".to_string(),
+                _ => format!("db 0xed,0x{:02x}", op),
+            },
+        };
+    }
+    if x == 2 && z <= 3 && y >= 4 {
+        return bli_names[y as usize - 4][z as usize].to_string();
+    }
+    return format!("db 0xed,0x{:02x}", op);
+}
@@ -1,32 +1,102 @@
 #![allow(non_camel_case_types)]
 #![allow(non_snake_case)]
 #![allow(non_upper_case_globals)]
-
-use crate::*;
-
-type read_byte = fn(userdata: &mut userdata, addr: u16) -> u8;
-type write_byte = fn(userdata: &mut userdata, addr: u16, val: u8);
-type port_in = fn(z: &mut z80, port: u8) -> u8;
-type port_out = fn(z: &mut z80, port: u8, val: u8);
-
-pub fn read_byte_null(_userdata: &mut userdata, _addr: u16) -> u8 {
+#![cfg_attr(not(feature = "std"), no_std)]
+
+// Z80 cpu emulator, with an optional intel 8080 mode.
+//
+// The cpu knows nothing of the board it runs on: "z80<U>" holds the board's
+// own data "U" (given to z80::new), and reaches memory, io and the interrupt
+// acknowledge through callbacks. A board sets them, then calls z80_init and
+// runs the cpu with z80_step or z80_run.
+//
+// features:
+// - i8080: the 8080 mode ("i8080" field), compiled out otherwise
+// - std (default): without it the crate is no_std, and the diagnostics of
+//   unknown opcodes are not printed
+// - trace: the "trace" hook, called before each instruction, and
+//   z80_debug_output (with std)
+// - state: z80_save_state and z80_load_state
+// - disasm: z80_disasm (disasm.rs), also used by z80_debug_output
+// - asm: z80_asm (asm.rs), to write patches and test programs as source
+
+use core::ptr;
+
+#[cfg(feature = "disasm")]
+pub mod disasm;
+#[cfg(feature = "disasm")]
+pub use disasm::*;
+#[cfg(feature = "asm")]
+pub mod asm;
+#[cfg(feature = "asm")]
+pub use asm::*;
+
+pub type read_byte<U> = fn(userdata: &mut U, addr: u16) -> u8;
+pub type write_byte<U> = fn(userdata: &mut U, addr: u16, val: u8);
+pub type port_in<U> = fn(z: &mut z80<U>, port: u8) -> u8;
+pub type port_out<U> = fn(z: &mut z80<U>, port: u8, val: u8);
+pub type int_ack<U> = fn(z: &mut z80<U>) -> u8;
+pub type read_opcode<U> = fn(z: &mut z80<U>, addr: u16) -> u8;
+pub type wait_states<U> = fn(z: &mut z80<U>, access: bus_access, addr: u16) -> u32;
+pub type trace<U> = fn(z: &mut z80<U>);
+
+// the kinds of bus cycles a board can add wait states to
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum bus_access {
+    Opcode, // m1 cycle
+    MemRead,
+    MemWrite,
+    IoRead,
+    IoWrite,
+}
+
+pub fn read_byte_null<U>(_userdata: &mut U, _addr: u16) -> u8 {
     return 0;
 }
 
-pub fn write_byte_null(_userdata: &mut userdata, _addr: u16, _val: u8) {}
+pub fn write_byte_null<U>(_userdata: &mut U, _addr: u16, _val: u8) {}
 
-pub fn port_in_null(_z: &mut z80, _port: u8) -> u8 {
+pub fn port_in_null<U>(_z: &mut z80<U>, _port: u8) -> u8 {
     return 0;
 }
 
-pub fn port_out_null(_z: &mut z80, _port: u8, _val: u8) {}
-
-pub struct z80<'a> {
-    pub read_byte: read_byte,
-    pub write_byte: write_byte,
-    pub port_in: port_in,
-    pub port_out: port_out,
-    pub userdata: userdata<'a>,
+pub fn port_out_null<U>(_z: &mut z80<U>, _port: u8, _val: u8) {}
+
+// opcode fetches are plain memory reads, unless the board tells them apart
+pub fn read_opcode_default<U>(z: &mut z80<U>, addr: u16) -> u8 {
+    return (z.read_byte)(&mut z.userdata, addr);
+}
+
+// nothing drives the data bus: it floats high, which is rst 38h in im 0
+pub fn int_ack_null<U>(_z: &mut z80<U>) -> u8 {
+    return 0xFF;
+}
+
+pub struct z80<U> {
+    pub read_byte: read_byte<U>,
+    pub write_byte: write_byte<U>,
+    pub port_in: port_in<U>,
+    pub port_out: port_out<U>,
+    // reads the data bus when an interrupt is acknowledged: the vector in
+    // im 2, the instruction to execute in im 0 (once per byte)
+    pub int_ack: int_ack<U>,
+    // extra t-states of a bus access (memory contention, slow devices...),
+    // None if the board has no wait states
+    pub wait_states: Option<wait_states<U>>,
+    // reads opcodes (m1 cycles, the prefixes and the opcode following them)
+    // where read_byte reads the operands and data. For the cpus that only
+    // decrypt opcodes, and for debuggers telling code from data.
+    pub read_opcode: read_opcode<U>,
+    // called by z80_step before each instruction (but the nops of a halt
+    // skipped by z80_run), None if nothing traces the cpu. Calling
+    // z80_stop_run from it ends z80_run before the instruction.
+    #[cfg(feature = "trace")]
+    pub trace: Option<trace<U>>,
+    pub userdata: U,
+
+    // intel 8080 mode: 8080 flags and timings, no prefixed opcodes
+    #[cfg(feature = "i8080")]
+    pub i8080: bool,
 
     // cycle count (t-states)
     pub cyc: u64,
@@ -59,35 +129,46 @@ pub struct z80<'a> {
     pub i: u8,
     pub r: u8,
 
-    // flags: sign, zero, yf, half-carry, xf, parity/overflow, negative, carry
-    pub sf: u8,
-    pub zf: u8,
-    pub yf: u8,
-    pub hf: u8,
-    pub xf: u8,
-    pub pf: u8,
-    pub nf: u8,
-    pub cf: u8,
+    // flags (see FLAG_*)
+    pub f: u8,
+    // "q": the flags set by the last instruction, or 0 if it did not set
+    // any. Read by scf/ccf for xf and yf.
+    pub q: u8,
 
     pub iff_delay: u8,
     pub interrupt_mode: u8,
-    pub int_data: u8,
     pub iff1: bool,
     pub iff2: bool,
     pub halted: bool,
-    pub int_pending: bool,
+    // state of the int line, held by the board until it clears it
+    pub irq_line: bool,
+    // set while an im 0 acknowledge reads its instruction from the data bus
+    pub int_ack_fetch: bool,
     pub nmi_pending: bool,
+
+    // cycles z80_run executed past its budget, taken from the next one
+    pub run_extra: u64,
+    // set by the board to end z80_run after the current instruction
+    pub run_stop: bool,
 }
 
-impl<'a> z80<'a> {
-    pub fn new() -> Self {
+impl<U> z80<U> {
+    pub fn new(userdata: U) -> Self {
         Self {
             read_byte: read_byte_null,
             write_byte: write_byte_null,
             port_in: port_in_null,
             port_out: port_out_null,
+            int_ack: int_ack_null,
+            read_opcode: read_opcode_default,
+            wait_states: None,
+            #[cfg(feature = "trace")]
+            trace: None,
+
+            userdata,
 
-            userdata: userdata::new(),
+            #[cfg(feature = "i8080")]
+            i8080: false,
             // cycle count (t-states)
             cyc: 0,
 
@@ -119,30 +200,26 @@ impl<'a> z80<'a> {
             i: 0,
             r: 0,
 
-            // flags: sign, zero, yf, half-carry, xf, parity/overflow, negative, carry
-            sf: 0,
-            zf: 0,
-            yf: 0,
-            hf: 0,
-            xf: 0,
-            pf: 0,
-            nf: 0,
-            cf: 0,
+            f: 0,
+            q: 0,
 
             iff_delay: 0,
             interrupt_mode: 0,
-            int_data: 0,
             iff1: false,
             iff2: false,
             halted: false,
-            int_pending: false,
+            irq_line: false,
+            int_ack_fetch: false,
             nmi_pending: false,
+
+            run_extra: 0,
+            run_stop: false,
         }
     }
 }
 
 // MARK: timings
-pub const cyc_00: [u8; 256] = [
+pub static cyc_00: [u8; 256] = [
     4, 10, 7, 6, 4, 4, 7, 4, 4, 11, 7, 6, 4, 4, 7, 4, 8, 10, 7, 6, 4, 4, 7, 4, 12, 11, 7, 6, 4, 4,
     7, 4, 7, 10, 16, 6, 4, 4, 7, 4, 7, 11, 16, 6, 4, 4, 7, 4, 7, 10, 13, 6, 11, 11, 10, 4, 7, 11,
     13, 6, 4, 4, 7, 4, 4, 4, 4, 4, 4, 4, 7, 4, 4, 4, 4, 4, 4, 4, 7, 4, 4, 4, 4, 4, 4, 4, 7, 4, 4,
@@ -154,7 +231,7 @@ pub const cyc_00: [u8; 256] = [
     11, 5, 10, 10, 4, 10, 11, 7, 11, 5, 6, 10, 4, 10, 0, 7, 11,
 ];
 
-pub const cyc_ed: [u8; 256] = [
+pub static cyc_ed: [u8; 256] = [
     8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8,
     8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8,
     12, 12, 15, 20, 8, 14, 8, 9, 12, 12, 15, 20, 8, 14, 8, 9, 12, 12, 15, 20, 8, 14, 8, 9, 12, 12,
@@ -166,7 +243,7 @@ pub const cyc_ed: [u8; 256] = [
     8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8,
 ];
 
-pub const cyc_ddfd: [u8; 256] = [
+pub static cyc_ddfd: [u8; 256] = [
     4, 4, 4, 4, 4, 4, 4, 4, 4, 15, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 15, 4, 4, 4, 4, 4,
     4, 4, 14, 20, 10, 8, 8, 11, 4, 4, 15, 20, 10, 8, 8, 11, 4, 4, 4, 4, 4, 23, 23, 19, 4, 4, 15, 4,
     4, 4, 4, 4, 4, 4, 4, 4, 4, 8, 8, 19, 4, 4, 4, 4, 4, 8, 8, 19, 4, 4, 4, 4, 4, 8, 8, 19, 4, 4, 4,
@@ -178,6 +255,95 @@ pub const cyc_ddfd: [u8; 256] = [
     4, 4, 4, 4, 4, 4, 4, 4, 10, 4, 4, 4, 4, 4, 4,
 ];
 
+// timings of the intel 8080, used in 8080 mode
+pub static cyc_8080: [u8; 256] = [
+    4, 10, 7, 5, 5, 5, 7, 4, 4, 10, 7, 5, 5, 5, 7, 4, 4, 10, 7, 5, 5, 5, 7, 4, 4, 10, 7, 5, 5, 5,
+    7, 4, 4, 10, 16, 5, 5, 5, 7, 4, 4, 10, 16, 5, 5, 5, 7, 4, 4, 10, 13, 5, 10, 10, 10, 4, 4, 10,
+    13, 5, 5, 5, 7, 4, 5, 5, 5, 5, 5, 5, 7, 5, 5, 5, 5, 5, 5, 5, 7, 5, 5, 5, 5, 5, 5, 5, 7, 5, 5,
+    5, 5, 5, 5, 5, 7, 5, 5, 5, 5, 5, 5, 5, 7, 5, 5, 5, 5, 5, 5, 5, 7, 5, 7, 7, 7, 7, 7, 7, 7, 7, 5,
+    5, 5, 5, 5, 5, 7, 5, 4, 4, 4, 4, 4, 4, 7, 4, 4, 4, 4, 4, 4, 4, 7, 4, 4, 4, 4, 4, 4, 4, 7, 4, 4,
+    4, 4, 4, 4, 4, 7, 4, 4, 4, 4, 4, 4, 4, 7, 4, 4, 4, 4, 4, 4, 4, 7, 4, 4, 4, 4, 4, 4, 4, 7, 4, 4,
+    4, 4, 4, 4, 4, 7, 4, 5, 10, 10, 10, 11, 11, 7, 11, 5, 10, 10, 10, 11, 17, 7, 11, 5, 10, 10, 10,
+    11, 11, 7, 11, 5, 10, 10, 10, 11, 17, 7, 11, 5, 10, 10, 18, 11, 11, 7, 11, 5, 5, 10, 4, 11, 17,
+    7, 11, 5, 10, 10, 4, 11, 11, 7, 11, 5, 5, 10, 4, 11, 17, 7, 11,
+];
+
+// MARK: flags
+// bits of the flags register
+pub const FLAG_C: u8 = 0x01; // carry
+pub const FLAG_N: u8 = 0x02; // negative: the last operation was a substraction
+pub const FLAG_P: u8 = 0x04; // parity/overflow
+pub const FLAG_X: u8 = 0x08; // undocumented, usually bit 3 of the result
+pub const FLAG_H: u8 = 0x10; // half-carry
+pub const FLAG_Y: u8 = 0x20; // undocumented, usually bit 5 of the result
+pub const FLAG_Z: u8 = 0x40; // zero
+pub const FLAG_S: u8 = 0x80; // sign
+pub const FLAG_XY: u8 = FLAG_X | FLAG_Y;
+
+// the flags that only depend on a result are looked up in these tables,
+// computed at compile time, rather than set one by one
+
+// sign, zero, yf and xf of a byte
+pub static SZ: [u8; 256] = make_sz();
+// the same plus the parity
+pub static SZP: [u8; 256] = make_szp();
+// flags of "inc r" and "dec r" (but the carry, left unchanged) by result:
+// sign, zero, half-carry, overflow, yf, xf (and nf for dec)
+pub static SZHV_inc: [u8; 256] = make_szhv(false);
+pub static SZHV_dec: [u8; 256] = make_szhv(true);
+
+const fn make_sz() -> [u8; 256] {
+    let mut table: [u8; 256] = [0; 256];
+    let mut i: usize = 0;
+    while i < 256 {
+        table[i] = (i as u8) & (FLAG_S | FLAG_XY);
+        if i == 0 {
+            table[i] |= FLAG_Z;
+        }
+        i += 1;
+    }
+    return table;
+}
+
+const fn make_szp() -> [u8; 256] {
+    let mut table: [u8; 256] = make_sz();
+    let mut i: usize = 0;
+    while i < 256 {
+        if (i as u8).count_ones() % 2 == 0 {
+            table[i] |= FLAG_P;
+        }
+        i += 1;
+    }
+    return table;
+}
+
+const fn make_szhv(dec: bool) -> [u8; 256] {
+    let mut table: [u8; 256] = make_sz();
+    let mut i: usize = 0;
+    while i < 256 {
+        if dec {
+            // borrow from bit 4, overflow from 0x80 to 0x7f
+            table[i] |= FLAG_N;
+            if i & 0x0F == 0x0F {
+                table[i] |= FLAG_H;
+            }
+            if i == 0x7F {
+                table[i] |= FLAG_P;
+            }
+        } else {
+            // carry to bit 4, overflow from 0x7f to 0x80
+            if i & 0x0F == 0x00 {
+                table[i] |= FLAG_H;
+            }
+            if i == 0x80 {
+                table[i] |= FLAG_P;
+            }
+        }
+        i += 1;
+    }
+    return table;
+}
+
 // MARK: helpers
 
 // get bit "n" of number "val"
@@ -187,139 +353,185 @@ pub fn GET_BIT(n: u8, val: u8) -> u8 {
     return (val >> n) & 1;
 }
 
-pub fn rb(z: &mut z80, addr: u16) -> u8 {
+// whether the cpu is in 8080 mode. Without the "i8080" feature it never
+// is, and the 8080 code is compiled out.
+#[inline(always)]
+#[cfg_attr(not(feature = "i8080"), allow(unused_variables))]
+pub fn is_8080<U>(z: &z80<U>) -> bool {
+    #[cfg(feature = "i8080")]
+    return z.i8080;
+    #[cfg(not(feature = "i8080"))]
+    return false;
+}
+
+// adds the wait states of a bus access to the cycle count
+pub fn wait<U>(z: &mut z80<U>, access: bus_access, addr: u16) {
+    if let Some(wait_states) = z.wait_states {
+        z.cyc += wait_states(z, access, addr) as u64;
+    }
+}
+
+pub fn rb<U>(z: &mut z80<U>, addr: u16) -> u8 {
     //println!("rb-z80");
 
+    wait(z, bus_access::MemRead, addr);
     let result = (z.read_byte)(&mut z.userdata, addr);
 
     return result;
 }
 
-pub fn wb(z: &mut z80, addr: u16, val: u8) {
+pub fn wb<U>(z: &mut z80<U>, addr: u16, val: u8) {
     //println!("wb-z80");
 
+    wait(z, bus_access::MemWrite, addr);
     (z.write_byte)(&mut z.userdata, addr, val);
 }
 
-pub fn rw(z: &mut z80, addr: u16) -> u16 {
+pub fn rw<U>(z: &mut z80<U>, addr: u16) -> u16 {
     //println!("rw");
 
-    let value1 = ((z.read_byte)(&mut z.userdata, addr + 1) as u16) << 8;
-    let value2 = (z.read_byte)(&mut z.userdata, addr) as u16;
+    let value1 = (rb(z, addr.wrapping_add(1)) as u16) << 8;
+    let value2 = rb(z, addr) as u16;
     let result = value1 | value2;
 
     return result as u16;
 }
 
-pub fn ww(z: &mut z80, addr: u16, val: u16) {
+pub fn ww<U>(z: &mut z80<U>, addr: u16, val: u16) {
     //println!("ww");
 
-    (z.write_byte)(&mut z.userdata, addr, (val & 0xFF) as u8);
-    (z.write_byte)(&mut z.userdata, addr + 1, (val >> 8) as u8);
+    wb(z, addr, (val & 0xFF) as u8);
+    wb(z, addr.wrapping_add(1), (val >> 8) as u8);
 }
 
-pub fn pushw(z: &mut z80, val: u16) {
+pub fn port_rb<U>(z: &mut z80<U>, port: u8) -> u8 {
+    //println!("port_rb");
+
+    wait(z, bus_access::IoRead, port as u16);
+    return (z.port_in)(z, port);
+}
+
+pub fn port_wb<U>(z: &mut z80<U>, port: u8, val: u8) {
+    //println!("port_wb");
+
+    wait(z, bus_access::IoWrite, port as u16);
+    (z.port_out)(z, port, val);
+}
+
+pub fn pushw<U>(z: &mut z80<U>, val: u16) {
     //println!("pushw");
 
-    z.sp -= 2;
+    z.sp = z.sp.wrapping_sub(2);
     ww(z, z.sp, val);
 }
 
-pub fn popw(z: &mut z80) -> u16 {
+pub fn popw<U>(z: &mut z80<U>) -> u16 {
     //println!("popw");
 
-    z.sp += 2;
-    return rw(z, z.sp - 2);
+    z.sp = z.sp.wrapping_add(2);
+    return rw(z, z.sp.wrapping_sub(2));
 }
 
-pub fn nextb(z: &mut z80) -> u8 {
+pub fn nextb<U>(z: &mut z80<U>) -> u8 {
     //println!("nextb");
 
+    // an im 0 instruction comes from the data bus, pc does not move
+    if z.int_ack_fetch {
+        return (z.int_ack)(z);
+    }
     let temp = rb(z, z.pc);
-    z.pc += 1;
+    z.pc = z.pc.wrapping_add(1);
+    return temp;
+}
+
+// fetches the next opcode (or prefixed opcode)
+pub fn nextop<U>(z: &mut z80<U>) -> u8 {
+    //println!("nextop");
+
+    if z.int_ack_fetch {
+        return (z.int_ack)(z);
+    }
+    wait(z, bus_access::Opcode, z.pc);
+    let temp = (z.read_opcode)(z, z.pc);
+    z.pc = z.pc.wrapping_add(1);
     return temp;
 }
 
-pub fn nextw(z: &mut z80) -> u16 {
+pub fn nextw<U>(z: &mut z80<U>) -> u16 {
     //println!("nextw");
 
-    z.pc += 2;
-    return rw(z, z.pc - 2);
+    if z.int_ack_fetch {
+        let lo = (z.int_ack)(z) as u16;
+        let hi = (z.int_ack)(z) as u16;
+        return (hi << 8) | lo;
+    }
+    z.pc = z.pc.wrapping_add(2);
+    return rw(z, z.pc.wrapping_sub(2));
 }
 
-pub fn get_bc(z: &mut z80) -> u16 {
+pub fn get_bc<U>(z: &mut z80<U>) -> u16 {
     //println!("get_bc");
 
     return ((z.b as u16) << 8) | (z.c as u16);
 }
 
-pub fn get_de(z: &mut z80) -> u16 {
+pub fn get_de<U>(z: &mut z80<U>) -> u16 {
     //println!("get_de");
 
     return ((z.d as u16) << 8) | (z.e as u16);
 }
 
-pub fn get_hl(z: &mut z80) -> u16 {
+pub fn get_hl<U>(z: &mut z80<U>) -> u16 {
     //println!("get_hl");
 
     return ((z.h as u16) << 8) | (z.l as u16);
 }
 
-pub fn set_bc(z: &mut z80, val: u16) {
+pub fn set_bc<U>(z: &mut z80<U>, val: u16) {
     //println!("set_bc");
 
     z.b = (val >> 8) as u8;
     z.c = (val & 0xFF) as u8;
 }
 
-pub fn set_de(z: &mut z80, val: u16) {
+pub fn set_de<U>(z: &mut z80<U>, val: u16) {
     //println!("set_de");
 
     z.d = (val >> 8) as u8;
     z.e = (val & 0xFF) as u8;
 }
 
-pub fn set_hl(z: &mut z80, val: u16) {
+pub fn set_hl<U>(z: &mut z80<U>, val: u16) {
     //println!("set_hl");
 
     z.h = (val >> 8) as u8;
     z.l = (val & 0xFF) as u8;
 }
 
-pub fn get_f(z: &mut z80) -> u8 {
+pub fn get_f<U>(z: &mut z80<U>) -> u8 {
     //println!("get_f");
 
-    let mut val: u8 = 0;
-    val |= z.cf << 0;
-    val |= z.nf << 1;
-    val |= z.pf << 2;
-    val |= z.xf << 3;
-    val |= z.hf << 4;
-    val |= z.yf << 5;
-    val |= z.zf << 6;
-    val |= z.sf << 7;
-
-    return val;
+    // the 8080 has no xf/yf/nf: bits 3 and 5 always read 0, bit 1 reads 1
+    if is_8080(z) {
+        return (z.f & !(FLAG_XY | FLAG_N)) | FLAG_N;
+    }
+    return z.f;
 }
 
-pub fn set_f(z: &mut z80, val: u8) {
+pub fn set_f<U>(z: &mut z80<U>, val: u8) {
     //println!("set_f");
 
-    z.cf = (val >> 0) & 1;
-    z.nf = (val >> 1) & 1;
-    z.pf = (val >> 2) & 1;
-    z.xf = (val >> 3) & 1;
-    z.hf = (val >> 4) & 1;
-    z.yf = (val >> 5) & 1;
-    z.zf = (val >> 6) & 1;
-    z.sf = (val >> 7) & 1;
+    z.f = val;
+    if is_8080(z) {
+        z.f &= !(FLAG_XY | FLAG_N);
+    }
 }
 
 // increments R, keeping the highest byte intact
-pub fn inc_r(z: &mut z80) {
+pub fn inc_r<U>(z: &mut z80<U>) {
     //println!("inc_r");
 
-    z.r = (z.r & 0x80) | ((z.r + 1) & 0x7f);
+    z.r = (z.r & 0x80) | (z.r.wrapping_add(1) & 0x7f);
 }
 
 // returns if there was a carry between bit "bit_no" and "bit_no - 1" when
@@ -353,7 +565,7 @@ pub fn parity(val: u8) -> bool {
 
 // MARK: opcodes
 // jumps to an address
-pub fn jump(z: &mut z80, addr: u16) {
+pub fn jump<U>(z: &mut z80<U>, addr: u16) {
     //println!("jump");
 
     z.pc = addr;
@@ -361,7 +573,7 @@ pub fn jump(z: &mut z80, addr: u16) {
 }
 
 // jumps to next word in memory if condition is true
-pub fn cond_jump(z: &mut z80, condition: bool) {
+pub fn cond_jump<U>(z: &mut z80<U>, condition: bool) {
     //println!("cond_jump");
 
     let addr: u16 = nextw(z);
@@ -372,7 +584,7 @@ pub fn cond_jump(z: &mut z80, condition: bool) {
 }
 
 // calls to next word in memory
-pub fn call(z: &mut z80, addr: u16) {
+pub fn call<U>(z: &mut z80<U>, addr: u16) {
     //println!("call");
 
     pushw(z, z.pc);
@@ -381,19 +593,19 @@ pub fn call(z: &mut z80, addr: u16) {
 }
 
 // calls to next word in memory if condition is true
-pub fn cond_call(z: &mut z80, condition: bool) {
+pub fn cond_call<U>(z: &mut z80<U>, condition: bool) {
     //println!("cond_call");
 
     let addr: u16 = nextw(z);
     if condition {
         call(z, addr);
-        z.cyc += 7;
+        z.cyc += if is_8080(z) { 6 } else { 7 };
     }
     z.mem_ptr = addr;
 }
 
 // returns from subroutine
-pub fn ret(z: &mut z80) {
+pub fn ret<U>(z: &mut z80<U>) {
     //println!("ret");
 
     z.pc = popw(z);
@@ -401,7 +613,7 @@ pub fn ret(z: &mut z80) {
 }
 
 // returns from subroutine if condition is true
-pub fn cond_ret(z: &mut z80, condition: bool) {
+pub fn cond_ret<U>(z: &mut z80<U>, condition: bool) {
     //println!("cond_ret");
 
     if condition {
@@ -410,7 +622,7 @@ pub fn cond_ret(z: &mut z80, condition: bool) {
     }
 }
 
-pub fn jr(z: &mut z80, displacement: i8) {
+pub fn jr<U>(z: &mut z80<U>, displacement: i8) {
     //println!("jr");
 
     z.pc = z.pc.wrapping_add(displacement as u16);
@@ -418,7 +630,7 @@ pub fn jr(z: &mut z80, displacement: i8) {
     z.mem_ptr = z.pc;
 }
 
-pub fn cond_jr(z: &mut z80, condition: bool) {
+pub fn cond_jr<U>(z: &mut z80<U>, condition: bool) {
     //println!("cond_jr");
 
     let b: i8 = nextb(z) as i8;
@@ -429,174 +641,148 @@ pub fn cond_jr(z: &mut z80, condition: bool) {
 }
 
 // ADD Byte: adds two bytes together
-pub fn addb(z: &mut z80, a: u8, b: u8, cy: u8) -> u8 {
+pub fn addb<U>(z: &mut z80<U>, a: u8, b: u8, cy: u8) -> u8 {
     //println!("addb");
 
-    let cy_ = cy & 0x01;
-
-    let result: u8 = (a as u16 + b as u16 + cy_ as u16) as u8;
+    let sum: u16 = a as u16 + b as u16 + (cy & 0x01) as u16;
+    let result: u8 = sum as u8;
 
-    z.sf = result >> 7;
-    if result == 0 {
-        z.zf = 1;
-    } else {
-        z.zf = 0;
-    }
-    z.hf = carry(4, a as u16, b as u16, cy_ as u16);
-    if z.hf > 0 {
-        z.hf = 1;
-    } else {
-        z.hf = 0;
-    }
-    if carry(7, a as u16, b as u16, cy_ as u16) != carry(8, a as u16, b as u16, cy_ as u16) {
-        z.pf = 1;
+    // the half-carry is the carry into bit 4, and there is an overflow if
+    // both operands have the same sign, different from the result's
+    let mut f: u8 = SZ[result as usize] | ((a ^ b ^ result) & FLAG_H) | (sum >> 8) as u8;
+    if is_8080(z) {
+        // the 8080 has no overflow flag: p is always the parity
+        f |= SZP[result as usize] & FLAG_P;
     } else {
-        z.pf = 0;
-    }
-    z.cf = carry(8, a as u16, b as u16, cy_ as u16);
-    if z.cf > 0 {
-        z.cf = 1;
-    } else {
-        z.cf = 0;
-    }
-    z.nf = 0;
-    z.xf = GET_BIT(3, result);
-    if z.xf > 0 {
-        z.xf = 1;
-    } else {
-        z.xf = 0;
-    }
-    z.yf = GET_BIT(5, result);
-    if z.yf > 0 {
-        z.yf = 1;
-    } else {
-        z.yf = 0;
+        f |= (((a ^ result) & (b ^ result)) >> 5) & FLAG_P;
     }
+    z.f = f;
 
     return result;
 }
 
 // SUBstract Byte: substracts two bytes (with optional carry)
-pub fn subb(z: &mut z80, a: u8, b: u8, cy: u8) -> u8 {
+pub fn subb<U>(z: &mut z80<U>, a: u8, b: u8, cy: u8) -> u8 {
     //println!("subb");
 
     let cy__ = !cy & 0x01;
 
     let val: u8 = addb(z, a, !b, cy__);
-    z.cf = !z.cf & 0x1;
-    z.hf = !z.hf & 0x1;
-    z.nf = 1;
+    // the 8080 keeps the auxiliary carry of the addition of the complement
+    if is_8080(z) {
+        z.f ^= FLAG_C;
+    } else {
+        z.f ^= FLAG_C | FLAG_H;
+    }
+    z.f |= FLAG_N;
     return val;
 }
 
 // ADD Word: adds two words together
-pub fn addw(z: &mut z80, a: u16, b: u16, cy: u8) -> u16 {
+pub fn addw<U>(z: &mut z80<U>, a: u16, b: u16, cy: u8) -> u16 {
     //println!("addw");
 
     let cy_ = cy & 0x01;
 
     let lsb: u8 = addb(z, a as u8, b as u8, cy_);
-    let msb: u8 = addb(z, (a >> 8) as u8, (b >> 8) as u8, z.cf);
+    let msb: u8 = addb(z, (a >> 8) as u8, (b >> 8) as u8, z.f & FLAG_C);
 
     let result: u16 = ((msb as u16) << 8) | lsb as u16;
+    z.f &= !FLAG_Z;
     if result == 0 {
-        z.zf = 1;
-    } else {
-        z.zf = 0;
+        z.f |= FLAG_Z;
     }
 
-    z.mem_ptr = a + 1;
+    z.mem_ptr = a.wrapping_add(1);
     return result;
 }
 
 // SUBstract Word: substracts two words (with optional carry)
-pub fn subw(z: &mut z80, a: u16, b: u16, cy: u8) -> u16 {
+pub fn subw<U>(z: &mut z80<U>, a: u16, b: u16, cy: u8) -> u16 {
     //println!("subw");
 
     let cy_ = cy & 0x01;
 
     let lsb: u8 = subb(z, a as u8, b as u8, cy_);
-    let msb: u8 = subb(z, (a >> 8) as u8, (b >> 8) as u8, z.cf);
+    let msb: u8 = subb(z, (a >> 8) as u8, (b >> 8) as u8, z.f & FLAG_C);
 
     let result: u16 = ((msb as u16) << 8) | lsb as u16;
+    z.f &= !FLAG_Z;
     if result == 0 {
-        z.zf = 1;
-    } else {
-        z.zf = 0;
+        z.f |= FLAG_Z;
     }
 
-    z.mem_ptr = a + 1;
+    z.mem_ptr = a.wrapping_add(1);
     return result;
 }
 
 // adds a word to HL
-pub fn addhl(z: &mut z80, val: u16) {
+pub fn addhl<U>(z: &mut z80<U>, val: u16) {
     //println!("addhl");
 
-    let sf: u8 = z.sf;
-    let zf: u8 = z.zf;
-    let pf: u8 = z.pf;
+    let szp: u8 = z.f & (FLAG_S | FLAG_Z | FLAG_P);
     let result: u16 = get_hl(z);
     let result: u16 = addw(z, result, val, 0);
     set_hl(z, result);
-    z.sf = sf;
-    z.zf = zf;
-    z.pf = pf;
+    z.f = (z.f & !(FLAG_S | FLAG_Z | FLAG_P)) | szp;
 }
 
 // adds a word to IX or IY
-pub fn addiz(z: &mut z80, reg: &mut u16, val: u16) {
+pub fn addiz<U>(z: &mut z80<U>, reg: &mut u16, val: u16) {
     //println!("addiz");
 
-    let sf: u8 = z.sf;
-    let zf: u8 = z.zf;
-    let pf: u8 = z.pf;
+    let szp: u8 = z.f & (FLAG_S | FLAG_Z | FLAG_P);
     let result: u16 = addw(z, *reg, val, 0);
     *reg = result;
-    z.sf = sf;
-    z.zf = zf;
-    z.pf = pf;
+    z.f = (z.f & !(FLAG_S | FLAG_Z | FLAG_P)) | szp;
 }
 
 // adds a word (+ carry) to HL
-pub fn adchl(z: &mut z80, val: u16) {
+pub fn adchl<U>(z: &mut z80<U>, val: u16) {
     //println!("adchl");
 
+    // sf and zf are those of the whole word, as set by addw
     let result: u16 = get_hl(z);
-    let result: u16 = addw(z, result, val, z.cf);
-    z.sf = (result >> 15) as u8;
-    z.zf = (result == 0) as u8;
+    let result: u16 = addw(z, result, val, z.f & FLAG_C);
     set_hl(z, result);
 }
 
 // substracts a word (+ carry) to HL
-pub fn sbchl(z: &mut z80, val: u16) {
+pub fn sbchl<U>(z: &mut z80<U>, val: u16) {
     //println!("sbchl");
 
     let result: u16 = get_hl(z);
-    let result: u16 = subw(z, result, val, z.cf);
-    z.sf = (result >> 15) as u8;
-    z.zf = (result == 0) as u8;
+    let result: u16 = subw(z, result, val, z.f & FLAG_C);
     set_hl(z, result);
 }
 
 // increments a byte value
-pub fn inc(z: &mut z80, a: u8) -> u8 {
+pub fn inc<U>(z: &mut z80<U>, a: u8) -> u8 {
     //println!("inc");
 
-    let cf: u8 = z.cf;
-    let result: u8 = addb(z, a, 1, 0);
-    z.cf = cf;
+    let result: u8 = a.wrapping_add(1);
+    if is_8080(z) {
+        z.f = (z.f & FLAG_C) | (SZHV_inc[result as usize] & !FLAG_P) | (SZP[result as usize] & FLAG_P);
+    } else {
+        z.f = (z.f & FLAG_C) | SZHV_inc[result as usize];
+    }
     return result;
 }
 
 // decrements a byte value
-pub fn dec(z: &mut z80, a: u8) -> u8 {
+pub fn dec<U>(z: &mut z80<U>, a: u8) -> u8 {
     //println!("dec");
 
-    let cf: u8 = z.cf;
-    let result: u8 = subb(z, a, 1, 0);
-    z.cf = cf;
+    let result: u8 = a.wrapping_sub(1);
+    if is_8080(z) {
+        // the auxiliary carry is that of the addition of the complement
+        z.f = (z.f & FLAG_C)
+            | (SZHV_dec[result as usize] & !(FLAG_H | FLAG_P))
+            | (!SZHV_dec[result as usize] & FLAG_H)
+            | (SZP[result as usize] & FLAG_P);
+    } else {
+        z.f = (z.f & FLAG_C) | SZHV_dec[result as usize];
+    }
     return result;
 }
 
@@ -604,84 +790,41 @@ pub fn dec(z: &mut z80, a: u8) -> u8 {
 
 // executes a logic "and" between register A and a byte, then stores the
 // result in register A
-pub fn land(z: &mut z80, val: u8) {
+pub fn land<U>(z: &mut z80<U>, val: u8) {
     //println!("land");
 
-    let result: u16 = (z.a & val) as u16;
-    z.sf = (result >> 7) as u8;
-    if result == 0 {
-        z.zf = 1;
-    } else {
-        z.zf = 0;
-    }
+    let result: u8 = z.a & val;
+    z.f = SZP[result as usize];
 
-    z.hf = 1;
-    if parity(result as u8) {
-        z.pf = 1;
+    // on the 8080, the auxiliary carry is the or of bits 3 of the operands
+    if is_8080(z) {
+        z.f |= ((z.a | val) << 1) & FLAG_H;
     } else {
-        z.pf = 0;
+        z.f |= FLAG_H;
     }
-
-    z.nf = 0;
-    z.cf = 0;
-    z.xf = GET_BIT(3, result as u8);
-    z.yf = GET_BIT(5, result as u8);
-    z.a = result as u8;
+    z.a = result;
 }
 
 // executes a logic "xor" between register A and a byte, then stores the
 // result in register A
-pub fn lxor(z: &mut z80, val: u8) {
+pub fn lxor<U>(z: &mut z80<U>, val: u8) {
     //println!("lxor");
 
-    let result: u8 = z.a ^ val;
-    z.sf = result >> 7;
-    if result == 0 {
-        z.zf = 1;
-    } else {
-        z.zf = 0;
-    }
-
-    z.hf = 0;
-    if parity(result as u8) {
-        z.pf = 1;
-    } else {
-        z.pf = 0;
-    }
-    z.nf = 0;
-    z.cf = 0;
-    z.xf = GET_BIT(3, result);
-    z.yf = GET_BIT(5, result);
-    z.a = result;
+    z.a ^= val;
+    z.f = SZP[z.a as usize];
 }
 
 // executes a logic "or" between register A and a byte, then stores the
 // result in register A
-pub fn lor(z: &mut z80, val: u8) {
+pub fn lor<U>(z: &mut z80<U>, val: u8) {
     //println!("lor");
 
-    let result: u8 = z.a | val;
-    z.sf = result >> 7;
-    if result == 0 {
-        z.zf = 1;
-    } else {
-        z.zf = 0;
-    }
-    z.hf = 0;
-    if parity(result as u8) {
-        z.pf = 1;
-    } else {
-        z.pf = 0;
-    }
-    z.nf = 0;
-    z.cf = 0;
-    z.xf = GET_BIT(3, result);
-    z.yf = GET_BIT(5, result);
-    z.a = result;
+    z.a |= val;
+    z.f = SZP[z.a as usize];
 }
 
 // compares a value with register A
-pub fn cp(z: &mut z80, val: u8) {
+pub fn cp<U>(z: &mut z80<U>, val: u8) {
     //println!("cp");
 
     subb(z, z.a, val, 0);
@@ -689,230 +832,100 @@ pub fn cp(z: &mut z80, val: u8) {
     // the only difference between cp and sub is that
     // the xf/yf are taken from the value to be substracted,
     // not the result
-    z.yf = GET_BIT(5, val);
-    z.xf = GET_BIT(3, val);
+    z.f = (z.f & !FLAG_XY) | (val & FLAG_XY);
 }
 
 // 0xCB opcodes
 // rotate left with carry
-pub fn cb_rlc(z: &mut z80, val: u8) -> u8 {
+pub fn cb_rlc<U>(z: &mut z80<U>, val: u8) -> u8 {
     //println!("cb_rlc");
 
     let old: u8 = val >> 7;
     let val = (val << 1) | old;
-    z.sf = val >> 7;
-    if val == 0 {
-        z.zf = 1;
-    } else {
-        z.zf = 0;
-    }
-    if parity(val) {
-        z.pf = 1;
-    } else {
-        z.pf = 0;
-    }
-    z.nf = 0;
-    z.hf = 0;
-    z.cf = old;
-    z.xf = GET_BIT(3, val);
-    z.yf = GET_BIT(5, val);
+    z.f = SZP[val as usize] | old;
     return val;
 }
 
 // rotate right with carry
-pub fn cb_rrc(z: &mut z80, val: u8) -> u8 {
+pub fn cb_rrc<U>(z: &mut z80<U>, val: u8) -> u8 {
     //println!("cb_rrc");
 
     let old: u8 = val & 1;
     let val = (val >> 1) | (old << 7);
-    z.sf = val >> 7;
-    if val == 0 {
-        z.zf = 1;
-    } else {
-        z.zf = 0;
-    }
-    z.nf = 0;
-    z.hf = 0;
-    z.cf = old;
-    if parity(val) {
-        z.pf = 1;
-    } else {
-        z.pf = 0;
-    }
-    z.xf = GET_BIT(3, val);
-    z.yf = GET_BIT(5, val);
+    z.f = SZP[val as usize] | old;
     return val;
 }
 
 // rotate left (simple)
-pub fn cb_rl(z: &mut z80, val: u8) -> u8 {
+pub fn cb_rl<U>(z: &mut z80<U>, val: u8) -> u8 {
     //println!("cb_rl");
 
-    let cf: u8 = z.cf;
-    z.cf = val >> 7;
-    let val = (val << 1) | cf;
-    z.sf = val >> 7;
-    if val == 0 {
-        z.zf = 1;
-    } else {
-        z.zf = 0;
-    }
-    z.nf = 0;
-    z.hf = 0;
-    if parity(val) {
-        z.pf = 1;
-    } else {
-        z.pf = 0;
-    }
-    z.xf = GET_BIT(3, val);
-    z.yf = GET_BIT(5, val);
-    return val;
+    let cf: u8 = z.f & FLAG_C;
+    let val_ = (val << 1) | cf;
+    z.f = SZP[val_ as usize] | (val >> 7);
+    return val_;
 }
 
 // rotate right (simple)
-pub fn cb_rr(z: &mut z80, val: u8) -> u8 {
+pub fn cb_rr<U>(z: &mut z80<U>, val: u8) -> u8 {
     //println!("cb_rr");
 
-    let c: u8 = z.cf;
-    z.cf = val & 1;
-    let val = (val >> 1) | (c << 7);
-    z.sf = val >> 7;
-    if val == 0 {
-        z.zf = 1;
-    } else {
-        z.zf = 0;
-    }
-    z.nf = 0;
-    z.hf = 0;
-    if parity(val) {
-        z.pf = 1;
-    } else {
-        z.pf = 0;
-    }
-    z.xf = GET_BIT(3, val);
-    z.yf = GET_BIT(5, val);
-    return val;
+    let c: u8 = z.f & FLAG_C;
+    let val_ = (val >> 1) | (c << 7);
+    z.f = SZP[val_ as usize] | (val & 1);
+    return val_;
 }
 
 // shift left preserving sign
-pub fn cb_sla(z: &mut z80, val: u8) -> u8 {
+pub fn cb_sla<U>(z: &mut z80<U>, val: u8) -> u8 {
     //println!("cb_sla");
 
-    z.cf = val >> 7;
-    let val = val << 1;
-    z.sf = val >> 7;
-    if val == 0 {
-        z.zf = 1;
-    } else {
-        z.zf = 0;
-    }
-    z.nf = 0;
-    z.hf = 0;
-    if parity(val) {
-        z.pf = 1;
-    } else {
-        z.pf = 0;
-    }
-    z.xf = GET_BIT(3, val);
-    z.yf = GET_BIT(5, val);
-    return val;
+    let val_ = val << 1;
+    z.f = SZP[val_ as usize] | (val >> 7);
+    return val_;
 }
 
 // SLL (exactly like SLA, but sets the first bit to 1)
-pub fn cb_sll(z: &mut z80, val: u8) -> u8 {
+pub fn cb_sll<U>(z: &mut z80<U>, val: u8) -> u8 {
     //println!("cb_sll");
 
-    z.cf = val >> 7;
-    let mut val = val << 1;
-    val |= 1;
-    z.sf = val >> 7;
-    if val == 0 {
-        z.zf = 1;
-    } else {
-        z.zf = 0;
-    }
-    z.nf = 0;
-    z.hf = 0;
-    if parity(val) {
-        z.pf = 1;
-    } else {
-        z.pf = 0;
-    }
-    z.xf = GET_BIT(3, val);
-    z.yf = GET_BIT(5, val);
-    return val;
+    let val_ = (val << 1) | 1;
+    z.f = SZP[val_ as usize] | (val >> 7);
+    return val_;
 }
 
 // shift right preserving sign
-pub fn cb_sra(z: &mut z80, val: u8) -> u8 {
+pub fn cb_sra<U>(z: &mut z80<U>, val: u8) -> u8 {
     //println!("cb_sra");
 
-    z.cf = val & 1;
-    let val = (val >> 1) | (val & 0x80); // 0b10000000
-    z.sf = val >> 7;
-    if val == 0 {
-        z.zf = 1;
-    } else {
-        z.zf = 0;
-    }
-    z.nf = 0;
-    z.hf = 0;
-    if parity(val) {
-        z.pf = 1;
-    } else {
-        z.pf = 0;
-    }
-    z.xf = GET_BIT(3, val);
-    z.yf = GET_BIT(5, val);
-    return val;
+    let val_ = (val >> 1) | (val & 0x80); // 0b10000000
+    z.f = SZP[val_ as usize] | (val & 1);
+    return val_;
 }
 
 // shift register right
-pub fn cb_srl(z: &mut z80, val: u8) -> u8 {
+pub fn cb_srl<U>(z: &mut z80<U>, val: u8) -> u8 {
     //println!("cb_srl");
 
-    z.cf = val & 1;
-    let val = val >> 1;
-    z.sf = val >> 7;
-    if val == 0 {
-        z.zf = 1;
-    } else {
-        z.zf = 0;
-    }
-
-    z.nf = 0;
-    z.hf = 0;
-    if parity(val) {
-        z.pf = 1;
-    } else {
-        z.pf = 0;
-    }
-    z.xf = GET_BIT(3, val);
-    z.yf = GET_BIT(5, val);
-    return val;
+    let val_ = val >> 1;
+    z.f = SZP[val_ as usize] | (val & 1);
+    return val_;
 }
 
 // tests bit "n" from a byte
-pub fn cb_bit(z: &mut z80, val: u8, n: u8) -> u8 {
+pub fn cb_bit<U>(z: &mut z80<U>, val: u8, n: u8) -> u8 {
     //println!("cb_bit");
 
+    // pf is set as zf, xf and yf come from the value tested
     let result: u8 = val & (1 << n);
-    z.sf = result >> 7;
+    z.f = (z.f & FLAG_C) | FLAG_H | (result & FLAG_S) | (val & FLAG_XY);
     if result == 0 {
-        z.zf = 1;
-    } else {
-        z.zf = 0;
+        z.f |= FLAG_Z | FLAG_P;
     }
-
-    z.yf = GET_BIT(5, val);
-    z.hf = 1;
-    z.xf = GET_BIT(3, val);
-    z.pf = z.zf;
-    z.nf = 0;
     return result;
 }
 
-pub fn ldi(z: &mut z80) {
+pub fn ldi<U>(z: &mut z80<U>) {
     //println!("ldi");
 
     let de: u16 = get_de(z);
@@ -921,150 +934,188 @@ pub fn ldi(z: &mut z80) {
 
     wb(z, de, val);
 
-    let result = get_hl(z);
-    set_hl(z, result + 1);
-    let result = get_de(z);
-    set_de(z, result + 1);
-    let result = get_bc(z);
-    set_bc(z, result - 1);
+    set_hl(z, hl.wrapping_add(1));
+    set_de(z, de.wrapping_add(1));
+    let bc = get_bc(z).wrapping_sub(1);
+    set_bc(z, bc);
 
     // see https://wikiti.brandonw.net/index.php?title=Z80_Instruction_Set
     // for the calculation of xf/yf on LDI
-    let result: u16 = val.wrapping_add(z.a) as u16;
-    z.xf = GET_BIT(3, result as u8);
-    z.yf = GET_BIT(1, result as u8);
-
-    z.nf = 0;
-    z.hf = 0;
-    if get_bc(z) > 0 {
-        z.pf = 1;
-    } else {
-        z.pf = 0;
+    let n: u8 = val.wrapping_add(z.a);
+    z.f = (z.f & (FLAG_S | FLAG_Z | FLAG_C)) | (n & FLAG_X) | ((n << 4) & FLAG_Y);
+    if bc != 0 {
+        z.f |= FLAG_P;
     }
 }
 
-pub fn ldd(z: &mut z80) {
+pub fn ldd<U>(z: &mut z80<U>) {
     //println!("ldd");
 
     ldi(z);
     // same as ldi but HL and DE are decremented instead of incremented
     let result = get_hl(z);
-    set_hl(z, result - 2);
+    set_hl(z, result.wrapping_sub(2));
     let result = get_de(z);
-    set_de(z, result - 2);
+    set_de(z, result.wrapping_sub(2));
 }
 
-pub fn cpi(z: &mut z80) {
+pub fn cpi<U>(z: &mut z80<U>) {
     //println!("cpi");
 
-    let cf: u8 = z.cf;
-    let result = get_hl(z);
-    let result = rb(z, result);
-    let result: u16 = subb(z, z.a, result, 0) as u16;
-    let temp = get_hl(z);
-    set_hl(z, temp + 1);
-    let temp = get_bc(z);
-    set_bc(z, temp - 1);
-    z.xf = GET_BIT(3, result as u8 - z.hf);
-    z.yf = GET_BIT(1, result as u8 - z.hf);
-    if get_bc(z) != 0 {
-        z.pf = 1;
-    } else {
-        z.pf = 0;
+    let cf: u8 = z.f & FLAG_C;
+    let hl = get_hl(z);
+    let val = rb(z, hl);
+    let result: u8 = subb(z, z.a, val, 0);
+    set_hl(z, hl.wrapping_add(1));
+    let bc = get_bc(z).wrapping_sub(1);
+    set_bc(z, bc);
+
+    // xf and yf come from the result minus the half-carry
+    let n: u8 = result.wrapping_sub((z.f & FLAG_H) >> 4);
+    z.f = (z.f & (FLAG_S | FLAG_Z | FLAG_H | FLAG_N)) | (n & FLAG_X) | ((n << 4) & FLAG_Y) | cf;
+    if bc != 0 {
+        z.f |= FLAG_P;
     }
 
-    z.cf = cf;
-    z.mem_ptr += 1;
+    z.mem_ptr = z.mem_ptr.wrapping_add(1);
 }
 
-pub fn cpd(z: &mut z80) {
+pub fn cpd<U>(z: &mut z80<U>) {
     //println!("cpd");
 
     cpi(z);
     // same as cpi but HL is decremented instead of incremented
     let result = get_hl(z);
-    set_hl(z, result - 2);
-    z.mem_ptr -= 2;
+    set_hl(z, result.wrapping_sub(2));
+    z.mem_ptr = z.mem_ptr.wrapping_sub(2);
 }
 
-pub fn in_r_c(z: &mut z80, r: &mut u8) {
+pub fn in_r_c<U>(z: &mut z80<U>, r: &mut u8) {
     //println!("in_r_c");
 
-    *r = (z.port_in)(z, z.c);
-    if *r == 0 {
-        z.zf = 1;
-    } else {
-        z.zf = 0;
-    }
+    *r = port_rb(z, z.c);
+    z.f = (z.f & (FLAG_C | FLAG_XY)) | (SZP[*r as usize] & !FLAG_XY);
+}
 
-    z.sf = *r >> 7;
-    if parity(*r) {
-        z.pf = 1;
-    } else {
-        z.pf = 0;
-    }
+// flags of ini/ind/outi/outd, from the byte transferred "val" and the sum "k"
+// the carry comes from. See "The Undocumented Z80 Documented".
+fn block_io_flags<U>(z: &mut z80<U>, val: u8, k: u16) {
+    //println!("block_io_flags");
 
-    z.nf = 0;
-    z.hf = 0;
+    z.f = SZ[z.b as usize] | ((val >> 6) & FLAG_N);
+    if k > 0xff {
+        z.f |= FLAG_H | FLAG_C;
+    }
+    z.f |= SZP[((k & 7) as u8 ^ z.b) as usize] & FLAG_P;
 }
 
-pub fn ini(z: &mut z80) {
+// returns the byte read
+pub fn ini<U>(z: &mut z80<U>) -> u8 {
     //println!("ini");
 
-    let val: u8 = (z.port_in)(z, z.c);
+    z.mem_ptr = get_bc(z).wrapping_add(1);
+    let val: u8 = port_rb(z, z.c);
     let result = get_hl(z);
     wb(z, result, val);
     let result = get_hl(z);
-    set_hl(z, result + 1);
-    z.b -= 1;
-    if z.b == 0 {
-        z.zf = 1;
-    } else {
-        z.zf = 0;
-    }
+    set_hl(z, result.wrapping_add(1));
+    z.b = z.b.wrapping_sub(1);
 
-    z.nf = 1;
-    z.mem_ptr = get_bc(z) + 1;
+    let k: u16 = val as u16 + z.c.wrapping_add(1) as u16;
+    block_io_flags(z, val, k);
+    return val;
 }
 
-pub fn ind(z: &mut z80) {
+// returns the byte read
+pub fn ind<U>(z: &mut z80<U>) -> u8 {
     //println!("ind");
 
-    ini(z);
-    let temp = get_hl(z);
-    set_hl(z, temp - 2);
-    z.mem_ptr = get_bc(z) - 2;
+    z.mem_ptr = get_bc(z).wrapping_sub(1);
+    let val: u8 = port_rb(z, z.c);
+    let result = get_hl(z);
+    wb(z, result, val);
+    let result = get_hl(z);
+    set_hl(z, result.wrapping_sub(1));
+    z.b = z.b.wrapping_sub(1);
+
+    let k: u16 = val as u16 + z.c.wrapping_sub(1) as u16;
+    block_io_flags(z, val, k);
+    return val;
 }
 
-pub fn outi(z: &mut z80) {
+// returns the byte written
+pub fn outi<U>(z: &mut z80<U>) -> u8 {
     //println!("outi");
 
     let temp = get_hl(z);
-    let temp = rb(z, temp);
-    (z.port_out)(z, z.c, temp);
-    let temp = get_hl(z) + 1;
+    let val = rb(z, temp);
+    z.b = z.b.wrapping_sub(1);
+    port_wb(z, z.c, val);
+    let temp = get_hl(z).wrapping_add(1);
     set_hl(z, temp);
-    z.b -= 1;
-    if z.b == 0 {
-        z.zf = 1;
-    } else {
-        z.zf = 0;
-    }
+    z.mem_ptr = get_bc(z).wrapping_add(1);
 
-    z.nf = 1;
-    z.mem_ptr = get_bc(z) + 1;
+    let k: u16 = val as u16 + z.l as u16;
+    block_io_flags(z, val, k);
+    return val;
 }
 
-pub fn outd(z: &mut z80) {
+// returns the byte written
+pub fn outd<U>(z: &mut z80<U>) -> u8 {
     //println!("outd");
 
-    outi(z);
     let temp = get_hl(z);
-    set_hl(z, temp - 2);
-    z.mem_ptr = get_bc(z) - 2;
+    let val = rb(z, temp);
+    z.b = z.b.wrapping_sub(1);
+    port_wb(z, z.c, val);
+    let temp = get_hl(z).wrapping_sub(1);
+    set_hl(z, temp);
+    z.mem_ptr = get_bc(z).wrapping_sub(1);
+
+    let k: u16 = val as u16 + z.l as u16;
+    block_io_flags(z, val, k);
+    return val;
+}
+
+// a repeating block instruction that is not over goes back to itself, and an
+// interrupt can be taken before it runs again. The undocumented flags then
+// come from the high byte of pc. See David Banks' and Peter Helcmanovsky's
+// research on interrupted block instructions.
+fn block_repeat<U>(z: &mut z80<U>) {
+    //println!("block_repeat");
+
+    z.pc = z.pc.wrapping_sub(2);
+    z.cyc += 5;
+    z.mem_ptr = z.pc.wrapping_add(1);
+    z.f = (z.f & !FLAG_XY) | ((z.pc >> 8) as u8 & FLAG_XY);
+}
+
+// inir/indr/otir/otdr also change pf and hf when they repeat
+fn block_io_repeat<U>(z: &mut z80<U>, val: u8) {
+    //println!("block_io_repeat");
+
+    block_repeat(z);
+    let mut p: u8 = z.b & 7;
+    if z.f & FLAG_C != 0 {
+        z.f &= !FLAG_H;
+        if val & 0x80 != 0 {
+            p = z.b.wrapping_sub(1) & 7;
+            if (z.b & 0x0f) == 0x00 {
+                z.f |= FLAG_H;
+            }
+        } else {
+            p = z.b.wrapping_add(1) & 7;
+            if (z.b & 0x0f) == 0x0f {
+                z.f |= FLAG_H;
+            }
+        }
+    }
+    if !parity(p) {
+        z.f ^= FLAG_P;
+    }
 }
 
-pub fn daa(z: &mut z80) {
+pub fn daa<U>(z: &mut z80<U>) {
     //println!("daa");
 
     // "When this instruction is executed, the A register is BCD corrected
@@ -1076,53 +1127,32 @@ pub fn daa(z: &mut z80) {
     // than 9 or the C flag is set, then $60 is added."
     // > http://z80-heaven.wikidot.com/instructions-set:daa
     let mut correction: u8 = 0;
+    let mut cf: u8 = z.f & FLAG_C;
+    let hf: u8;
 
-    if (z.a & 0x0F) > 0x09 || z.hf != 0 {
+    if (z.a & 0x0F) > 0x09 || z.f & FLAG_H != 0 {
         correction += 0x06;
     }
 
-    if z.a > 0x99 || z.cf != 0 {
+    if z.a > 0x99 || cf != 0 {
         correction += 0x60;
-        z.cf = 1;
+        cf = FLAG_C;
     }
 
-    let substraction: u8 = z.nf;
-    if substraction != 0 {
-        if z.hf != 0 && (z.a & 0x0F) < 0x06 {
-            z.hf = 1;
-        } else {
-            z.hf = 0;
-        }
-
+    // the 8080 only adjusts after an addition
+    let substraction: bool = !is_8080(z) && z.f & FLAG_N != 0;
+    if substraction {
+        hf = if z.f & FLAG_H != 0 && (z.a & 0x0F) < 0x06 { FLAG_H } else { 0 };
         z.a = z.a.wrapping_sub(correction);
     } else {
-        if (z.a & 0x0F) > 0x09 {
-            z.hf = 1;
-        } else {
-            z.hf = 0;
-        }
-
+        hf = if (z.a & 0x0F) > 0x09 { FLAG_H } else { 0 };
         z.a = z.a.wrapping_add(correction);
     }
 
-    z.sf = z.a >> 7;
-    if z.a == 0 {
-        z.zf = 1;
-    } else {
-        z.zf = 0;
-    }
-
-    if parity(z.a) {
-        z.pf = 1;
-    } else {
-        z.pf = 0;
-    }
-
-    z.xf = GET_BIT(3, z.a);
-    z.yf = GET_BIT(5, z.a);
+    z.f = SZP[z.a as usize] | hf | (z.f & FLAG_N) | cf;
 }
 
-pub fn displace(z: &mut z80, base_addr: u16, displacement: i8) -> u16 {
+pub fn displace<U>(z: &mut z80<U>, base_addr: u16, displacement: i8) -> u16 {
     //println!("displace");
 
     let addr: u16 = base_addr.wrapping_add(displacement as u16);
@@ -1130,7 +1160,7 @@ pub fn displace(z: &mut z80, base_addr: u16, displacement: i8) -> u16 {
     return addr;
 }
 
-pub fn process_interrupts(z: &mut z80) {
+pub fn process_interrupts<U>(z: &mut z80<U>) {
     //println!("process_interrupts");
 
     // "When an EI instruction is executed, any pending interrupt request
@@ -1155,31 +1185,43 @@ pub fn process_interrupts(z: &mut z80) {
         return;
     }
 
-    if z.int_pending && z.iff1 {
-        z.int_pending = false;
+    // the int line is level triggered: it stays asserted until the board
+    // clears it, and is taken again once interrupts are enabled if it was not
+    if z.irq_line && z.iff1 {
         z.halted = false;
         z.iff1 = false;
         z.iff2 = false;
-        inc_r(z);
 
         match z.interrupt_mode {
             0 => {
-                z.cyc += 11;
-                exec_opcode(z, z.int_data);
+                // the acknowledge adds 2 wait states to the instruction read
+                // from the bus (none on the 8080)
+                if !is_8080(z) {
+                    z.cyc += 2;
+                }
+                z.int_ack_fetch = true;
+                let opcode = (z.int_ack)(z);
+                exec_opcode(z, opcode);
+                z.int_ack_fetch = false;
             }
 
             1 => {
+                inc_r(z);
                 z.cyc += 13;
+                (z.int_ack)(z);
                 call(z, 0x38);
             }
 
             2 => {
+                inc_r(z);
                 z.cyc += 19;
-                let temp = rw(z, ((z.i as u16) << 8) | z.int_data as u16);
+                let vector = (z.int_ack)(z);
+                let temp = rw(z, ((z.i as u16) << 8) | vector as u16);
                 call(z, temp);
             }
 
             _ => {
+                #[cfg(feature = "std")]
                 println!("unsupported interrupt mode {}", z.interrupt_mode);
             }
         }
@@ -1189,9 +1231,9 @@ pub fn process_interrupts(z: &mut z80) {
 }
 
 // MARK: interface
-// initialises a z80 struct. Note that read_byte, write_byte, port_in, port_out
-// and userdata must be manually set by the user afterwards.
-pub fn z80_init(z: &mut z80) {
+// initialises a z80 struct. Note that read_byte, write_byte, port_in and
+// port_out must be manually set by the user afterwards.
+pub fn z80_init<U>(z: &mut z80<U>) {
     //println!("z80_init");
 
     //z.read_byte = NULL;
@@ -1230,33 +1272,39 @@ pub fn z80_init(z: &mut z80) {
     z.i = 0;
     z.r = 0;
 
-    z.sf = 1;
-    z.zf = 1;
-    z.yf = 1;
-    z.hf = 1;
-    z.xf = 1;
-    z.pf = 1;
-    z.nf = 1;
-    z.cf = 1;
+    z.f = 0xFF;
+    z.q = 0;
 
     z.iff_delay = 0;
     z.interrupt_mode = 0;
     z.iff1 = false;
     z.iff2 = false;
     z.halted = false;
-    z.int_pending = false;
+    z.irq_line = false;
+    z.int_ack_fetch = false;
     z.nmi_pending = false;
-    z.int_data = 0;
+    z.run_extra = 0;
+    z.run_stop = false;
 }
 
 // executes the next instruction in memory + handles interrupts
-pub fn z80_step(z: &mut z80) {
+pub fn z80_step<U>(z: &mut z80<U>) {
     //println!("z80_step");
 
+    #[cfg(feature = "trace")]
+    if let Some(trace) = z.trace {
+        let stopped: bool = z.run_stop;
+        trace(z);
+        // the hook ended z80_run before the instruction (a breakpoint)
+        if z.run_stop && !stopped {
+            return;
+        }
+    }
+
     if z.halted {
         exec_opcode(z, 0x00);
     } else {
-        let opcode: u8 = nextb(z);
+        let opcode: u8 = nextop(z);
         //println!("BUG z80_step opcode:{}", opcode);
         exec_opcode(z, opcode);
     }
@@ -1264,8 +1312,45 @@ pub fn z80_step(z: &mut z80) {
     process_interrupts(z);
 }
 
+// executes instructions for "cycles" cycles, less what the previous call ran
+// past its own budget. Returns the number of cycles executed, which can be
+// less if the board called z80_stop_run.
+pub fn z80_run<U>(z: &mut z80<U>, cycles: u64) -> u64 {
+    //println!("z80_run");
+
+    let start: u64 = z.cyc;
+    let budget: u64 = cycles.saturating_sub(z.run_extra);
+    z.run_stop = false;
+
+    while z.cyc - start < budget && !z.run_stop {
+        if z.halted && z.iff_delay == 0 && !z.nmi_pending && !(z.irq_line && z.iff1) {
+            // nothing can end the halt before the budget is spent: the nops
+            // it executes are skipped at once
+            let nops: u64 = (budget - (z.cyc - start) + 3) / 4;
+            z.cyc += nops * 4;
+            z.r = (z.r & 0x80) | ((z.r as u64 + nops) & 0x7f) as u8;
+            z.q = 0;
+            break;
+        }
+        z80_step(z);
+    }
+
+    let executed: u64 = z.cyc - start;
+    z.run_extra = (z.run_extra + executed).saturating_sub(cycles);
+    return executed;
+}
+
+// ends z80_run after the current instruction, for the boards that need to
+// act at a precise time (from a memory or io callback)
+pub fn z80_stop_run<U>(z: &mut z80<U>) {
+    //println!("z80_stop_run");
+
+    z.run_stop = true;
+}
+
 // outputs to stdout a debug trace of the emulator
-pub fn z80_debug_output(z: &mut z80) {
+#[cfg(all(feature = "trace", feature = "std"))]
+pub fn z80_debug_output<U>(z: &mut z80<U>) {
     //println!("z80_debug_output");
 
     let temp1 = get_f(z);
@@ -1276,37 +1361,199 @@ pub fn z80_debug_output(z: &mut z80) {
         z.pc, ((z.a as u16) << 8) | temp1 as u16, temp2, temp3, temp4, z.sp,
         z.ix, z.iy, z.i, z.r);
 
+    // read without wait states: tracing takes no cpu time
+    let mut bytes: [u8; 4] = [0; 4];
+    for i in 0..4 {
+        bytes[i] = (z.read_byte)(&mut z.userdata, z.pc.wrapping_add(i as u16));
+    }
     print!(
-        "\t({:02X} {:02X} {:02X} {:02X}), cyc: {}\n",
-        rb(z, z.pc),
-        rb(z, z.pc + 1),
-        rb(z, z.pc + 2),
-        rb(z, z.pc + 3),
-        z.cyc
+        "\t({:02X} {:02X} {:02X} {:02X}), cyc: {}",
+        bytes[0], bytes[1], bytes[2], bytes[3], z.cyc
     );
+
+    #[cfg(feature = "disasm")]
+    {
+        let (text, _) = z80_disasm(&mut |addr| (z.read_byte)(&mut z.userdata, addr), z.pc);
+        print!("\t{}", text);
+    }
+    print!("\n");
 }
 
 // function to call when an NMI is to be serviced
-pub fn z80_gen_nmi(z: &mut z80) {
+pub fn z80_gen_nmi<U>(z: &mut z80<U>) {
     //println!("z80_gen_nmi");
 
     z.nmi_pending = true;
 }
 
-// function to call when an INT is to be serviced
-pub fn z80_gen_int(z: &mut z80, data: u8) {
-    //println!("z80_gen_int");
+// asserts the INT line. The interrupt is taken as soon as the cpu accepts
+// it, and again after each ei until the line is cleared.
+pub fn z80_assert_irq<U>(z: &mut z80<U>) {
+    //println!("z80_assert_irq");
 
-    z.int_pending = true;
-    z.int_data = data;
+    z.irq_line = true;
+}
+
+// clears the INT line
+pub fn z80_clear_irq<U>(z: &mut z80<U>) {
+    //println!("z80_clear_irq");
+
+    z.irq_line = false;
+}
+
+// MARK: state
+
+// size of a saved state: the registers, the interrupt state and the cycle
+// count. The callbacks and the userdata belong to the board, not saved.
+#[cfg(feature = "state")]
+pub const Z80_STATE_SIZE: usize = 49;
+#[cfg(feature = "state")]
+const Z80_STATE_VERSION: u8 = 1;
+
+#[cfg(feature = "state")]
+#[derive(Debug, PartialEq)]
+pub enum state_error {
+    // saved by another version of the core
+    BadVersion(u8),
+}
+
+// saves the state of the cpu, for z80_load_state
+#[cfg(feature = "state")]
+pub fn z80_save_state<U>(z: &z80<U>, buf: &mut [u8; Z80_STATE_SIZE]) {
+    //println!("z80_save_state");
+
+    let words: [u16; 5] = [z.pc, z.sp, z.ix, z.iy, z.mem_ptr];
+    let bytes: [u8; 21] = [
+        z.a, z.b, z.c, z.d, z.e, z.h, z.l, z.f, z.a_, z.b_, z.c_, z.d_, z.e_, z.h_, z.l_, z.f_,
+        z.i, z.r, z.q, z.iff_delay, z.interrupt_mode,
+    ];
+    let bits: [bool; 7] = [
+        z.iff1,
+        z.iff2,
+        z.halted,
+        z.irq_line,
+        z.int_ack_fetch,
+        z.nmi_pending,
+        is_8080(z),
+    ];
+
+    buf[0] = Z80_STATE_VERSION;
+    for i in 0..words.len() {
+        buf[1 + i * 2..3 + i * 2].copy_from_slice(&words[i].to_le_bytes());
+    }
+    buf[11..32].copy_from_slice(&bytes);
+    buf[32] = 0;
+    for i in 0..bits.len() {
+        buf[32] |= (bits[i] as u8) << i;
+    }
+    buf[33..41].copy_from_slice(&z.cyc.to_le_bytes());
+    buf[41..49].copy_from_slice(&z.run_extra.to_le_bytes());
+}
+
+// restores a state saved by z80_save_state. The 8080 mode is restored only
+// with the "i8080" feature.
+#[cfg(feature = "state")]
+pub fn z80_load_state<U>(z: &mut z80<U>, buf: &[u8; Z80_STATE_SIZE]) -> Result<(), state_error> {
+    //println!("z80_load_state");
+
+    if buf[0] != Z80_STATE_VERSION {
+        return Err(state_error::BadVersion(buf[0]));
+    }
+
+    let word = |i: usize| u16::from_le_bytes([buf[1 + i * 2], buf[2 + i * 2]]);
+    z.pc = word(0);
+    z.sp = word(1);
+    z.ix = word(2);
+    z.iy = word(3);
+    z.mem_ptr = word(4);
+
+    let regs: [&mut u8; 21] = [
+        &mut z.a, &mut z.b, &mut z.c, &mut z.d, &mut z.e, &mut z.h, &mut z.l, &mut z.f,
+        &mut z.a_, &mut z.b_, &mut z.c_, &mut z.d_, &mut z.e_, &mut z.h_, &mut z.l_, &mut z.f_,
+        &mut z.i, &mut z.r, &mut z.q, &mut z.iff_delay, &mut z.interrupt_mode,
+    ];
+    for i in 0..regs.len() {
+        *regs[i] = buf[11 + i];
+    }
+
+    let bit = |i: usize| (buf[32] >> i) & 1 != 0;
+    z.iff1 = bit(0);
+    z.iff2 = bit(1);
+    z.halted = bit(2);
+    z.irq_line = bit(3);
+    z.int_ack_fetch = bit(4);
+    z.nmi_pending = bit(5);
+    #[cfg(feature = "i8080")]
+    {
+        z.i8080 = bit(6);
+    }
+
+    let mut cyc: [u8; 8] = [0; 8];
+    cyc.copy_from_slice(&buf[33..41]);
+    z.cyc = u64::from_le_bytes(cyc);
+    cyc.copy_from_slice(&buf[41..49]);
+    z.run_extra = u64::from_le_bytes(cyc);
+    z.run_stop = false;
+    return Ok(());
+}
+
+// in 8080 mode, the z80 opcode an 8080 opcode runs as. The z80 prefixes and
+// relative jumps are undocumented aliases of nop, jmp, call and ret there.
+pub fn opcode_8080(opcode: u8) -> u8 {
+    return match opcode {
+        0x08 | 0x10 | 0x18 | 0x20 | 0x28 | 0x30 | 0x38 => 0x00, // nop
+        0xCB => 0xC3,                                           // jmp
+        0xD9 => 0xC9,                                           // ret
+        0xDD | 0xED | 0xFD => 0xCD,                             // call
+        _ => opcode,
+    };
+}
+
+// non-prefixed opcodes that set the flags: inc/dec r, rotates of a, add hl,
+// daa, cpl, scf, ccf and the alu operations. "q" is kept for those.
+fn sets_flags(opcode: u8) -> bool {
+    return (opcode & 0xC6) == 0x04
+        || (opcode & 0xC7) == 0x07
+        || (opcode & 0xCF) == 0x09
+        || (opcode & 0xC0) == 0x80
+        || (opcode & 0xC7) == 0xC6;
+}
+
+// ED opcodes that set the flags: in r,(c), sbc/adc hl, neg, ld a,i, ld a,r,
+// rrd, rld and the block instructions
+fn sets_flags_ed(opcode: u8) -> bool {
+    return (opcode & 0xC7) == 0x40
+        || (opcode & 0xC7) == 0x42
+        || (opcode & 0xC7) == 0x44
+        || opcode == 0x57
+        || opcode == 0x5F
+        || opcode == 0x67
+        || opcode == 0x6F
+        || (opcode & 0xE4) == 0xA0;
+}
+
+// updates q after a non-prefixed or DD/FD opcode. The prefixes set it
+// once their opcode has run.
+fn set_q<U>(z: &mut z80<U>, opcode: u8) {
+    match opcode {
+        0xCB | 0xDD | 0xED | 0xFD => {}
+        _ => z.q = if sets_flags(opcode) { get_f(z) } else { 0 },
+    }
 }
 
 // executes a non-prefixed opcode
-pub fn exec_opcode(z: &mut z80, opcode: u8) {
+pub fn exec_opcode<U>(z: &mut z80<U>, opcode: u8) {
     //println!("exec_opcode");
 
-    z.cyc += cyc_00[opcode as usize] as u64;
+    let opcode: u8 = if is_8080(z) {
+        z.cyc += cyc_8080[opcode as usize] as u64;
+        opcode_8080(opcode)
+    } else {
+        z.cyc += cyc_00[opcode as usize] as u64;
+        opcode
+    };
     inc_r(z);
+    let hf: u8 = z.f & FLAG_H;
 
     match opcode {
         0x7F => {
@@ -1541,36 +1788,36 @@ pub fn exec_opcode(z: &mut z80, opcode: u8) {
         0x0A => {
             let temp = get_bc(z);
             z.a = rb(z, temp);
-            z.mem_ptr = get_bc(z) + 1;
+            z.mem_ptr = get_bc(z).wrapping_add(1);
             // ld a,(bc)
         }
         0x1A => {
             let temp = get_de(z);
             z.a = rb(z, temp);
-            z.mem_ptr = get_de(z) + 1;
+            z.mem_ptr = get_de(z).wrapping_add(1);
             // ld a,(de)
         }
         0x3A => {
             let addr: u16 = nextw(z);
             z.a = rb(z, addr);
-            z.mem_ptr = addr + 1;
+            z.mem_ptr = addr.wrapping_add(1);
         } // ld a,(**)
         0x02 => {
             let temp = get_bc(z);
             wb(z, temp, z.a);
-            z.mem_ptr = ((z.a as u16) << 8) | ((get_bc(z) + 1) & 0xFF);
+            z.mem_ptr = ((z.a as u16) << 8) | (get_bc(z).wrapping_add(1) & 0xFF);
             // ld (bc),a
         }
         0x12 => {
             let temp = get_de(z);
             wb(z, temp, z.a);
-            z.mem_ptr = ((z.a as u16) << 8) | ((get_de(z) + 1) & 0xFF);
+            z.mem_ptr = ((z.a as u16) << 8) | (get_de(z).wrapping_add(1) & 0xFF);
             // ld (de),a
         }
         0x32 => {
             let addr: u16 = nextw(z);
             wb(z, addr, z.a);
-            z.mem_ptr = ((z.a as u16) << 8) | ((addr + 1) & 0xFF);
+            z.mem_ptr = ((z.a as u16) << 8) | (addr.wrapping_add(1) & 0xFF);
         } // ld (**),a
         0x01 => {
             let temp = nextw(z);
@@ -1591,13 +1838,13 @@ pub fn exec_opcode(z: &mut z80, opcode: u8) {
             let addr: u16 = nextw(z);
             let temp = rw(z, addr);
             set_hl(z, temp);
-            z.mem_ptr = addr + 1;
+            z.mem_ptr = addr.wrapping_add(1);
         } // ld hl,(**)
         0x22 => {
             let addr: u16 = nextw(z);
             let temp = get_hl(z);
             ww(z, addr, temp);
-            z.mem_ptr = addr + 1;
+            z.mem_ptr = addr.wrapping_add(1);
         } // ld (**),hl
         0xF9 => {
             z.sp = get_hl(z);
@@ -1646,34 +1893,34 @@ pub fn exec_opcode(z: &mut z80, opcode: u8) {
             z.a = addb(z, z.a, temp, 0);
         } // add a,*
         0x8F => {
-            z.a = addb(z, z.a, z.a, z.cf);
+            z.a = addb(z, z.a, z.a, z.f & FLAG_C);
         } // adc a,a
         0x88 => {
-            z.a = addb(z, z.a, z.b, z.cf);
+            z.a = addb(z, z.a, z.b, z.f & FLAG_C);
         } // adc a,b
         0x89 => {
-            z.a = addb(z, z.a, z.c, z.cf);
+            z.a = addb(z, z.a, z.c, z.f & FLAG_C);
         } // adc a,c
         0x8A => {
-            z.a = addb(z, z.a, z.d, z.cf);
+            z.a = addb(z, z.a, z.d, z.f & FLAG_C);
         } // adc a,d
         0x8B => {
-            z.a = addb(z, z.a, z.e, z.cf);
+            z.a = addb(z, z.a, z.e, z.f & FLAG_C);
         } // adc a,e
         0x8C => {
-            z.a = addb(z, z.a, z.h, z.cf);
+            z.a = addb(z, z.a, z.h, z.f & FLAG_C);
         } // adc a,h
         0x8D => {
-            z.a = addb(z, z.a, z.l, z.cf);
+            z.a = addb(z, z.a, z.l, z.f & FLAG_C);
         } // adc a,l
         0x8E => {
             let temp1 = get_hl(z);
             let temp2 = rb(z, temp1);
-            z.a = addb(z, z.a, temp2, z.cf);
+            z.a = addb(z, z.a, temp2, z.f & FLAG_C);
         } // adc a,(hl)
         0xCE => {
             let temp = nextb(z);
-            z.a = addb(z, z.a, temp, z.cf);
+            z.a = addb(z, z.a, temp, z.f & FLAG_C);
         } // adc a,*
         0x97 => {
             z.a = subb(z, z.a, z.a, 0);
@@ -1706,34 +1953,34 @@ pub fn exec_opcode(z: &mut z80, opcode: u8) {
             z.a = subb(z, z.a, temp, 0);
         } // sub a,*
         0x9F => {
-            z.a = subb(z, z.a, z.a, z.cf);
+            z.a = subb(z, z.a, z.a, z.f & FLAG_C);
         } // sbc a,a
         0x98 => {
-            z.a = subb(z, z.a, z.b, z.cf);
+            z.a = subb(z, z.a, z.b, z.f & FLAG_C);
         } // sbc a,b
         0x99 => {
-            z.a = subb(z, z.a, z.c, z.cf);
+            z.a = subb(z, z.a, z.c, z.f & FLAG_C);
         } // sbc a,c
         0x9A => {
-            z.a = subb(z, z.a, z.d, z.cf);
+            z.a = subb(z, z.a, z.d, z.f & FLAG_C);
         } // sbc a,d
         0x9B => {
-            z.a = subb(z, z.a, z.e, z.cf);
+            z.a = subb(z, z.a, z.e, z.f & FLAG_C);
         } // sbc a,e
         0x9C => {
-            z.a = subb(z, z.a, z.h, z.cf);
+            z.a = subb(z, z.a, z.h, z.f & FLAG_C);
         } // sbc a,h
         0x9D => {
-            z.a = subb(z, z.a, z.l, z.cf);
+            z.a = subb(z, z.a, z.l, z.f & FLAG_C);
         } // sbc a,l
         0x9E => {
             let temp1 = get_hl(z);
             let temp2 = rb(z, temp1);
-            z.a = subb(z, z.a, temp2, z.cf);
+            z.a = subb(z, z.a, temp2, z.f & FLAG_C);
         } // sbc a,(hl)
         0xDE => {
             let temp = nextb(z);
-            z.a = subb(z, z.a, temp, z.cf);
+            z.a = subb(z, z.a, temp, z.f & FLAG_C);
         } // sbc a,*
         0x09 => {
             let temp = get_bc(z);
@@ -1820,96 +2067,77 @@ pub fn exec_opcode(z: &mut z80, opcode: u8) {
         } // dec (hl)
         0x03 => {
             let temp = get_bc(z);
-            set_bc(z, temp + 1);
+            set_bc(z, temp.wrapping_add(1));
         } // inc bc
         0x13 => {
             let temp = get_de(z);
-            set_de(z, temp + 1);
+            set_de(z, temp.wrapping_add(1));
         } // inc de
         0x23 => {
             let temp = get_hl(z);
-            set_hl(z, temp + 1);
+            set_hl(z, temp.wrapping_add(1));
         } // inc hl
         0x33 => {
-            z.sp = z.sp + 1;
+            z.sp = z.sp.wrapping_add(1);
         } // inc sp
         0x0B => {
             let temp = get_bc(z);
-            set_bc(z, temp - 1);
+            set_bc(z, temp.wrapping_sub(1));
         } // dec bc
         0x1B => {
             let temp = get_de(z);
-            set_de(z, temp - 1);
+            set_de(z, temp.wrapping_sub(1));
         } // dec de
         0x2B => {
             let temp = get_hl(z);
-            set_hl(z, temp - 1);
+            set_hl(z, temp.wrapping_sub(1));
         } // dec hl
         0x3B => {
-            z.sp = z.sp - 1;
+            z.sp = z.sp.wrapping_sub(1);
         } // dec sp
         0x27 => {
             daa(z);
         } // daa
         0x2F => {
             z.a = !z.a;
-            z.nf = 1;
-            z.hf = 1;
-            z.xf = GET_BIT(3, z.a);
-            z.yf = GET_BIT(5, z.a);
+            z.f = (z.f & (FLAG_S | FLAG_Z | FLAG_P | FLAG_C)) | FLAG_H | FLAG_N | (z.a & FLAG_XY);
             // cpl
         }
         0x37 => {
-            z.cf = 1;
-            z.nf = 0;
-            z.hf = 0;
-            z.xf = GET_BIT(3, z.a);
-            z.yf = GET_BIT(5, z.a);
+            // xf and yf come from a, or'ed with the flags unless the
+            // previous instruction set them (q)
+            let xy: u8 = (z.q ^ get_f(z)) | z.a;
+            z.f = (z.f & (FLAG_S | FLAG_Z | FLAG_P)) | FLAG_C | (xy & FLAG_XY);
             // scf
         }
         0x3F => {
-            z.hf = z.cf;
-            z.cf = !z.cf & 0x1;
-            z.nf = 0;
-            z.xf = GET_BIT(3, z.a);
-            z.yf = GET_BIT(5, z.a);
+            // hf is the previous carry
+            let xy: u8 = (z.q ^ get_f(z)) | z.a;
+            let cf: u8 = z.f & FLAG_C;
+            z.f = (z.f & (FLAG_S | FLAG_Z | FLAG_P)) | (cf << 4) | (cf ^ FLAG_C) | (xy & FLAG_XY);
             // ccf
         }
         0x07 => {
-            z.cf = z.a >> 7;
-            z.a = (z.a << 1) | z.cf;
-            z.nf = 0;
-            z.hf = 0;
-            z.xf = GET_BIT(3, z.a);
-            z.yf = GET_BIT(5, z.a);
+            let cf: u8 = z.a >> 7;
+            z.a = (z.a << 1) | cf;
+            z.f = (z.f & (FLAG_S | FLAG_Z | FLAG_P)) | (z.a & FLAG_XY) | cf;
         } // rlca (rotate left)
         0x0F => {
-            z.cf = z.a & 1;
-            z.a = (z.a >> 1) | (z.cf << 7);
-            z.nf = 0;
-            z.hf = 0;
-            z.xf = GET_BIT(3, z.a);
-            z.yf = GET_BIT(5, z.a);
+            let cf: u8 = z.a & 1;
+            z.a = (z.a >> 1) | (cf << 7);
+            z.f = (z.f & (FLAG_S | FLAG_Z | FLAG_P)) | (z.a & FLAG_XY) | cf;
         } // rrca (rotate right)
         0x17 => {
-            let cy: u8 = z.cf;
-            let cy_ = cy & 0x01;
-            z.cf = z.a >> 7;
+            let cy_: u8 = z.f & FLAG_C;
+            let cf: u8 = z.a >> 7;
             z.a = (z.a << 1) | cy_;
-            z.nf = 0;
-            z.hf = 0;
-            z.xf = GET_BIT(3, z.a);
-            z.yf = GET_BIT(5, z.a);
+            z.f = (z.f & (FLAG_S | FLAG_Z | FLAG_P)) | (z.a & FLAG_XY) | cf;
         } // rla
         0x1F => {
-            let cy: u8 = z.cf;
-            let cy_ = cy & 0x01;
-            z.cf = z.a & 1;
+            let cy_: u8 = z.f & FLAG_C;
+            let cf: u8 = z.a & 1;
             z.a = (z.a >> 1) | (cy_ << 7);
-            z.nf = 0;
-            z.hf = 0;
-            z.xf = GET_BIT(3, z.a);
-            z.yf = GET_BIT(5, z.a);
+            z.f = (z.f & (FLAG_S | FLAG_Z | FLAG_P)) | (z.a & FLAG_XY) | cf;
         } // rra
         0xA7 => {
             land(z, z.a);
@@ -2036,28 +2264,28 @@ pub fn exec_opcode(z: &mut z80, opcode: u8) {
             jump(z, temp);
         } // jm **
         0xC2 => {
-            cond_jump(z, z.zf == 0);
+            cond_jump(z, (z.f & FLAG_Z) == 0);
         } // jp nz, **
         0xCA => {
-            cond_jump(z, z.zf == 1);
+            cond_jump(z, (z.f & FLAG_Z) != 0);
         } // jp z, **
         0xD2 => {
-            cond_jump(z, z.cf == 0);
+            cond_jump(z, (z.f & FLAG_C) == 0);
         } // jp nc, **
         0xDA => {
-            cond_jump(z, z.cf == 1);
+            cond_jump(z, (z.f & FLAG_C) != 0);
         } // jp c, **
         0xE2 => {
-            cond_jump(z, z.pf == 0);
+            cond_jump(z, (z.f & FLAG_P) == 0);
         } // jp po, **
         0xEA => {
-            cond_jump(z, z.pf == 1);
+            cond_jump(z, (z.f & FLAG_P) != 0);
         } // jp pe, **
         0xF2 => {
-            cond_jump(z, z.sf == 0);
+            cond_jump(z, (z.f & FLAG_S) == 0);
         } // jp p, **
         0xFA => {
-            cond_jump(z, z.sf == 1);
+            cond_jump(z, (z.f & FLAG_S) != 0);
         } // jp m, **
         0x10 => {
             let condition: bool;
@@ -2074,16 +2302,16 @@ pub fn exec_opcode(z: &mut z80, opcode: u8) {
             z.pc = z.pc.wrapping_add(temp as u16);
         } // jr *
         0x20 => {
-            cond_jr(z, z.zf == 0);
+            cond_jr(z, (z.f & FLAG_Z) == 0);
         } // jr nz, *
         0x28 => {
-            cond_jr(z, z.zf == 1);
+            cond_jr(z, (z.f & FLAG_Z) != 0);
         } // jr z, *
         0x30 => {
-            cond_jr(z, z.cf == 0);
+            cond_jr(z, (z.f & FLAG_C) == 0);
         } // jr nc, *
         0x38 => {
-            cond_jr(z, z.cf == 1);
+            cond_jr(z, (z.f & FLAG_C) != 0);
         } // jr c, *
         0xE9 => {
             z.pc = get_hl(z);
@@ -2093,55 +2321,55 @@ pub fn exec_opcode(z: &mut z80, opcode: u8) {
             call(z, temp);
         } // call
         0xC4 => {
-            cond_call(z, z.zf == 0);
+            cond_call(z, (z.f & FLAG_Z) == 0);
         } // cnz
         0xCC => {
-            cond_call(z, z.zf == 1);
+            cond_call(z, (z.f & FLAG_Z) != 0);
         } // cz
         0xD4 => {
-            cond_call(z, z.cf == 0);
+            cond_call(z, (z.f & FLAG_C) == 0);
         } // cnc
         0xDC => {
-            cond_call(z, z.cf == 1);
+            cond_call(z, (z.f & FLAG_C) != 0);
         } // cc
         0xE4 => {
-            cond_call(z, z.pf == 0);
+            cond_call(z, (z.f & FLAG_P) == 0);
         } // cpo
         0xEC => {
-            cond_call(z, z.pf == 1);
+            cond_call(z, (z.f & FLAG_P) != 0);
         } // cpe
         0xF4 => {
-            cond_call(z, z.sf == 0);
+            cond_call(z, (z.f & FLAG_S) == 0);
         } // cp
         0xFC => {
-            cond_call(z, z.sf == 1);
+            cond_call(z, (z.f & FLAG_S) != 0);
         } // cm
         0xC9 => {
             ret(z);
         } // ret
         0xC0 => {
-            cond_ret(z, z.zf == 0);
+            cond_ret(z, (z.f & FLAG_Z) == 0);
         } // ret nz
         0xC8 => {
-            cond_ret(z, z.zf == 1);
+            cond_ret(z, (z.f & FLAG_Z) != 0);
         } // ret z
         0xD0 => {
-            cond_ret(z, z.cf == 0);
+            cond_ret(z, (z.f & FLAG_C) == 0);
         } // ret nc
         0xD8 => {
-            cond_ret(z, z.cf == 1);
+            cond_ret(z, (z.f & FLAG_C) != 0);
         } // ret c
         0xE0 => {
-            cond_ret(z, z.pf == 0);
+            cond_ret(z, (z.f & FLAG_P) == 0);
         } // ret po
         0xE8 => {
-            cond_ret(z, z.pf == 1);
+            cond_ret(z, (z.f & FLAG_P) != 0);
         } // ret pe
         0xF0 => {
-            cond_ret(z, z.sf == 0);
+            cond_ret(z, (z.f & FLAG_S) == 0);
         } // ret p
         0xF8 => {
-            cond_ret(z, z.sf == 1);
+            cond_ret(z, (z.f & FLAG_S) != 0);
         } // ret m
         0xC7 => {
             call(z, 0x00);
@@ -2203,12 +2431,12 @@ pub fn exec_opcode(z: &mut z80, opcode: u8) {
         0xDB => {
             let port: u8 = nextb(z);
             let a: u8 = z.a;
-            z.a = (z.port_in)(z, port);
+            z.a = port_rb(z, port);
             z.mem_ptr = ((a as u16) << 8) | (z.a as u16 + 1);
         } // in a,(n)
         0xD3 => {
             let port: u8 = nextb(z);
-            (z.port_out)(z, port, z.a);
+            port_wb(z, port, z.a);
             z.mem_ptr = (port as u16 + 1) | ((z.a as u16) << 8);
         } // out (n), a
         0x08 => {
@@ -2244,30 +2472,42 @@ pub fn exec_opcode(z: &mut z80, opcode: u8) {
             z.l_ = l;
         } // exx
         0xCB => {
-            let temp = nextb(z);
+            let temp = nextop(z);
             exec_opcode_cb(z, temp);
         }
         0xED => {
-            let temp = nextb(z);
+            let temp = nextop(z);
             exec_opcode_ed(z, temp);
         }
         0xDD => {
-            let temp1 = nextb(z);
+            let temp1 = nextop(z);
             let mut temp2 = z.ix;
             exec_opcode_ddfd(z, temp1, &mut temp2);
             z.ix = temp2;
         }
         0xFD => {
-            let temp1 = nextb(z);
+            let temp1 = nextop(z);
             let mut temp2 = z.iy;
             exec_opcode_ddfd(z, temp1, &mut temp2);
             z.iy = temp2;
         }
     }
+
+    // the 8080 leaves the auxiliary carry alone on rotates, dad, cma, stc
+    // and cmc
+    if is_8080(z) {
+        match opcode {
+            0x07 | 0x0F | 0x17 | 0x1F | 0x09 | 0x19 | 0x29 | 0x39 | 0x2F | 0x37 | 0x3F => {
+                z.f = (z.f & !FLAG_H) | hf;
+            }
+            _ => {}
+        }
+    }
+    set_q(z, opcode);
 }
 
 // executes a DD/FD opcode (IZ = IX or IY)
-pub fn exec_opcode_ddfd(z: &mut z80, opcode: u8, iz: &mut u16) {
+pub fn exec_opcode_ddfd<U>(z: &mut z80<U>, opcode: u8, iz: &mut u16) {
     //println!("exec_opcode_ddfd");
 
     z.cyc += cyc_ddfd[opcode as usize] as u64;
@@ -2306,10 +2546,10 @@ pub fn exec_opcode_ddfd(z: &mut z80, opcode: u8, iz: &mut u16) {
         } // add a,izl
         0x8C => {
             let IZH = *iz >> 8;
-            z.a = addb(z, z.a, IZH as u8, z.cf);
+            z.a = addb(z, z.a, IZH as u8, z.f & FLAG_C);
         } // adc a,izh
         0x8D => {
-            z.a = addb(z, z.a, (*iz & 0xFF) as u8, z.cf);
+            z.a = addb(z, z.a, (*iz & 0xFF) as u8, z.f & FLAG_C);
         } // adc a,izl
         0x86 => {
             let temp = nextb(z);
@@ -2321,7 +2561,7 @@ pub fn exec_opcode_ddfd(z: &mut z80, opcode: u8, iz: &mut u16) {
             let temp = nextb(z);
             let IZD = displace(z, *iz, temp as i8);
             let temp = rb(z, IZD);
-            z.a = addb(z, z.a, temp, z.cf);
+            z.a = addb(z, z.a, temp, z.f & FLAG_C);
         } // adc a,(iz+*)
         0x96 => {
             let temp = nextb(z);
@@ -2333,7 +2573,7 @@ pub fn exec_opcode_ddfd(z: &mut z80, opcode: u8, iz: &mut u16) {
             let temp = nextb(z);
             let IZD = displace(z, *iz, temp as i8);
             let temp = rb(z, IZD);
-            z.a = subb(z, z.a, temp, z.cf);
+            z.a = subb(z, z.a, temp, z.f & FLAG_C);
         } // sbc (iz+*)
         0x94 => {
             let IZH = *iz >> 8;
@@ -2344,10 +2584,10 @@ pub fn exec_opcode_ddfd(z: &mut z80, opcode: u8, iz: &mut u16) {
         } // sub izl
         0x9C => {
             let IZH = *iz >> 8;
-            z.a = subb(z, z.a, IZH as u8, z.cf);
+            z.a = subb(z, z.a, IZH as u8, z.f & FLAG_C);
         } // sbc izh
         0x9D => {
-            z.a = subb(z, z.a, (*iz & 0xFF) as u8, z.cf);
+            z.a = subb(z, z.a, (*iz & 0xFF) as u8, z.f & FLAG_C);
         } // sbc izl
         0xA6 => {
             let temp = nextb(z);
@@ -2402,10 +2642,10 @@ pub fn exec_opcode_ddfd(z: &mut z80, opcode: u8, iz: &mut u16) {
             cp(z, (*iz & 0xFF) as u8);
         } // cp izl
         0x23 => {
-            *iz += 1;
+            *iz = iz.wrapping_add(1);
         } // inc iz
         0x2B => {
-            *iz -= 1;
+            *iz = iz.wrapping_sub(1);
         } // dec iz
         0x34 => {
             let temp = nextb(z);
@@ -2652,10 +2892,11 @@ pub fn exec_opcode_ddfd(z: &mut z80, opcode: u8, iz: &mut u16) {
             z.r = (z.r & 0x80) | ((z.r.wrapping_sub(1)) & 0x7f);
         }
     }
+    set_q(z, opcode);
 }
 
 // executes a CB opcode
-pub fn exec_opcode_cb(z: &mut z80, opcode: u8) {
+pub fn exec_opcode_cb<U>(z: &mut z80<U>, opcode: u8) {
     //println!("exec_opcode_cb");
 
     z.cyc += 8;
@@ -2735,8 +2976,7 @@ pub fn exec_opcode_cb(z: &mut z80, opcode: u8) {
 
                 // in bit (hl), x/y flags are handled differently:
                 if z_ == 6 {
-                    z.yf = GET_BIT(5, (z.mem_ptr >> 8) as u8);
-                    z.xf = GET_BIT(3, (z.mem_ptr >> 8) as u8);
+                    z.f = (z.f & !FLAG_XY) | ((z.mem_ptr >> 8) as u8 & FLAG_XY);
                     z.cyc += 4;
                 }
             }
@@ -2758,10 +2998,12 @@ pub fn exec_opcode_cb(z: &mut z80, opcode: u8) {
         let temp = get_hl(z);
         wb(z, temp, hl);
     }
+    // rotates, shifts and bit set the flags, res and set do not
+    z.q = if x_ < 2 { get_f(z) } else { 0 };
 }
 
 // executes a displaced CB opcode (DDCB or FDCB)
-pub fn exec_opcode_dcb(z: &mut z80, opcode: u8, addr: u16) {
+pub fn exec_opcode_dcb<U>(z: &mut z80<U>, opcode: u8, addr: u16) {
     //println!("exec_opcode_dcb");
 
     let val: u8 = rb(z, addr);
@@ -2805,8 +3047,7 @@ pub fn exec_opcode_dcb(z: &mut z80, opcode: u8, addr: u16) {
         }
         1 => {
             result = cb_bit(z, val, y_) as u16;
-            z.yf = GET_BIT(5, (addr >> 8) as u8);
-            z.xf = GET_BIT(3, (addr >> 8) as u8);
+            z.f = (z.f & !FLAG_XY) | ((addr >> 8) as u8 & FLAG_XY);
         } // bit y,(iz+d)
         2 => {
             result = (val & !(1 << y_)) as u16;
@@ -2816,6 +3057,7 @@ pub fn exec_opcode_dcb(z: &mut z80, opcode: u8, addr: u16) {
         } // set y, (iz+d)
 
         _ => {
+            #[cfg(feature = "std")]
             println!("unknown XYCB opcode: {:2x}", opcode);
         }
     }
@@ -2862,10 +3104,11 @@ pub fn exec_opcode_dcb(z: &mut z80, opcode: u8, addr: u16) {
         wb(z, addr, result as u8);
         z.cyc += 23;
     }
+    z.q = if x_ < 2 { get_f(z) } else { 0 };
 }
 
 // executes a ED opcode
-pub fn exec_opcode_ed(z: &mut z80, opcode: u8) {
+pub fn exec_opcode_ed<U>(z: &mut z80<U>, opcode: u8) {
     //println!("exec_opcode_ed");
 
     z.cyc += cyc_ed[opcode as usize] as u64;
@@ -2880,35 +3123,19 @@ pub fn exec_opcode_ed(z: &mut z80, opcode: u8) {
 
         0x57 => {
             z.a = z.i;
-            z.sf = z.a >> 7;
-            if z.a == 0 {
-                z.zf = 1;
-            } else {
-                z.zf = 0;
-            }
-            z.hf = 0;
-            z.nf = 0;
+            // pf is iff2
+            z.f = (z.f & (FLAG_XY | FLAG_C)) | (SZ[z.a as usize] & (FLAG_S | FLAG_Z));
             if z.iff2 {
-                z.pf = 1;
-            } else {
-                z.pf = 0;
+                z.f |= FLAG_P;
             }
             // ld a,i
         }
         0x5F => {
             z.a = z.r;
-            z.sf = z.a >> 7;
-            if z.a == 0 {
-                z.zf = 1;
-            } else {
-                z.zf = 0;
-            }
-            z.hf = 0;
-            z.nf = 0;
+            // pf is iff2
+            z.f = (z.f & (FLAG_XY | FLAG_C)) | (SZ[z.a as usize] & (FLAG_S | FLAG_Z));
             if z.iff2 {
-                z.pf = 1;
-            } else {
-                z.pf = 0;
+                z.f |= FLAG_P;
             }
             // ld a,r
         }
@@ -2928,9 +3155,7 @@ pub fn exec_opcode_ed(z: &mut z80, opcode: u8) {
             ldi(z);
 
             if get_bc(z) != 0 {
-                z.pc -= 2;
-                z.cyc += 5;
-                z.mem_ptr = z.pc + 1;
+                block_repeat(z);
             }
         } // ldir
         0xA8 => {
@@ -2940,9 +3165,7 @@ pub fn exec_opcode_ed(z: &mut z80, opcode: u8) {
             ldd(z);
 
             if get_bc(z) != 0 {
-                z.pc -= 2;
-                z.cyc += 5;
-                z.mem_ptr = z.pc + 1;
+                block_repeat(z);
             }
         } // lddr
         0xA1 => {
@@ -2953,21 +3176,14 @@ pub fn exec_opcode_ed(z: &mut z80, opcode: u8) {
         } // cpd
         0xB1 => {
             cpi(z);
-            if get_bc(z) != 0 && z.zf == 0 {
-                z.pc -= 2;
-                z.cyc += 5;
-                z.mem_ptr = z.pc + 1;
-            } else {
-                z.mem_ptr += 1;
+            if get_bc(z) != 0 && (z.f & FLAG_Z) == 0 {
+                block_repeat(z);
             }
         } // cpir
         0xB9 => {
             cpd(z);
-            if get_bc(z) != 0 && z.zf == 0 {
-                z.pc -= 2;
-                z.cyc += 5;
-            } else {
-                z.mem_ptr += 1;
+            if get_bc(z) != 0 && (z.f & FLAG_Z) == 0 {
+                block_repeat(z);
             }
         } // cpdr
         0x40 => {
@@ -3008,17 +3224,16 @@ pub fn exec_opcode_ed(z: &mut z80, opcode: u8) {
             let mut value = z.a;
             in_r_c(z, &mut value);
             z.a = value;
-            z.mem_ptr = get_bc(z) + 1;
+            z.mem_ptr = get_bc(z).wrapping_add(1);
             // in a, (c)
         }
         0xA2 => {
             ini(z);
         } // ini}
         0xB2 => {
-            ini(z);
+            let val = ini(z);
             if z.b > 0 {
-                z.pc -= 2;
-                z.cyc += 5;
+                block_io_repeat(z, val);
             }
             // inir
         }
@@ -3026,56 +3241,54 @@ pub fn exec_opcode_ed(z: &mut z80, opcode: u8) {
             ind(z);
         } // ind}
         0xBA => {
-            ind(z);
+            let val = ind(z);
             if z.b > 0 {
-                z.pc -= 2;
-                z.cyc += 5;
+                block_io_repeat(z, val);
             }
             // indr
         }
         0x41 => {
-            (z.port_out)(z, z.c, z.b);
+            port_wb(z, z.c, z.b);
         } // out (c), b
         0x49 => {
-            (z.port_out)(z, z.c, z.c);
+            port_wb(z, z.c, z.c);
         } // out (c), c
         0x51 => {
-            (z.port_out)(z, z.c, z.d);
+            port_wb(z, z.c, z.d);
         } // out (c), d
         0x59 => {
-            (z.port_out)(z, z.c, z.e);
+            port_wb(z, z.c, z.e);
         } // out (c), e
         0x61 => {
-            (z.port_out)(z, z.c, z.h);
+            port_wb(z, z.c, z.h);
         } // out (c), h
         0x69 => {
-            (z.port_out)(z, z.c, z.l);
+            port_wb(z, z.c, z.l);
         } // out (c), l
         0x71 => {
-            (z.port_out)(z, z.c, 0);
+            port_wb(z, z.c, 0);
         } // out (c), 0
         0x79 => {
-            (z.port_out)(z, z.c, z.a);
-            z.mem_ptr = get_bc(z) + 1;
+            port_wb(z, z.c, z.a);
+            z.mem_ptr = get_bc(z).wrapping_add(1);
             // out (c), a
         }
         0xA3 => {
             outi(z);
         } // outi
         0xB3 => {
-            outi(z);
+            let val = outi(z);
             if z.b > 0 {
-                z.pc -= 2;
-                z.cyc += 5;
+                block_io_repeat(z, val);
             }
         } // otir
         0xAB => {
             outd(z);
         } // outd
         0xBB => {
-            outd(z);
+            let val = outd(z);
             if z.b > 0 {
-                z.pc -= 2;
+                block_io_repeat(z, val);
             }
         } // otdr
         0x42 => {
@@ -3112,47 +3325,47 @@ pub fn exec_opcode_ed(z: &mut z80, opcode: u8) {
             let addr: u16 = nextw(z);
             let result = get_bc(z);
             ww(z, addr, result);
-            z.mem_ptr = addr + 1;
+            z.mem_ptr = addr.wrapping_add(1);
         } // ld (**), bc
         0x53 => {
             let addr: u16 = nextw(z);
             let result = get_de(z);
             ww(z, addr, result);
-            z.mem_ptr = addr + 1;
+            z.mem_ptr = addr.wrapping_add(1);
         } // ld (**), de
         0x63 => {
             let addr: u16 = nextw(z);
             let result = get_hl(z);
             ww(z, addr, result);
-            z.mem_ptr = addr + 1;
+            z.mem_ptr = addr.wrapping_add(1);
         } // ld (**), hl
         0x73 => {
             let addr: u16 = nextw(z);
             ww(z, addr, z.sp);
-            z.mem_ptr = addr + 1;
+            z.mem_ptr = addr.wrapping_add(1);
         } // ld (**),sp
         0x4B => {
             let addr: u16 = nextw(z);
             let result = rw(z, addr);
             set_bc(z, result);
-            z.mem_ptr = addr + 1;
+            z.mem_ptr = addr.wrapping_add(1);
         } // ld bc, (**)
         0x5B => {
             let addr: u16 = nextw(z);
             let result = rw(z, addr);
             set_de(z, result);
-            z.mem_ptr = addr + 1;
+            z.mem_ptr = addr.wrapping_add(1);
         } // ld de, (**)
         0x6B => {
             let addr: u16 = nextw(z);
             let result = rw(z, addr);
             set_hl(z, result);
-            z.mem_ptr = addr + 1;
+            z.mem_ptr = addr.wrapping_add(1);
         } // ld hl, (**)
         0x7B => {
             let addr: u16 = nextw(z);
             z.sp = rw(z, addr);
-            z.mem_ptr = addr + 1;
+            z.mem_ptr = addr.wrapping_add(1);
         } // ld sp,(**)
         0x44 | 0x54 | 0x64 | 0x74 | 0x4C | 0x5C | 0x6C | 0x7C => {
             z.a = subb(z, 0, z.a, 0);
@@ -3174,23 +3387,8 @@ pub fn exec_opcode_ed(z: &mut z80, opcode: u8) {
             let result = get_hl(z);
             wb(z, result, (val >> 4) | (a << 4));
 
-            z.nf = 0;
-            z.hf = 0;
-            z.xf = GET_BIT(3, z.a);
-            z.yf = GET_BIT(5, z.a);
-            if z.a == 0 {
-                z.zf = 1;
-            } else {
-                z.zf = 0;
-            }
-
-            z.sf = z.a >> 7;
-            if parity(z.a) {
-                z.pf = 1;
-            } else {
-                z.pf = 0;
-            }
-            z.mem_ptr = get_hl(z) + 1;
+            z.f = (z.f & FLAG_C) | SZP[z.a as usize];
+            z.mem_ptr = get_hl(z).wrapping_add(1);
         } // rrd
         0x6F => {
             let a: u8 = z.a;
@@ -3200,27 +3398,14 @@ pub fn exec_opcode_ed(z: &mut z80, opcode: u8) {
             let result = get_hl(z);
             wb(z, result, (val << 4) | (a & 0xF));
 
-            z.nf = 0;
-            z.hf = 0;
-            z.xf = GET_BIT(3, z.a);
-            z.yf = GET_BIT(5, z.a);
-            if z.a == 0 {
-                z.zf = 1;
-            } else {
-                z.zf = 0;
-            }
-            z.sf = z.a >> 7;
-            if parity(z.a) {
-                z.pf = 1;
-            } else {
-                z.pf = 0;
-            }
-
-            z.mem_ptr = get_hl(z) + 1;
+            z.f = (z.f & FLAG_C) | SZP[z.a as usize];
+            z.mem_ptr = get_hl(z).wrapping_add(1);
         } // rld
 
         _ => {
+            #[cfg(feature = "std")]
             println!("unknown ED opcode: {:02X}", opcode);
         }
     }
+    z.q = if sets_flags_ed(opcode) { get_f(z) } else { 0 };
 }
@@ -0,0 +1,130 @@
+#![allow(non_camel_case_types)]
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+
+// Checks z80_asm: directives, labels and expressions on a few sources, and,
+// for every opcode, that assembling the text of the disassembler gives back
+// an instruction of the same text and length (but for ignored
+// prefixes and the long forms of ld hl,(nn) and ld (nn),hl).
+
+use ::z80::*;
+
+// the bytes of a source assembled as one block
+fn asm(source: &str) -> Vec<u8> {
+    let blocks = z80_asm(source).unwrap();
+    assert_eq!(blocks.len(), 1, "{}", source);
+    return blocks.into_iter().next().unwrap().bytes;
+}
+
+fn disasm_at(bytes: &[u8], addr: u16) -> (String, u16) {
+    let mut memory: [u8; 0x10000] = [0; 0x10000];
+    memory[addr as usize..addr as usize + bytes.len()].copy_from_slice(bytes);
+    return z80_disasm(&mut |addr| memory[addr as usize], addr);
+}
+
+#[test]
+fn sources() {
+    let cases: [(&str, &[u8]); 16] = [
+        ("nop", &[0x00]),
+        ("  LD A,(IX+5) ; comment", &[0xdd, 0x7e, 0x05]),
+        ("ld (iy-2),0x12", &[0xfd, 0x36, 0xfe, 0x12]),
+        ("ld (ix),a", &[0xdd, 0x77, 0x00]),
+        ("ex af,af' ; swap", &[0x08]),
+        ("ld hl,(0x4e00)", &[0x2a, 0x00, 0x4e]),
+        ("ld de,(0x4e00)", &[0xed, 0x5b, 0x00, 0x4e]),
+        ("ld a,1+2*3", &[0x3e, 0x07]),
+        ("ld a,(1+2)*3", &[0x3e, 0x09]),
+        ("ld bc,$1234 & 0ff0h | %1", &[0x01, 0x31, 0x02]),
+        ("ld a,'A'", &[0x3e, 0x41]),
+        ("ld a,-1", &[0x3e, 0xff]),
+        ("db 1,\"ab;c\",-2", &[0x01, 0x61, 0x62, 0x3b, 0x63, 0xfe]),
+        ("dw 0x1234,$", &[0x34, 0x12, 0x02, 0x00]),
+        ("ds 3,0xff", &[0xff, 0xff, 0xff]),
+        ("jp c,0x1234", &[0xda, 0x34, 0x12]),
+    ];
+    for (source, bytes) in cases {
+        assert_eq!(asm(source), bytes, "{}", source);
+    }
+}
+
+#[test]
+fn labels() {
+    let source = "
+        count equ 3
+        org 0x1000
+start:  ld b,count
+loop:   djnz loop
+        jr nz,done      ; forward
+        jp start
+        dw done-start
+done:   ret
+        org 0x2000
+        call done
+    ";
+    let blocks = z80_asm(source).unwrap();
+    assert_eq!(
+        blocks,
+        vec![
+            asm_block {
+                org: 0x1000,
+                bytes: vec![0x06, 0x03, 0x10, 0xfe, 0x20, 0x05, 0xc3, 0x00, 0x10, 0x0b, 0x00, 0xc9],
+            },
+            asm_block {
+                org: 0x2000,
+                bytes: vec![0xcd, 0x0b, 0x10],
+            },
+        ]
+    );
+}
+
+#[test]
+fn errors() {
+    let cases: [(&str, usize); 8] = [
+        ("nop\nfoo a", 2),
+        ("ld a,undefined", 1),
+        ("ld a,0x100", 1),
+        ("ld h,ixl", 1),
+        ("ld (hl),(hl)", 1),
+        ("jr 0x200", 1),
+        ("x: nop\nx: nop", 2),
+        ("bit 8,a", 1),
+    ];
+    for (source, line) in cases {
+        let err = z80_asm(source).unwrap_err();
+        assert_eq!(err.line, line, "{}: {}", source, err);
+    }
+}
+
+#[test]
+fn round_trip() {
+    // every opcode, after each prefix, with operands that don't matter
+    let mut sequences: Vec<Vec<u8>> = Vec::new();
+    for op in 0..=0xffu8 {
+        sequences.push(vec![op, 0x12, 0x34, 0x56]);
+        for prefix in [0xcb, 0xed, 0xdd, 0xfd] {
+            sequences.push(vec![prefix, op, 0x12, 0x34, 0x56]);
+        }
+        for prefix in [0xdd, 0xfd] {
+            sequences.push(vec![prefix, 0xcb, 0x12, op]);
+        }
+    }
+
+    for bytes in sequences {
+        let (text, len) = disasm_at(&bytes, 0x1000);
+        if text.starts_with("db") {
+            continue;
+        }
+        let source = format!("org 0x1000\n{}", text);
+        let blocks = z80_asm(&source).unwrap_or_else(|e| panic!("{:02x?} {}: {}", bytes, text, e));
+        let (again, again_len) = disasm_at(&blocks[0].bytes, 0x1000);
+        assert_eq!(again, text, "{:02x?}", bytes);
+        // a dd or fd prefix that the instruction ignores is not assembled,
+        // and ld hl,(nn) and ld (nn),hl get their short opcodes
+        let ignored = (bytes[0] == 0xdd || bytes[0] == 0xfd) && !text.contains("ix") && !text.contains("iy");
+        if ignored || bytes[..2] == [0xed, 0x63] || bytes[..2] == [0xed, 0x6b] {
+            continue;
+        }
+        assert_eq!(again_len, len, "{:02x?} {}", bytes, text);
+        assert_eq!(blocks[0].bytes.len(), len as usize, "{:02x?} {}", bytes, text);
+    }
+}
@@ -0,0 +1,62 @@
+#![allow(non_camel_case_types)]
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+#![allow(dead_code)]
+
+// The machine the tests run the z80 core on: 64 KB of ram, plus the state of
+// the test ("test", kept by its io or wait states hooks).
+
+use std::ptr;
+
+// the z80 crate, running with the userdata of the test machine
+pub mod z80 {
+    pub use ::z80::*;
+
+    pub type z80<T = ()> = ::z80::z80<super::userdata<T>>;
+}
+
+pub use z80::*;
+
+pub struct userdata<T = ()> {
+    pub machine_ptr: *mut machine<T>,
+}
+impl<T> userdata<T> {
+    pub fn new() -> Self {
+        Self {
+            machine_ptr: ptr::null_mut(),
+        }
+    }
+}
+
+pub struct machine<T = ()> {
+    pub cpu: z80::z80<T>,
+    pub memory: [u8; 0x10000],
+    pub test: T,
+}
+
+pub fn test_rb<T>(userdata: &mut userdata<T>, addr: u16) -> u8 {
+    unsafe {
+        return (*userdata.machine_ptr).memory[addr as usize];
+    }
+}
+
+pub fn test_wb<T>(userdata: &mut userdata<T>, addr: u16, val: u8) {
+    unsafe {
+        (*userdata.machine_ptr).memory[addr as usize] = val;
+    }
+}
+
+// a reset machine with cleared ram, its cpu reading and writing it
+pub fn new_machine<T>(test: T) -> Box<machine<T>> {
+    let mut m = Box::new(machine {
+        cpu: z80::z80::new(userdata::new()),
+        memory: [0; 0x10000],
+        test,
+    });
+    z80_init(&mut m.cpu);
+    let m_ptr: *mut machine<T> = &mut *m;
+    m.cpu.userdata.machine_ptr = m_ptr;
+    m.cpu.read_byte = test_rb;
+    m.cpu.write_byte = test_wb;
+    return m;
+}
@@ -0,0 +1,139 @@
+#![allow(non_camel_case_types)]
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+
+// Checks z80_disasm: the text of a few instructions of each opcode table
+// (and with symbols), and, for every opcode, that its length is how far the
+// core moves pc when it runs it (jumps, calls, returns and repeated block
+// instructions aside).
+
+mod common;
+use common::*;
+
+// disassembles "bytes" at 0x1000
+fn disasm(bytes: &[u8]) -> (String, u16) {
+    let mut memory: [u8; 0x10000] = [0; 0x10000];
+    memory[0x1000..0x1000 + bytes.len()].copy_from_slice(bytes);
+    return z80_disasm(&mut |addr| memory[addr as usize], 0x1000);
+}
+
+#[test]
+fn mnemonics() {
+    let cases: [(&[u8], &str); 32] = [
+        (&[0x00], "nop"),
+        (&[0x08], "ex af,af'"),
+        (&[0x10, 0xfe], "djnz 0x1000"),
+        (&[0x20, 0x05], "jr nz,0x1007"),
+        (&[0x21, 0x34, 0x12], "ld hl,0x1234"),
+        (&[0x22, 0x00, 0x4e], "ld (0x4e00),hl"),
+        (&[0x36, 0x7f], "ld (hl),0x7f"),
+        (&[0x76], "halt"),
+        (&[0x7e], "ld a,(hl)"),
+        (&[0x96], "sub (hl)"),
+        (&[0xce, 0x01], "adc a,0x01"),
+        (&[0xcd, 0x5e, 0x2c], "call 0x2c5e"),
+        (&[0xd3, 0x00], "out (0x00),a"),
+        (&[0xe9], "jp (hl)"),
+        (&[0xff], "rst 0x38"),
+        (&[0xcb, 0x37], "sll a"),
+        (&[0xcb, 0x7e], "bit 7,(hl)"),
+        (&[0xed, 0x46], "im 0"),
+        (&[0xed, 0x70], "in (c)"),
+        (&[0xed, 0x71], "out (c),0"),
+        (&[0xed, 0x73, 0xfe, 0xff], "ld (0xfffe),sp"),
+        (&[0xed, 0xb0], "ldir"),
+        (&[0xed, 0x00], "db 0xed,0x00"),
+        (&[0xdd, 0x21, 0x00, 0x50], "ld ix,0x5000"),
+        (&[0xdd, 0x36, 0xfe, 0x12], "ld (ix-0x02),0x12"),
+        (&[0xdd, 0x66, 0x03], "ld h,(ix+0x03)"),
+        (&[0xfd, 0x7c], "ld a,iyh"),
+        (&[0xfd, 0x2d], "dec iyl"),
+        (&[0xdd, 0xcb, 0x05, 0x46], "bit 0,(ix+0x05)"),
+        (&[0xfd, 0xcb, 0x80, 0x00], "rlc (iy-0x80),b"),
+        (&[0xdd, 0x00], "nop"),
+        (&[0xdd, 0xdd, 0x00], "db 0xdd"),
+    ];
+    for (bytes, text) in cases {
+        let (dis, len) = disasm(bytes);
+        assert_eq!(dis, text, "{:02x?}", bytes);
+        if text != "db 0xdd" {
+            assert_eq!(len as usize, bytes.len(), "{:02x?}", bytes);
+        }
+    }
+}
+
+#[test]
+fn symbols() {
+    // as the frontend: an immediate is named by a symbol of data only
+    let symbol = |addr: u16, kind: disasm_operand| match addr {
+        0x2c5e if kind != disasm_operand::Immediate => Some("print_text".to_string()),
+        0x1000 if kind != disasm_operand::Immediate => Some("loop".to_string()),
+        0x4e00 => Some("game_state".to_string()),
+        0x4e01..=0x4e0f => Some(format!("game_state+0x{:x}", addr - 0x4e00)),
+        _ => None,
+    };
+    let cases: [(&[u8], &str); 11] = [
+        // targets
+        (&[0xcd, 0x5e, 0x2c], "call print_text"),
+        (&[0xc3, 0x5e, 0x2c], "jp print_text"),
+        (&[0x10, 0xfe], "djnz loop"),
+        (&[0x18, 0xfe], "jr loop"),
+        // memory operands
+        (&[0x3a, 0x03, 0x4e], "ld a,(game_state+0x3)"),
+        (&[0xed, 0x43, 0x00, 0x4e], "ld (game_state),bc"),
+        // immediates: numbers, unless they are the address of data
+        (&[0x21, 0x34, 0x12], "ld hl,0x1234"),
+        (&[0x01, 0x5e, 0x2c], "ld bc,0x2c5e"),
+        (&[0x11, 0x00, 0x10], "ld de,0x1000"),
+        (&[0x21, 0x00, 0x4e], "ld hl,game_state"),
+        (&[0x3e, 0x10], "ld a,0x10"),
+    ];
+    for (bytes, text) in cases {
+        let mut memory: [u8; 0x10000] = [0; 0x10000];
+        memory[0x1000..0x1000 + bytes.len()].copy_from_slice(bytes);
+        let (dis, _) = z80_disasm_symbols(&mut |addr| memory[addr as usize], 0x1000, &symbol);
+        assert_eq!(dis, text, "{:02x?}", bytes);
+    }
+}
+
+// the instructions that may not fall through to the next one
+fn branches(text: &str) -> bool {
+    let mnemonic = text.split(' ').next().unwrap_or("");
+    return [
+        "jp", "jr", "djnz", "call", "ret", "reti", "retn", "rst", "halt", "ldir", "lddr", "cpir",
+        "cpdr", "inir", "indr", "otir", "otdr",
+    ]
+    .contains(&mnemonic);
+}
+
+#[test]
+fn lengths() {
+    let mut m = new_machine(());
+
+    // every opcode, after each prefix, with operands that don't matter
+    let mut sequences: Vec<Vec<u8>> = Vec::new();
+    for op in 0..=0xffu8 {
+        sequences.push(vec![op, 0x12, 0x34, 0x56]);
+        for prefix in [0xcb, 0xed, 0xdd, 0xfd] {
+            sequences.push(vec![prefix, op, 0x12, 0x34, 0x56]);
+        }
+        for prefix in [0xdd, 0xfd] {
+            sequences.push(vec![prefix, 0xcb, 0x12, op]);
+        }
+    }
+
+    for bytes in sequences {
+        let (text, len) = disasm(&bytes);
+        if branches(&text) || text.starts_with("db") {
+            continue;
+        }
+
+        z80_init(&mut m.cpu);
+        m.memory = [0; 0x10000];
+        m.memory[0x1000..0x1000 + bytes.len()].copy_from_slice(&bytes);
+        m.cpu.pc = 0x1000;
+        m.cpu.sp = 0x8000;
+        z80_step(&mut m.cpu);
+        assert_eq!(m.cpu.pc, 0x1000 + len, "{:02x?} {}", bytes, text);
+    }
+}
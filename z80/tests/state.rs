@@ -0,0 +1,81 @@
+#![allow(non_camel_case_types)]
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+
+// Checks z80_save_state and z80_load_state: a cpu restored from a state
+// saved in the middle of a program carries on exactly as the one it was
+// saved from.
+
+mod common;
+use common::*;
+
+// sums the bytes of 0x0100-0x01ff with the alternate registers and ix,
+// enabling interrupts on the way
+const program: &str = "
+        ld sp,0x8000
+        im 1
+        ei
+        ld hl,0x0100
+        ld b,0
+loop:   add a,(hl)
+        inc hl
+        exx
+        adc a,a
+        exx
+        inc ix
+        djnz loop
+        halt
+";
+
+// a machine with the program, and the bytes it sums
+fn program_machine() -> Box<machine> {
+    let mut m = new_machine(());
+    let code = &z80_asm(program).unwrap()[0].bytes;
+    m.memory[0..code.len()].copy_from_slice(code);
+    for i in 0..0x100 {
+        m.memory[0x100 + i] = (i * 7) as u8;
+    }
+    return m;
+}
+
+fn registers(z: &z80::z80) -> Vec<u64> {
+    return vec![
+        z.pc as u64, z.sp as u64, z.ix as u64, z.iy as u64, z.mem_ptr as u64, z.a as u64,
+        z.f as u64, z.b as u64, z.c as u64, z.h as u64, z.l as u64, z.a_ as u64, z.f_ as u64,
+        z.h_ as u64, z.l_ as u64, z.r as u64, z.q as u64, z.iff1 as u64, z.iff2 as u64,
+        z.interrupt_mode as u64, z.halted as u64, z.cyc,
+    ];
+}
+
+#[test]
+fn save_and_load() {
+    let mut m = program_machine();
+    for _ in 0..200 {
+        z80_step(&mut m.cpu);
+    }
+    let mut state: [u8; Z80_STATE_SIZE] = [0; Z80_STATE_SIZE];
+    z80_save_state(&m.cpu, &mut state);
+
+    // the memory is the board's: copied along with the state
+    let mut restored = program_machine();
+    restored.memory = m.memory;
+    assert_eq!(z80_load_state(&mut restored.cpu, &state), Ok(()));
+    assert_eq!(registers(&restored.cpu), registers(&m.cpu));
+
+    for _ in 0..2000 {
+        z80_step(&mut m.cpu);
+        z80_step(&mut restored.cpu);
+        assert_eq!(registers(&restored.cpu), registers(&m.cpu));
+    }
+    assert!(m.cpu.halted);
+}
+
+#[test]
+fn bad_version() {
+    let mut m = program_machine();
+    let state: [u8; Z80_STATE_SIZE] = [0xff; Z80_STATE_SIZE];
+    assert_eq!(
+        z80_load_state(&mut m.cpu, &state),
+        Err(state_error::BadVersion(0xff))
+    );
+}
@@ -0,0 +1,160 @@
+#![allow(non_camel_case_types)]
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+
+// Checks undocumented behaviours of the core that zexall doesn't see: xf and
+// yf of scf/ccf, which depend on whether the previous instruction set the
+// flags (the q latch), and the flags of a repeating block instruction
+// interrupted before its next iteration, which come from pc.
+
+mod common;
+use common::*;
+
+// the value read from any port
+fn test_port_in(z: &mut z80::z80<u8>, _port: u8) -> u8 {
+    return unsafe { (*z.userdata.machine_ptr).test };
+}
+
+fn test_port_out(_z: &mut z80::z80<u8>, _port: u8, _val: u8) {}
+
+// a machine with "bytes" at "addr", where pc is
+fn load(addr: u16, bytes: &[u8]) -> Box<machine<u8>> {
+    let mut m = new_machine(0xff);
+    m.cpu.port_in = test_port_in;
+    m.cpu.port_out = test_port_out;
+    m.memory[addr as usize..addr as usize + bytes.len()].copy_from_slice(bytes);
+    m.cpu.pc = addr;
+    m.cpu.sp = 0x8000;
+    m.cpu.a = 0;
+    m.cpu.f = 0;
+    return m;
+}
+
+// MARK: q latch
+
+// f after running "program", from a = 0 and f = 0
+fn flags_after(program: &[u8]) -> u8 {
+    let mut m = load(0x1000, program);
+    for _ in 0..program.len() {
+        if m.cpu.pc >= 0x1000 + program.len() as u16 {
+            break;
+        }
+        z80_step(&mut m.cpu);
+    }
+    return m.cpu.f;
+}
+
+#[test]
+fn scf_ccf() {
+    // "cp 0x28" from a = 0 sets s, yf, h, xf, n and c: f = 0xbb
+    let cases: [(&[u8], u8, &str); 7] = [
+        (&[0xfe, 0x28], 0xbb, "cp 0x28"),
+        // the flags were just set: xf and yf come from a only
+        (&[0xfe, 0x28, 0x37], 0x81, "cp 0x28; scf"),
+        (&[0xfe, 0x28, 0x3f], 0x90, "cp 0x28; ccf"),
+        // they were not: xf and yf of f are kept
+        (&[0xfe, 0x28, 0x00, 0x37], 0xa9, "cp 0x28; nop; scf"),
+        (&[0xfe, 0x28, 0x00, 0x3f], 0xb8, "cp 0x28; nop; ccf"),
+        (&[0xfe, 0x28, 0x06, 0x00, 0x37], 0xa9, "cp 0x28; ld b,0; scf"),
+        // scf sets the flags itself
+        (&[0xfe, 0x28, 0x00, 0x37, 0x37], 0x81, "cp 0x28; nop; scf; scf"),
+    ];
+    for (program, f, text) in cases {
+        assert_eq!(flags_after(program), f, "{}", text);
+    }
+}
+
+#[test]
+fn scf_from_a() {
+    // xf and yf of a always go to f
+    let mut m = load(0x1000, &[0x37]);
+    m.cpu.a = 0x28;
+    z80_step(&mut m.cpu);
+    assert_eq!(m.cpu.f, 0x29);
+}
+
+// MARK: interrupted block instructions
+
+// runs one iteration of the block instruction at "addr", with the irq line
+// asserted: an interrupt (im 1) is taken before the next. Returns f and the
+// address the interrupt returns to.
+fn interrupt_block(m: &mut machine<u8>, addr: u16) -> (u8, u16) {
+    m.cpu.interrupt_mode = 1;
+    m.cpu.iff1 = true;
+    m.cpu.iff2 = true;
+    m.cpu.pc = addr;
+    z80_assert_irq(&mut m.cpu);
+    z80_step(&mut m.cpu);
+    assert_eq!(m.cpu.pc, 0x0038);
+    z80_clear_irq(&mut m.cpu);
+    let ret = m.memory[m.cpu.sp as usize] as u16 | (m.memory[m.cpu.sp as usize + 1] as u16) << 8;
+    return (m.cpu.f, ret);
+}
+
+// runs the block instruction at "addr" to its last iteration, returning f
+fn last_iteration(m: &mut machine<u8>, addr: u16) -> u8 {
+    m.cpu.pc = addr;
+    while m.cpu.pc == addr {
+        z80_step(&mut m.cpu);
+    }
+    return m.cpu.f;
+}
+
+#[test]
+fn ldir() {
+    // ldir at 0x2800, copying zeroes: yf and xf come from pc (0x28) when
+    // it repeats, from a + the byte copied (0) when it is over
+    let mut m = load(0x2800, &[0xed, 0xb0]);
+    (m.cpu.b, m.cpu.c, m.cpu.h, m.cpu.l, m.cpu.d, m.cpu.e) = (0x00, 0x03, 0x40, 0x00, 0x50, 0x00);
+    assert_eq!(interrupt_block(&mut m, 0x2800), (0x2c, 0x2800));
+    assert_eq!(last_iteration(&mut m, 0x2800), 0x00);
+
+    // at 0x1000, pc has neither bit
+    let mut m = load(0x1000, &[0xed, 0xb0]);
+    (m.cpu.b, m.cpu.c, m.cpu.h, m.cpu.l, m.cpu.d, m.cpu.e) = (0x00, 0x03, 0x40, 0x00, 0x50, 0x00);
+    assert_eq!(interrupt_block(&mut m, 0x1000).0, 0x04);
+}
+
+#[test]
+fn cpir() {
+    // a = 0x10 compared with 0x01: a - byte - h = 0x0e sets yf and xf when
+    // it is over, pc (0x10) clears them when it repeats
+    let mut m = load(0x1000, &[0xed, 0xb1]);
+    m.cpu.a = 0x10;
+    (m.cpu.b, m.cpu.c, m.cpu.h, m.cpu.l) = (0x00, 0x02, 0x40, 0x00);
+    m.memory[0x4000] = 0x01;
+    m.memory[0x4001] = 0x01;
+    assert_eq!(interrupt_block(&mut m, 0x1000), (0x16, 0x1000));
+    assert_eq!(last_iteration(&mut m, 0x1000), 0x3a);
+}
+
+#[test]
+fn inir() {
+    // reading 0x80 from port 0x10, b = 2: n set, no carry. pf is
+    // parity(((0x80 + 0x11) & 7) ^ b) when it is over, inverted by
+    // parity(b & 7) when it repeats.
+    let mut m = load(0x1000, &[0xed, 0xb2]);
+    (m.cpu.b, m.cpu.c, m.cpu.h, m.cpu.l) = (0x02, 0x10, 0x40, 0x00);
+    m.test = 0x80;
+    assert_eq!(interrupt_block(&mut m, 0x1000), (0x02, 0x1000));
+    assert_eq!(last_iteration(&mut m, 0x1000), 0x42);
+
+    // reading 0xff: carry. hf is cleared as b & 0xf isn't 0, and pf is
+    // inverted by parity((b - 1) & 7).
+    let mut m = load(0x1000, &[0xed, 0xb2]);
+    (m.cpu.b, m.cpu.c, m.cpu.h, m.cpu.l) = (0x02, 0x10, 0x40, 0x00);
+    m.test = 0xff;
+    assert_eq!(interrupt_block(&mut m, 0x1000).0, 0x03);
+}
+
+#[test]
+fn otir() {
+    // writing 0x01 at 0x2800, b = 2: yf and xf come from pc, and pf,
+    // parity(((byte + l) & 7) ^ b) = 1, is inverted by parity(b & 7)
+    let mut m = load(0x2800, &[0xed, 0xb3]);
+    (m.cpu.b, m.cpu.c, m.cpu.h, m.cpu.l) = (0x02, 0x10, 0x40, 0x00);
+    m.memory[0x4000] = 0x01;
+    m.memory[0x4001] = 0x01;
+    assert_eq!(interrupt_block(&mut m, 0x2800), (0x28, 0x2800));
+    assert_eq!(last_iteration(&mut m, 0x2800), 0x44);
+}
@@ -0,0 +1,120 @@
+#![allow(non_camel_case_types)]
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+
+// Checks the wait states hook of the z80 core: a program whose cycle count is
+// known from the timing tables takes exactly as long with no hook or with a
+// hook adding nothing, and the wait states a hook returns are added to it.
+
+mod common;
+use common::*;
+
+// wait states of each kind of access, and the accesses seen
+pub struct bus_log {
+    pub waits: fn(access: bus_access, addr: u16) -> u32,
+    pub accesses: Vec<(bus_access, u16)>,
+}
+
+// exercises every kind of bus access: opcode fetches (prefixed too), memory
+// reads and writes, io, the stack and a block instruction
+const program: [u8; 37] = [
+    0x31, 0x00, 0x80, // ld sp,0x8000      10
+    0x3e, 0x12, // ld a,0x12               7
+    0xd3, 0x10, // out (0x10),a            11
+    0xdb, 0x10, // in a,(0x10)             11
+    0x21, 0x00, 0x40, // ld hl,0x4000      10
+    0x77, // ld (hl),a                     7
+    0xcd, 0x20, 0x00, // call 0x0020       17
+    0x01, 0x03, 0x00, // ld bc,3           10
+    0x11, 0x00, 0x50, // ld de,0x5000      10
+    0xed, 0xb0, // ldir                    21 + 21 + 16
+    0xdd, 0x21, 0x00, 0x40, // ld ix,0x4000 14
+    0xdd, 0x34, 0x01, // inc (ix+1)        23
+    0x76, // halt                          4
+    0xc5, // 0x0020: push bc               11
+    0xed, 0x78, // in a,(c)                12
+    0xc1, // pop bc                        10
+    0xc9, // ret                           10
+];
+const program_cycles: u64 = 235;
+
+// opcode fetches, and accesses to 0x4000-0x5fff or to an io port, made by
+// the program
+const program_fetches: u64 = 25;
+const program_slow_accesses: u64 = 9;
+const program_io_accesses: u64 = 3;
+
+fn test_port_in(_z: &mut z80::z80<bus_log>, _port: u8) -> u8 {
+    return 0xff;
+}
+
+fn test_port_out(_z: &mut z80::z80<bus_log>, _port: u8, _val: u8) {}
+
+fn test_wait_states(z: &mut z80::z80<bus_log>, access: bus_access, addr: u16) -> u32 {
+    let log = unsafe { &mut (*z.userdata.machine_ptr).test };
+    log.accesses.push((access, addr));
+    return (log.waits)(access, addr);
+}
+
+fn no_waits(_access: bus_access, _addr: u16) -> u32 {
+    return 0;
+}
+
+// runs the program until it halts, returning the cycle count and the
+// accesses seen by the hook
+fn run(hook: bool, waits: fn(bus_access, u16) -> u32) -> (u64, Vec<(bus_access, u16)>) {
+    let mut m = new_machine(bus_log {
+        waits,
+        accesses: Vec::new(),
+    });
+    m.cpu.port_in = test_port_in;
+    m.cpu.port_out = test_port_out;
+    if hook {
+        m.cpu.wait_states = Some(test_wait_states);
+    }
+    m.memory[0..program.len()].copy_from_slice(&program);
+
+    while !m.cpu.halted {
+        z80_step(&mut m.cpu);
+    }
+    return (m.cpu.cyc, std::mem::take(&mut m.test.accesses));
+}
+
+#[test]
+fn no_hook() {
+    let (cyc, _) = run(false, no_waits);
+    assert_eq!(cyc, program_cycles);
+}
+
+#[test]
+fn hook_without_waits() {
+    let (cyc, accesses) = run(true, no_waits);
+    assert_eq!(cyc, program_cycles);
+
+    let count = |kind: bus_access| accesses.iter().filter(|(a, _)| *a == kind).count() as u64;
+    assert_eq!(count(bus_access::Opcode), program_fetches);
+    assert_eq!(count(bus_access::IoRead) + count(bus_access::IoWrite), program_io_accesses);
+    assert!(count(bus_access::MemRead) > 0);
+    assert!(count(bus_access::MemWrite) > 0);
+}
+
+#[test]
+fn waits_on_opcode_fetches() {
+    let (cyc, _) = run(true, |access, _| (access == bus_access::Opcode) as u32);
+    assert_eq!(cyc, program_cycles + program_fetches);
+}
+
+#[test]
+fn waits_on_slow_memory_and_io() {
+    // 1 wait state for data accesses to 0x4000-0x5fff (as video ram
+    // contention), 2 for io
+    let (cyc, _) = run(true, |access, addr| match access {
+        bus_access::MemRead | bus_access::MemWrite if addr >= 0x4000 && addr < 0x6000 => 1,
+        bus_access::IoRead | bus_access::IoWrite => 2,
+        _ => 0,
+    });
+    assert_eq!(
+        cyc,
+        program_cycles + program_slow_accesses + 2 * program_io_accesses
+    );
+}
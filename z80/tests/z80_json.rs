@@ -0,0 +1,387 @@
+#![allow(non_camel_case_types)]
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+
+// Checks the z80 core against per-instruction test vectors, in the JSON
+// format of the SingleStepTests z80 suite: for every opcode (including the
+// CB, ED, DD/FD and DDCB/FDCB ones), a file of tests giving the registers and
+// ram before and after the instruction, and its cycles. Each test runs one
+// z80_step, which dispatches to exec_opcode and the prefixed opcode functions.
+// Registers are compared with mem_ptr (wz), r and the undocumented flags.
+//
+// The vectors are not part of the repository: copy the .json files to
+// tests/z80_json, or to the directory given in Z80_JSON_DIR, then run the
+// test with "cargo test -- --ignored". It fails if there are none.
+
+mod common;
+use common::*;
+
+// the values read by the io reads of a test, in order
+pub struct port_reads {
+    pub reads: Vec<u8>,
+    pub pos: usize,
+}
+
+fn test_port_in(z: &mut z80::z80<port_reads>, _port: u8) -> u8 {
+    let ports = unsafe { &mut (*z.userdata.machine_ptr).test };
+    let val = ports.reads.get(ports.pos).copied().unwrap_or(0xff);
+    ports.pos += 1;
+    return val;
+}
+
+fn test_port_out(_z: &mut z80::z80<port_reads>, _port: u8, _val: u8) {}
+
+// MARK: json
+
+pub enum json {
+    Null,
+    Bool(bool),
+    Num(f64),
+    Str(String),
+    Arr(Vec<json>),
+    Obj(Vec<(String, json)>),
+}
+
+impl json {
+    pub fn get(&self, key: &str) -> Option<&json> {
+        match self {
+            json::Obj(fields) => fields.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn num(&self) -> u64 {
+        match self {
+            json::Num(n) => *n as u64,
+            json::Bool(b) => *b as u64,
+            _ => 0,
+        }
+    }
+
+    pub fn arr(&self) -> &[json] {
+        match self {
+            json::Arr(items) => items,
+            _ => &[],
+        }
+    }
+
+    pub fn str(&self) -> &str {
+        match self {
+            json::Str(s) => s,
+            _ => "",
+        }
+    }
+}
+
+struct json_parser<'a> {
+    s: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> json_parser<'a> {
+    fn skip_ws(&mut self) {
+        while self.pos < self.s.len() && self.s[self.pos].is_ascii_whitespace() {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, c: u8) -> Result<(), String> {
+        self.skip_ws();
+        if self.pos < self.s.len() && self.s[self.pos] == c {
+            self.pos += 1;
+            return Ok(());
+        }
+        return Err(format!("expected '{}' at {}", c as char, self.pos));
+    }
+
+    fn value(&mut self) -> Result<json, String> {
+        self.skip_ws();
+        if self.pos >= self.s.len() {
+            return Err("unexpected end".to_string());
+        }
+        match self.s[self.pos] {
+            b'{' => {
+                self.pos += 1;
+                let mut fields = Vec::new();
+                self.skip_ws();
+                if self.s.get(self.pos) == Some(&b'}') {
+                    self.pos += 1;
+                    return Ok(json::Obj(fields));
+                }
+                loop {
+                    self.skip_ws();
+                    let key = match self.value()? {
+                        json::Str(key) => key,
+                        _ => return Err(format!("expected a key at {}", self.pos)),
+                    };
+                    self.expect(b':')?;
+                    fields.push((key, self.value()?));
+                    self.skip_ws();
+                    match self.s.get(self.pos) {
+                        Some(b',') => self.pos += 1,
+                        Some(b'}') => {
+                            self.pos += 1;
+                            return Ok(json::Obj(fields));
+                        }
+                        _ => return Err(format!("expected ',' or '}}' at {}", self.pos)),
+                    }
+                }
+            }
+            b'[' => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                self.skip_ws();
+                if self.s.get(self.pos) == Some(&b']') {
+                    self.pos += 1;
+                    return Ok(json::Arr(items));
+                }
+                loop {
+                    items.push(self.value()?);
+                    self.skip_ws();
+                    match self.s.get(self.pos) {
+                        Some(b',') => self.pos += 1,
+                        Some(b']') => {
+                            self.pos += 1;
+                            return Ok(json::Arr(items));
+                        }
+                        _ => return Err(format!("expected ',' or ']' at {}", self.pos)),
+                    }
+                }
+            }
+            b'"' => {
+                // the vectors only hold plain ascii strings
+                self.pos += 1;
+                let start = self.pos;
+                while self.pos < self.s.len() && self.s[self.pos] != b'"' {
+                    if self.s[self.pos] == b'\\' {
+                        self.pos += 1;
+                    }
+                    self.pos += 1;
+                }
+                let s = String::from_utf8_lossy(&self.s[start..self.pos]).to_string();
+                self.pos += 1;
+                return Ok(json::Str(s));
+            }
+            b't' | b'f' | b'n' => {
+                for (word, val) in [("true", json::Bool(true)), ("false", json::Bool(false)), ("null", json::Null)] {
+                    if self.s[self.pos..].starts_with(word.as_bytes()) {
+                        self.pos += word.len();
+                        return Ok(val);
+                    }
+                }
+                return Err(format!("unexpected word at {}", self.pos));
+            }
+            _ => {
+                let start = self.pos;
+                while self.pos < self.s.len() && b"+-.eE0123456789".contains(&self.s[self.pos]) {
+                    self.pos += 1;
+                }
+                let text = String::from_utf8_lossy(&self.s[start..self.pos]).to_string();
+                return text
+                    .parse::<f64>()
+                    .map(json::Num)
+                    .map_err(|_| format!("bad number at {}", start));
+            }
+        }
+    }
+}
+
+pub fn json_parse(s: &[u8]) -> Result<json, String> {
+    let mut parser = json_parser { s, pos: 0 };
+    return parser.value();
+}
+
+// MARK: test runner
+
+fn set_state(m: &mut machine<port_reads>, state: &json) {
+    let v = |key: &str| state.get(key).map(|v| v.num()).unwrap_or(0);
+    let z = &mut m.cpu;
+
+    z.pc = v("pc") as u16;
+    z.sp = v("sp") as u16;
+    z.a = v("a") as u8;
+    set_f(z, v("f") as u8);
+    z.b = v("b") as u8;
+    z.c = v("c") as u8;
+    z.d = v("d") as u8;
+    z.e = v("e") as u8;
+    z.h = v("h") as u8;
+    z.l = v("l") as u8;
+    z.i = v("i") as u8;
+    z.r = v("r") as u8;
+    z.mem_ptr = v("wz") as u16;
+    z.ix = v("ix") as u16;
+    z.iy = v("iy") as u16;
+    z.a_ = (v("af_") >> 8) as u8;
+    z.f_ = v("af_") as u8;
+    z.b_ = (v("bc_") >> 8) as u8;
+    z.c_ = v("bc_") as u8;
+    z.d_ = (v("de_") >> 8) as u8;
+    z.e_ = v("de_") as u8;
+    z.h_ = (v("hl_") >> 8) as u8;
+    z.l_ = v("hl_") as u8;
+    z.interrupt_mode = v("im") as u8;
+    z.iff1 = v("iff1") != 0;
+    z.iff2 = v("iff2") != 0;
+    z.q = v("q") as u8;
+    z.iff_delay = 0;
+    z.halted = false;
+    z.irq_line = false;
+    z.nmi_pending = false;
+
+    for cell in state.get("ram").map(|r| r.arr()).unwrap_or(&[]) {
+        let cell = cell.arr();
+        m.memory[cell[0].num() as usize] = cell[1].num() as u8;
+    }
+}
+
+// compares the cpu and ram with the expected state. Returns the differences.
+fn compare_state(m: &mut machine<port_reads>, state: &json) -> Vec<String> {
+    let z = &mut m.cpu;
+    let got: [(&str, u64); 23] = [
+        ("pc", z.pc as u64),
+        ("sp", z.sp as u64),
+        ("a", z.a as u64),
+        ("f", get_f(z) as u64),
+        ("b", z.b as u64),
+        ("c", z.c as u64),
+        ("d", z.d as u64),
+        ("e", z.e as u64),
+        ("h", z.h as u64),
+        ("l", z.l as u64),
+        ("i", z.i as u64),
+        ("r", z.r as u64),
+        ("wz", z.mem_ptr as u64),
+        ("ix", z.ix as u64),
+        ("iy", z.iy as u64),
+        ("af_", (z.a_ as u64) << 8 | z.f_ as u64),
+        ("bc_", (z.b_ as u64) << 8 | z.c_ as u64),
+        ("de_", (z.d_ as u64) << 8 | z.e_ as u64),
+        ("hl_", (z.h_ as u64) << 8 | z.l_ as u64),
+        ("im", z.interrupt_mode as u64),
+        ("iff1", z.iff1 as u64),
+        ("iff2", z.iff2 as u64),
+        ("q", z.q as u64),
+    ];
+
+    let mut diffs: Vec<String> = Vec::new();
+    for (key, val) in got {
+        if let Some(expected) = state.get(key) {
+            if expected.num() != val {
+                diffs.push(format!("{} {:x} (expected {:x})", key, val, expected.num()));
+            }
+        }
+    }
+    for cell in state.get("ram").map(|r| r.arr()).unwrap_or(&[]) {
+        let cell = cell.arr();
+        let (addr, expected) = (cell[0].num() as usize, cell[1].num() as u8);
+        if m.memory[addr] != expected {
+            diffs.push(format!(
+                "ram[{:04x}] {:02x} (expected {:02x})",
+                addr, m.memory[addr], expected
+            ));
+        }
+    }
+    return diffs;
+}
+
+// runs a test. Returns the differences with the expected state.
+fn run_test(m: &mut machine<port_reads>, test: &json) -> Vec<String> {
+    let initial = test.get("initial").unwrap();
+    let fin = test.get("final").unwrap();
+
+    set_state(m, initial);
+    m.test.reads = test
+        .get("ports")
+        .map(|p| p.arr())
+        .unwrap_or(&[])
+        .iter()
+        .filter(|p| p.arr()[2].str() == "r")
+        .map(|p| p.arr()[1].num() as u8)
+        .collect();
+    m.test.pos = 0;
+    m.cpu.cyc = 0;
+
+    z80_step(&mut m.cpu);
+
+    let mut diffs = compare_state(m, fin);
+    let cycles = test.get("cycles").map(|c| c.arr().len()).unwrap_or(0) as u64;
+    if m.cpu.cyc != cycles {
+        diffs.push(format!("cycles {} (expected {})", m.cpu.cyc, cycles));
+    }
+
+    // clearing the ram for the next test
+    for state in [initial, fin] {
+        for cell in state.get("ram").map(|r| r.arr()).unwrap_or(&[]) {
+            m.memory[cell.arr()[0].num() as usize] = 0;
+        }
+    }
+    return diffs;
+}
+
+#[test]
+#[ignore = "needs the json test vectors, see the README"]
+fn z80_json_vectors() {
+    let dir = std::env::var("Z80_JSON_DIR")
+        .unwrap_or(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/z80_json").to_string());
+    let mut files: Vec<String> = match std::fs::read_dir(&dir) {
+        Ok(entries) => entries
+            .flatten()
+            .map(|e| e.path().to_string_lossy().to_string())
+            .filter(|path| path.ends_with(".json"))
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    assert!(!files.is_empty(), "no test vectors in {}", dir);
+    files.sort();
+
+    let mut m = new_machine(port_reads {
+        reads: Vec::new(),
+        pos: 0,
+    });
+    m.cpu.port_in = test_port_in;
+    m.cpu.port_out = test_port_out;
+
+    // one line per failing opcode, with its first failing test
+    let mut failures: Vec<String> = Vec::new();
+    let mut nb_tests: usize = 0;
+    for path in &files {
+        let data = std::fs::read(path).unwrap();
+        let tests = match json_parse(&data) {
+            Ok(tests) => tests,
+            Err(e) => panic!("{}: {}", path, e),
+        };
+
+        let mut nb_failed: usize = 0;
+        let mut first_failure = String::new();
+        for test in tests.arr() {
+            nb_tests += 1;
+            let diffs = run_test(&mut m, test);
+            if !diffs.is_empty() {
+                if nb_failed == 0 {
+                    let name = test.get("name").map(|n| n.str()).unwrap_or("");
+                    first_failure = format!("{}: {}", name, diffs.join(", "));
+                }
+                nb_failed += 1;
+            }
+        }
+        if nb_failed > 0 {
+            let file_name = path.rsplit('/').next().unwrap_or(path);
+            failures.push(format!(
+                "{}: {} of {} failed, first is {}",
+                file_name,
+                nb_failed,
+                tests.arr().len(),
+                first_failure
+            ));
+        }
+    }
+
+    println!("{} tests in {} files", nb_tests, files.len());
+    assert!(
+        failures.is_empty(),
+        "{} opcodes failing:\n{}",
+        failures.len(),
+        failures.join("\n")
+    );
+}
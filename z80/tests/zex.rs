@@ -0,0 +1,129 @@
+#![allow(non_camel_case_types)]
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+
+// Runs the ZEXDOC and ZEXALL instruction exercisers (by Frank Cringle) on the
+// z80 core. ZEXDOC checks the documented flags, ZEXALL the undocumented ones
+// too. They are CP/M programs: the only BDOS calls they make, console output
+// and string print, are served by a small shim.
+//
+// The .COM files are not part of the repository: copy "zexdoc.com" and
+// "zexall.com" to tests/zex, or to the directory given in ZEX_DIR, then run
+// the tests with "cargo test --release -- --ignored" (each exerciser runs for
+// several billions of cycles). A test fails if its file is missing.
+
+use std::io::Write;
+
+mod common;
+use common::*;
+
+// what the program printed, and if it is over
+pub struct console {
+    pub output: String,
+    pub done: bool,
+}
+
+fn zex_port_in(_z: &mut z80::z80<console>, _port: u8) -> u8 {
+    return 0xff;
+}
+
+// port 0: BDOS call, port 1: warm boot (end of the program)
+fn zex_port_out(z: &mut z80::z80<console>, port: u8, _val: u8) {
+    let m = unsafe { &mut *z.userdata.machine_ptr };
+    if port != 0 {
+        m.test.done = true;
+        return;
+    }
+
+    let mut printed = String::new();
+    match z.c {
+        2 => printed.push(z.e as char),
+        9 => {
+            let mut addr = get_de(z);
+            while m.memory[addr as usize] != b'$' {
+                printed.push(m.memory[addr as usize] as char);
+                addr = addr.wrapping_add(1);
+            }
+        }
+        _ => {}
+    }
+
+    // progress is shown with "cargo test -- --nocapture"
+    print!("{}", printed);
+    let _ = std::io::stdout().flush();
+    m.test.output.push_str(&printed);
+}
+
+// runs a CP/M program, returning what it printed. None if it is missing.
+fn run_zex(file_name: &str) -> Option<String> {
+    let dir = std::env::var("ZEX_DIR")
+        .unwrap_or(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/zex").to_string());
+    let program = std::fs::read(format!("{}/{}", dir, file_name)).ok()?;
+
+    let mut m = new_machine(console {
+        output: String::new(),
+        done: false,
+    });
+    m.cpu.port_in = zex_port_in;
+    m.cpu.port_out = zex_port_out;
+
+    // warm boot at 0, BDOS entry at 5, jumping to "out (0),a" then "ret".
+    // The exercisers put their stack below the BDOS, whose address is the
+    // word at 6.
+    m.memory[0x0000] = 0xd3; // out (1),a
+    m.memory[0x0001] = 0x01;
+    m.memory[0x0005] = 0xc3; // jp 0xfe00
+    m.memory[0x0006] = 0x00;
+    m.memory[0x0007] = 0xfe;
+    m.memory[0xfe00] = 0xd3; // out (0),a
+    m.memory[0xfe01] = 0x00;
+    m.memory[0xfe02] = 0xc9; // ret
+    m.memory[0x0100..0x0100 + program.len()].copy_from_slice(&program);
+
+    m.cpu.pc = 0x0100;
+    m.cpu.sp = 0xfdfe; // returns to the warm boot
+    while !m.test.done {
+        z80_step(&mut m.cpu);
+    }
+    return Some(std::mem::take(&mut m.test.output));
+}
+
+fn check_zex(file_name: &str) {
+    let output = match run_zex(file_name) {
+        Some(output) => output,
+        None => panic!("{} not found", file_name),
+    };
+
+    // each group prints "<name>....  OK", or "<name>....  ERROR **** crc..."
+    let failed: Vec<&str> = output
+        .lines()
+        .filter(|line| line.contains("ERROR"))
+        .map(|line| line.split("....").next().unwrap_or(line).trim())
+        .collect();
+
+    assert!(
+        failed.is_empty(),
+        "{}: {} failing groups: {}",
+        file_name,
+        failed.len(),
+        failed.join(", ")
+    );
+    assert!(
+        output.contains("Tests complete"),
+        "{} did not complete:\n{}",
+        file_name,
+        output
+    );
+}
+
+#[test]
+#[ignore = "needs zexdoc.com, see the README"]
+fn zexdoc() {
+    check_zex("zexdoc.com");
+}
+
+#[test]
+#[ignore = "needs zexall.com, see the README"]
+fn zexall() {
+    check_zex("zexall.com");
+}